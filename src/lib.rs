@@ -1,4 +1,5 @@
 pub mod raydium;
 pub mod spl;
 pub mod pumpfun;
+pub mod router;
 pub mod utils;
\ No newline at end of file
@@ -0,0 +1,224 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use super::pda::{get_bonding_curve_pda, get_global_pda};
+
+// anchor discriminator: sha256("account:BondingCurve")[..8]
+pub const BONDING_CURVE_DISCRIMINATOR: [u8; 8] = [23, 183, 248, 55, 96, 216, 172, 96];
+// anchor discriminator: sha256("account:Global")[..8]
+pub const GLOBAL_DISCRIMINATOR: [u8; 8] = [167, 232, 232, 177, 200, 108, 114, 127];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Global {
+    pub initialized: bool,
+    pub authority: Pubkey,
+    /// 手续费接收账户
+    pub fee_recipient: Pubkey,
+    pub initial_virtual_token_reserves: u64,
+    pub initial_virtual_sol_reserves: u64,
+    pub initial_real_token_reserves: u64,
+    pub token_total_supply: u64,
+    /// 交易手续费, 按 SOL 数量收取 (100 即 1%)
+    pub fee_basis_points: u64,
+}
+
+impl Global {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        if data.len() < 8 + 1 + 32 * 2 + 8 * 5 {
+            return Err(anyhow!("global account data too short"));
+        }
+        if data[..8] != GLOBAL_DISCRIMINATOR {
+            return Err(anyhow!("not a pump.fun global account"));
+        }
+        let read_u64 = |offset: usize| -> Result<u64> {
+            Ok(u64::from_le_bytes(data[offset..offset + 8].try_into()?))
+        };
+        Ok(Self {
+            initialized: data[8] != 0,
+            authority: Pubkey::try_from(&data[9..41])?,
+            fee_recipient: Pubkey::try_from(&data[41..73])?,
+            initial_virtual_token_reserves: read_u64(73)?,
+            initial_virtual_sol_reserves: read_u64(81)?,
+            initial_real_token_reserves: read_u64(89)?,
+            token_total_supply: read_u64(97)?,
+            fee_basis_points: read_u64(105)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BondingCurve {
+    /// 虚拟代币储备
+    pub virtual_token_reserves: u64,
+    /// 虚拟 SOL 储备
+    pub virtual_sol_reserves: u64,
+    /// 实际代币储备
+    pub real_token_reserves: u64,
+    /// 实际 SOL 储备
+    pub real_sol_reserves: u64,
+    /// 代币总供应量
+    pub token_total_supply: u64,
+    /// 曲线是否已经完成 (完成后迁移到 raydium, 不能再通过 pump.fun 买卖)
+    pub complete: bool,
+}
+
+impl BondingCurve {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        if data.len() < 8 + 8 * 5 + 1 {
+            return Err(anyhow!("bonding curve account data too short"));
+        }
+        if data[..8] != BONDING_CURVE_DISCRIMINATOR {
+            return Err(anyhow!("not a bonding curve account"));
+        }
+        let read_u64 = |offset: usize| -> Result<u64> {
+            Ok(u64::from_le_bytes(data[offset..offset + 8].try_into()?))
+        };
+        Ok(Self {
+            virtual_token_reserves: read_u64(8)?,
+            virtual_sol_reserves: read_u64(16)?,
+            real_token_reserves: read_u64(24)?,
+            real_sol_reserves: read_u64(32)?,
+            token_total_supply: read_u64(40)?,
+            complete: data[48] != 0,
+        })
+    }
+
+    // 买入 amount 个代币需要支付的 SOL (含手续费), 按虚拟储备 x * y = k 向上取整
+    // fee_basis_points 取自 Global 账户
+    pub fn buy_cost(&self, amount: u64, fee_basis_points: u64) -> Result<u64> {
+        if self.complete {
+            return Err(anyhow!("bonding curve is complete"));
+        }
+        if amount > self.real_token_reserves {
+            return Err(anyhow!("amount exceeds bonding curve token reserves"));
+        }
+        let virtual_token_reserves = u128::from(self.virtual_token_reserves);
+        let numerator = u128::from(amount) * u128::from(self.virtual_sol_reserves);
+        let denominator = virtual_token_reserves - u128::from(amount);
+        let sol_cost = numerator.div_ceil(denominator);
+        let fee = sol_cost * u128::from(fee_basis_points) / 10_000;
+        Ok(u64::try_from(sol_cost + fee)?)
+    }
+
+    // 花费 sol_amount (含手续费) 最多能买到的代币数量
+    pub fn buy_quote(&self, sol_amount: u64, fee_basis_points: u64) -> Result<u64> {
+        if self.complete {
+            return Err(anyhow!("bonding curve is complete"));
+        }
+        let sol_after_fee =
+            u128::from(sol_amount) * 10_000 / (10_000 + u128::from(fee_basis_points));
+        let tokens = u128::from(self.virtual_token_reserves) * sol_after_fee
+            / (u128::from(self.virtual_sol_reserves) + sol_after_fee);
+        let mut tokens = u64::try_from(tokens)?.min(self.real_token_reserves);
+        // 向上取整的成本可能比 sol_amount 多一点, 逐个减少直到买得起
+        while tokens > 0 && self.buy_cost(tokens, fee_basis_points)? > sol_amount {
+            tokens -= 1;
+        }
+        Ok(tokens)
+    }
+}
+
+pub fn get_bonding_curve(client: &RpcClient, mint: &Pubkey) -> Result<BondingCurve> {
+    let data = client.get_account_data(&get_bonding_curve_pda(mint))?;
+    BondingCurve::from_account_data(&data)
+}
+
+pub fn get_global(client: &RpcClient) -> Result<Global> {
+    let data = client.get_account_data(&get_global_pda())?;
+    Global::from_account_data(&data)
+}
+
+pub fn is_bonding_curve_complete(client: &RpcClient, mint: &Pubkey) -> Result<bool> {
+    Ok(get_bonding_curve(client, mint)?.complete)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_bonding_curve() {
+        let curve = BondingCurve {
+            virtual_token_reserves: 1_073_000_000_000_000,
+            virtual_sol_reserves: 30_000_000_000,
+            real_token_reserves: 793_100_000_000_000,
+            real_sol_reserves: 0,
+            token_total_supply: 1_000_000_000_000_000,
+            complete: true,
+        };
+        let mut data = BONDING_CURVE_DISCRIMINATOR.to_vec();
+        for value in [
+            curve.virtual_token_reserves,
+            curve.virtual_sol_reserves,
+            curve.real_token_reserves,
+            curve.real_sol_reserves,
+            curve.token_total_supply,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.push(1);
+        assert_eq!(BondingCurve::from_account_data(&data).unwrap(), curve);
+        assert!(BondingCurve::from_account_data(&data[..40]).is_err());
+    }
+
+    #[test]
+    fn test_buy_quote() {
+        let curve = BondingCurve {
+            virtual_token_reserves: 1_073_000_000_000_000,
+            virtual_sol_reserves: 30_000_000_000,
+            real_token_reserves: 793_100_000_000_000,
+            real_sol_reserves: 0,
+            token_total_supply: 1_000_000_000_000_000,
+            complete: false,
+        };
+        let tokens = curve.buy_quote(1_000_000_000, 100).unwrap();
+        assert!(curve.buy_cost(tokens, 100).unwrap() <= 1_000_000_000);
+        // 1 个代币 (6 位小数) 约 28 lamports, 多买 1 个就超出预算
+        assert!(curve.buy_cost(tokens + 1_000_000, 100).unwrap() > 1_000_000_000);
+        assert!(curve.buy_cost(curve.real_token_reserves + 1, 100).is_err());
+
+        // 手续费越高, 同样的 SOL 买到的代币越少
+        let cost = curve.buy_cost(tokens, 0).unwrap();
+        assert_eq!(curve.buy_cost(tokens, 100).unwrap(), cost + cost / 100);
+        assert!(curve.buy_quote(1_000_000_000, 0).unwrap() > tokens);
+        assert!(curve.buy_quote(1_000_000_000, 200).unwrap() < tokens);
+        assert!(BondingCurve {
+            complete: true,
+            ..curve
+        }
+        .buy_quote(1, 100)
+        .is_err());
+    }
+
+    #[test]
+    fn test_decode_global() {
+        let global = Global {
+            initialized: true,
+            authority: Pubkey::new_unique(),
+            fee_recipient: Pubkey::new_unique(),
+            initial_virtual_token_reserves: 1_073_000_000_000_000,
+            initial_virtual_sol_reserves: 30_000_000_000,
+            initial_real_token_reserves: 793_100_000_000_000,
+            token_total_supply: 1_000_000_000_000_000,
+            fee_basis_points: 100,
+        };
+        let mut data = GLOBAL_DISCRIMINATOR.to_vec();
+        data.push(1);
+        data.extend_from_slice(global.authority.as_ref());
+        data.extend_from_slice(global.fee_recipient.as_ref());
+        for value in [
+            global.initial_virtual_token_reserves,
+            global.initial_virtual_sol_reserves,
+            global.initial_real_token_reserves,
+            global.token_total_supply,
+            global.fee_basis_points,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        assert_eq!(Global::from_account_data(&data).unwrap(), global);
+        assert!(Global::from_account_data(&data[..100]).is_err());
+        assert!(Global::from_account_data(&[0; 113]).is_err());
+    }
+}
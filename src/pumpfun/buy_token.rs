@@ -1,11 +1,18 @@
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-use bitcoin::base58;
-use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signer::Signer, system_instruction};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
 
 use derive_builder::Builder;
 
+use super::pda::{get_associated_bonding_curve, get_bonding_curve_pda};
+
+// anchor discriminator: sha256("global:buy")[..8]
+pub const BUY_DISCRIMINATOR: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+
 #[derive(Default, Builder, Debug)]
 pub struct BuyAccounts {
     /// 全局状态账户
@@ -60,6 +67,54 @@ pub struct BuyArgs {
     pub max_sol_cost: u64,
 }
 
+impl BuyAccounts {
+    // 根据代币和用户钱包推导出买入需要的所有账户
+    pub fn new(mint: &Pubkey, user: &Pubkey) -> Result<Self> {
+        let accounts = BuyAccountsBuilder::default()
+            .mint(mint.to_string())
+            .bonding_curve(get_bonding_curve_pda(mint).to_string())
+            .associated_bonding_curve(get_associated_bonding_curve(mint).to_string())
+            .associated_user(get_associated_token_address(user, mint).to_string())
+            .user(user.to_string())
+            .build()?;
+        Ok(accounts)
+    }
+
+    pub fn to_account_metas(&self) -> Result<Vec<AccountMeta>> {
+        Ok(vec![
+            AccountMeta::new_readonly(Pubkey::from_str(&self.global)?, false),
+            AccountMeta::new(Pubkey::from_str(&self.fee_recipient)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.mint)?, false),
+            AccountMeta::new(Pubkey::from_str(&self.bonding_curve)?, false),
+            AccountMeta::new(Pubkey::from_str(&self.associated_bonding_curve)?, false),
+            AccountMeta::new(Pubkey::from_str(&self.associated_user)?, false),
+            AccountMeta::new(Pubkey::from_str(&self.user)?, true),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.system_program)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.token_program)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.rent)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.event_authority)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.program)?, false),
+        ])
+    }
+}
+
+impl BuyArgs {
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = BUY_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&self.amount.to_le_bytes());
+        data.extend_from_slice(&self.max_sol_cost.to_le_bytes());
+        data
+    }
+}
+
+pub fn buy_instr(accounts: &BuyAccounts, args: &BuyArgs) -> Result<Instruction> {
+    Ok(Instruction {
+        program_id: Pubkey::from_str(&accounts.program)?,
+        accounts: accounts.to_account_metas()?,
+        data: args.data(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -69,4 +124,23 @@ mod tests {
         let accounts = BuyAccountsBuilder::default().build().unwrap();
         println!("{:?}", accounts);
     }
+
+    #[test]
+    fn test_buy_instr() {
+        let mint = Pubkey::from_str("CcQWG2M56Z1ESomovmzjvNPuDXBjHype7PoQPP2Zpump").unwrap();
+        let user = Pubkey::from_str("9WYirnyBy8RMBuoatC9yVCRQZ6AYpKpMyKmr2TrjypCG").unwrap();
+        let accounts = BuyAccounts::new(&mint, &user).unwrap();
+        assert_eq!(
+            accounts.bonding_curve,
+            "Ab4DiSUzi4tHLkE2W1k4W24mvmFoxsMvKCcpfNixNTJF"
+        );
+        let args = BuyArgs {
+            amount: 1_000_000,
+            max_sol_cost: 10_000,
+        };
+        let instruction = buy_instr(&accounts, &args).unwrap();
+        assert_eq!(instruction.accounts.len(), 12);
+        assert_eq!(instruction.data.len(), 24);
+        assert_eq!(instruction.data[..8], BUY_DISCRIMINATOR);
+    }
 }
@@ -0,0 +1,199 @@
+use anyhow::Result;
+use mpl_token_metadata::accounts::Metadata;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+
+use derive_builder::Builder;
+
+use super::buy_token::{buy_instr, BuyAccounts, BuyArgs};
+use super::pda::{
+    get_associated_bonding_curve, get_bonding_curve_pda, get_global_pda, get_mint_authority_pda,
+};
+
+// anchor discriminator: sha256("global:create")[..8]
+pub const CREATE_DISCRIMINATOR: [u8; 8] = [24, 30, 200, 40, 5, 28, 7, 119];
+
+#[derive(Default, Builder, Debug)]
+pub struct CreateAccounts {
+    /// 代币铸币账户 (新生成的 keypair, 需要签名)
+    pub mint: String,
+
+    /// 铸币权限账户
+    #[builder(default = "String::from(\"TSLvdd1pWpHVjahSpsvCXUbgwsL3JAcvokwaKt1eokM\")")]
+    pub mint_authority: String,
+
+    /// 债券曲线账户
+    pub bonding_curve: String,
+
+    /// 关联债券曲线账户
+    pub associated_bonding_curve: String,
+
+    /// 全局状态账户
+    #[builder(default = "String::from(\"4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf\")")]
+    pub global: String,
+
+    /// metaplex 元数据程序
+    #[builder(default = "String::from(\"metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s\")")]
+    pub mpl_token_metadata: String,
+
+    /// 元数据账户
+    pub metadata: String,
+
+    /// 用户钱包账户
+    pub user: String,
+
+    /// 系统程序
+    #[builder(default = "String::from(\"11111111111111111111111111111111\")")]
+    pub system_program: String,
+
+    /// 代币程序
+    #[builder(default = "String::from(\"TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA\")")]
+    pub token_program: String,
+
+    /// 关联代币程序
+    #[builder(default = "String::from(\"ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL\")")]
+    pub associated_token_program: String,
+
+    /// 租金账户
+    #[builder(default = "String::from(\"SysvarRent111111111111111111111111111111111\")")]
+    pub rent: String,
+
+    /// 事件权限账户
+    #[builder(default = "String::from(\"Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1\")")]
+    pub event_authority: String,
+
+    /// 程序账户
+    #[builder(default = "String::from(\"6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P\")")]
+    pub program: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CreateArgs {
+    /// 代币名称
+    pub name: String,
+    /// 代币符号
+    pub symbol: String,
+    /// 元数据 json 链接
+    pub uri: String,
+}
+
+impl CreateAccounts {
+    // 根据新代币和用户钱包推导出创建需要的所有账户, 元数据 pda 与 spl::create_spl_token 一致
+    pub fn new(mint: &Pubkey, user: &Pubkey) -> Result<Self> {
+        let accounts = CreateAccountsBuilder::default()
+            .mint(mint.to_string())
+            .mint_authority(get_mint_authority_pda().to_string())
+            .bonding_curve(get_bonding_curve_pda(mint).to_string())
+            .associated_bonding_curve(get_associated_bonding_curve(mint).to_string())
+            .global(get_global_pda().to_string())
+            .metadata(Metadata::find_pda(mint).0.to_string())
+            .user(user.to_string())
+            .build()?;
+        Ok(accounts)
+    }
+
+    pub fn to_account_metas(&self) -> Result<Vec<AccountMeta>> {
+        Ok(vec![
+            AccountMeta::new(Pubkey::from_str(&self.mint)?, true),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.mint_authority)?, false),
+            AccountMeta::new(Pubkey::from_str(&self.bonding_curve)?, false),
+            AccountMeta::new(Pubkey::from_str(&self.associated_bonding_curve)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.global)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.mpl_token_metadata)?, false),
+            AccountMeta::new(Pubkey::from_str(&self.metadata)?, false),
+            AccountMeta::new(Pubkey::from_str(&self.user)?, true),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.system_program)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.token_program)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.associated_token_program)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.rent)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.event_authority)?, false),
+            AccountMeta::new_readonly(Pubkey::from_str(&self.program)?, false),
+        ])
+    }
+}
+
+impl CreateArgs {
+    // borsh: string = u32 长度 + utf8 字节
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = CREATE_DISCRIMINATOR.to_vec();
+        for field in [&self.name, &self.symbol, &self.uri] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field.as_bytes());
+        }
+        data
+    }
+}
+
+pub fn create_instr(accounts: &CreateAccounts, args: &CreateArgs) -> Result<Instruction> {
+    Ok(Instruction {
+        program_id: Pubkey::from_str(&accounts.program)?,
+        accounts: accounts.to_account_metas()?,
+        data: args.data(),
+    })
+}
+
+// 创建代币, 可选在同一笔交易里由开发者先买入一部分
+// 交易需要 user 和 mint 两个签名
+pub fn prepare_create_token_instructions(
+    mint: &Pubkey,
+    user: &Pubkey,
+    args: &CreateArgs,
+    dev_buy: Option<&BuyArgs>,
+) -> Result<Vec<Instruction>> {
+    let create_accounts = CreateAccounts::new(mint, user)?;
+    let mut instructions = vec![create_instr(&create_accounts, args)?];
+
+    if let Some(buy_args) = dev_buy {
+        let buy_accounts = BuyAccounts::new(mint, user)?;
+        let create_ata_ix =
+            spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                user,
+                user,
+                mint,
+                &Pubkey::from_str(&buy_accounts.token_program)?,
+            );
+        instructions.push(create_ata_ix);
+        instructions.push(buy_instr(&buy_accounts, buy_args)?);
+    }
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{signature::Keypair, signer::Signer};
+
+    #[test]
+    fn test_create_token_instructions() {
+        let mint = Keypair::new();
+        let user = Pubkey::from_str("9WYirnyBy8RMBuoatC9yVCRQZ6AYpKpMyKmr2TrjypCG").unwrap();
+        let args = CreateArgs {
+            name: String::from("NewsMeMe"),
+            symbol: String::from("NEWS"),
+            uri: String::from("https://white-historical-basilisk-887.mypinata.cloud/ipfs/QmVd6xVRqg9sJQP1zkUVizZ7jah6zD7j6fSPn9F7MRjZMo"),
+        };
+        let buy_args = BuyArgs {
+            amount: 1_000_000 * 10_u64.pow(6),
+            max_sol_cost: 5 * 10_u64.pow(7),
+        };
+        let instructions =
+            prepare_create_token_instructions(&mint.pubkey(), &user, &args, Some(&buy_args))
+                .unwrap();
+        assert_eq!(instructions.len(), 3);
+
+        let create_ix = &instructions[0];
+        assert_eq!(create_ix.accounts.len(), 14);
+        assert_eq!(create_ix.data[..8], CREATE_DISCRIMINATOR);
+        assert_eq!(
+            create_ix.accounts[6].pubkey,
+            Metadata::find_pda(&mint.pubkey()).0
+        );
+        assert_eq!(
+            create_ix.data.len(),
+            8 + 4 * 3 + args.name.len() + args.symbol.len() + args.uri.len()
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use super::pda::PUMPFUN_PROGRAM_ID;
+
+// anchor discriminator: sha256("event:<EventName>")[..8]
+pub const TRADE_EVENT_DISCRIMINATOR: [u8; 8] = [189, 219, 127, 211, 78, 230, 97, 238];
+pub const CREATE_EVENT_DISCRIMINATOR: [u8; 8] = [27, 114, 169, 77, 222, 235, 99, 118];
+pub const COMPLETE_EVENT_DISCRIMINATOR: [u8; 8] = [95, 114, 97, 156, 212, 46, 152, 8];
+// emit_cpi! 产生的 inner instruction 前缀, anchor_lang::event::EVENT_IX_TAG_LE
+pub const EVENT_IX_TAG: [u8; 8] = [228, 69, 165, 46, 81, 203, 154, 29];
+
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TradeEvent {
+    pub mint: Pubkey,
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub is_buy: bool,
+    pub user: Pubkey,
+    pub timestamp: i64,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CreateEvent {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub user: Pubkey,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CompleteEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PumpEvent {
+    Trade(TradeEvent),
+    Create(CreateEvent),
+    Complete(CompleteEvent),
+}
+
+// borsh 顺序读取
+struct EventReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> EventReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(anyhow!("event data too short"));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn read_i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.take(1)?[0] != 0)
+    }
+
+    fn read_pubkey(&mut self) -> Result<Pubkey> {
+        Ok(Pubkey::try_from(self.take(32)?)?)
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into()?) as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
+
+// data = 事件 discriminator + borsh 数据, 不认识的事件返回 None
+pub fn parse_event(data: &[u8]) -> Result<Option<PumpEvent>> {
+    if data.len() < 8 {
+        return Ok(None);
+    }
+    let (discriminator, body) = data.split_at(8);
+    let mut reader = EventReader { data: body };
+    let event = if discriminator == TRADE_EVENT_DISCRIMINATOR {
+        PumpEvent::Trade(TradeEvent {
+            mint: reader.read_pubkey()?,
+            sol_amount: reader.read_u64()?,
+            token_amount: reader.read_u64()?,
+            is_buy: reader.read_bool()?,
+            user: reader.read_pubkey()?,
+            timestamp: reader.read_i64()?,
+            virtual_sol_reserves: reader.read_u64()?,
+            virtual_token_reserves: reader.read_u64()?,
+        })
+    } else if discriminator == CREATE_EVENT_DISCRIMINATOR {
+        PumpEvent::Create(CreateEvent {
+            name: reader.read_string()?,
+            symbol: reader.read_string()?,
+            uri: reader.read_string()?,
+            mint: reader.read_pubkey()?,
+            bonding_curve: reader.read_pubkey()?,
+            user: reader.read_pubkey()?,
+        })
+    } else if discriminator == COMPLETE_EVENT_DISCRIMINATOR {
+        PumpEvent::Complete(CompleteEvent {
+            user: reader.read_pubkey()?,
+            mint: reader.read_pubkey()?,
+            bonding_curve: reader.read_pubkey()?,
+            timestamp: reader.read_i64()?,
+        })
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(event))
+}
+
+// 解析 emit_cpi! 写入的 inner instruction data (已经从 base58 解码)
+pub fn parse_inner_instruction(data: &[u8]) -> Result<Option<PumpEvent>> {
+    match data.strip_prefix(EVENT_IX_TAG.as_slice()) {
+        Some(event_data) => parse_event(event_data),
+        None => Ok(None),
+    }
+}
+
+// 解析交易日志中 pump.fun 程序输出的 "Program data: " 行
+// 通过 invoke / success / failed 维护调用栈, 忽略其他程序 (比如 raydium) 的事件
+pub fn parse_logs(logs: &[String]) -> Result<Vec<PumpEvent>> {
+    let mut program_stack: Vec<&str> = vec![];
+    let mut events = vec![];
+    for log in logs {
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA_PREFIX) {
+            if program_stack.last() == Some(&PUMPFUN_PROGRAM_ID) {
+                if let Some(event) = parse_event(&STANDARD.decode(data)?)? {
+                    events.push(event);
+                }
+            }
+            continue;
+        }
+        let mut parts = log.split_whitespace();
+        if parts.next() != Some("Program") {
+            continue;
+        }
+        let (Some(program), Some(action)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        match action {
+            "invoke" => program_stack.push(program),
+            "success" | "failed:" => {
+                program_stack.pop();
+            }
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn trade_event_data(event: &TradeEvent) -> Vec<u8> {
+        let mut data = TRADE_EVENT_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(event.mint.as_ref());
+        data.extend_from_slice(&event.sol_amount.to_le_bytes());
+        data.extend_from_slice(&event.token_amount.to_le_bytes());
+        data.push(event.is_buy as u8);
+        data.extend_from_slice(event.user.as_ref());
+        data.extend_from_slice(&event.timestamp.to_le_bytes());
+        data.extend_from_slice(&event.virtual_sol_reserves.to_le_bytes());
+        data.extend_from_slice(&event.virtual_token_reserves.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_trade_event() {
+        let event = TradeEvent {
+            mint: Pubkey::from_str("CcQWG2M56Z1ESomovmzjvNPuDXBjHype7PoQPP2Zpump").unwrap(),
+            sol_amount: 50_000_000,
+            token_amount: 1_745_000_000_000,
+            is_buy: true,
+            user: Pubkey::from_str("9WYirnyBy8RMBuoatC9yVCRQZ6AYpKpMyKmr2TrjypCG").unwrap(),
+            timestamp: 1_725_000_000,
+            virtual_sol_reserves: 30_050_000_000,
+            virtual_token_reserves: 1_071_255_000_000_000,
+        };
+        let data = trade_event_data(&event);
+        let logs = vec![
+            format!("Program {} invoke [1]", PUMPFUN_PROGRAM_ID),
+            String::from("Program log: Instruction: Buy"),
+            format!("{}{}", PROGRAM_DATA_PREFIX, STANDARD.encode(&data)),
+            format!("Program {} success", PUMPFUN_PROGRAM_ID),
+            String::from("Program 11111111111111111111111111111111 invoke [1]"),
+            format!("{}{}", PROGRAM_DATA_PREFIX, STANDARD.encode(&data)),
+            String::from("Program 11111111111111111111111111111111 success"),
+        ];
+        let events = parse_logs(&logs).unwrap();
+        assert_eq!(events, vec![PumpEvent::Trade(event.clone())]);
+
+        let mut ix_data = EVENT_IX_TAG.to_vec();
+        ix_data.extend_from_slice(&data);
+        assert_eq!(
+            parse_inner_instruction(&ix_data).unwrap(),
+            Some(PumpEvent::Trade(event))
+        );
+        assert!(parse_event(&data[..40]).is_err());
+    }
+}
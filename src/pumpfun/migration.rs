@@ -0,0 +1,189 @@
+use anyhow::{anyhow, Result};
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use std::str::FromStr;
+
+use crate::raydium::amm_v4::{swap_base_out_instr, AmmV4Pool, AMM_INFO_LEN};
+use crate::utils::{
+    unwrap_sol_instruction, wrap_sol_instructions, SPL_TOKEN_PROGRAM_ID, WSOL_MINT,
+};
+
+use super::bonding_curve::get_bonding_curve;
+use super::buy_token::{buy_instr, BuyAccounts, BuyArgs};
+
+// AmmInfo 中 coin_vault_mint / pc_vault_mint 的偏移
+const COIN_MINT_OFFSET: usize = 400;
+const PC_MINT_OFFSET: usize = 432;
+
+// 曲线完成后 pump.fun 会新建 openbook 市场并迁移到 raydium amm v4 (coin = token, pc = wsol)
+// 池子地址由市场决定, 无法直接推导, 只能按 mint 查找; 池子还没创建时返回 None
+pub fn find_migrated_pool(
+    client: &RpcClient,
+    amm_program: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Option<Pubkey>> {
+    let wsol_mint = Pubkey::from_str(WSOL_MINT)?;
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(AMM_INFO_LEN as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(COIN_MINT_OFFSET, mint.as_ref())),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                PC_MINT_OFFSET,
+                wsol_mint.as_ref(),
+            )),
+        ]),
+        // 只需要地址, 不返回账户数据
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig {
+                offset: 0,
+                length: 0,
+            }),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let accounts = client.get_program_accounts_with_config(amm_program, config)?;
+    Ok(accounts.first().map(|(pool_id, _)| *pool_id))
+}
+
+// 在迁移后的 amm v4 池子中用 wsol 买入 amount 个代币, 最多花费 max_sol_cost lamports
+pub fn prepare_migrated_buy_instructions(
+    pool: &AmmV4Pool,
+    mint: &Pubkey,
+    user: &Pubkey,
+    args: &BuyArgs,
+) -> Result<Vec<Instruction>> {
+    let wsol_mint = Pubkey::from_str(WSOL_MINT)?;
+    let amm_info = &pool.amm_info;
+    let paired = (amm_info.coin_vault_mint == *mint && amm_info.pc_vault_mint == wsol_mint)
+        || (amm_info.coin_vault_mint == wsol_mint && amm_info.pc_vault_mint == *mint);
+    if !paired {
+        return Err(anyhow!("amm {} is not a {}/wsol pool", pool.amm_id, mint));
+    }
+
+    let mut instructions = wrap_sol_instructions(user, args.max_sol_cost)?;
+    instructions.push(
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            user,
+            user,
+            mint,
+            &Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+        ),
+    );
+    instructions.push(swap_base_out_instr(
+        pool,
+        spl_associated_token_account::get_associated_token_address(user, &wsol_mint),
+        spl_associated_token_account::get_associated_token_address(user, mint),
+        *user,
+        args.max_sol_cost,
+        args.amount,
+    )?);
+    instructions.push(unwrap_sol_instruction(user)?);
+    Ok(instructions)
+}
+
+// 买入 amount 个代币, 最多花费 max_sol_cost lamports
+// 曲线未完成走 pump.fun buy, 完成后走 raydium amm v4 swap_base_out (语义相同: 固定输出, 限制最大输入)
+pub fn prepare_buy_instructions(
+    client: &RpcClient,
+    amm_program: &Pubkey,
+    mint: &Pubkey,
+    user: &Pubkey,
+    args: &BuyArgs,
+) -> Result<Vec<Instruction>> {
+    let bonding_curve = get_bonding_curve(client, mint)?;
+    if !bonding_curve.complete {
+        let accounts = BuyAccounts::new(mint, user)?;
+        return Ok(vec![
+            spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                user,
+                user,
+                mint,
+                &Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+            ),
+            buy_instr(&accounts, args)?,
+        ]);
+    }
+
+    let pool_id = find_migrated_pool(client, amm_program, mint)?.ok_or_else(|| {
+        anyhow!(
+            "bonding curve of {} is complete but no migrated pool found",
+            mint
+        )
+    })?;
+    let pool = AmmV4Pool::fetch(client, amm_program, &pool_id)?;
+    prepare_migrated_buy_instructions(&pool, mint, user, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raydium::amm_v4::{
+        AmmInfo, MarketState, RAYDIUM_AMM_V4_PROGRAM_ID, SWAP_BASE_OUT_TAG,
+    };
+
+    #[test]
+    fn test_migrated_buy_instructions() {
+        let mint = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let wsol_mint = Pubkey::from_str(WSOL_MINT).unwrap();
+        let market_program = Pubkey::new_unique();
+        let own_address = Pubkey::new_unique();
+        // vault signer 需要是 curve 外的地址
+        let vault_signer_nonce = (0u64..)
+            .find(|nonce| {
+                Pubkey::create_program_address(
+                    &[own_address.as_ref(), &nonce.to_le_bytes()],
+                    &market_program,
+                )
+                .is_ok()
+            })
+            .unwrap();
+        let mut pool = AmmV4Pool {
+            program_id: Pubkey::from_str(RAYDIUM_AMM_V4_PROGRAM_ID).unwrap(),
+            amm_id: Pubkey::new_unique(),
+            amm_info: AmmInfo {
+                coin_vault_mint: mint,
+                pc_vault_mint: wsol_mint,
+                market_program,
+                ..Default::default()
+            },
+            market: MarketState {
+                own_address,
+                vault_signer_nonce,
+                ..Default::default()
+            },
+            coin_vault_amount: 0,
+            pc_vault_amount: 0,
+        };
+        let args = BuyArgs {
+            amount: 1_000,
+            max_sol_cost: 2_000,
+        };
+        let instructions = prepare_migrated_buy_instructions(&pool, &mint, &user, &args).unwrap();
+        // wrap (3) + 创建 ata + swap + unwrap
+        assert_eq!(instructions.len(), 6);
+        let swap = &instructions[4];
+        assert_eq!(swap.program_id, pool.program_id);
+        assert_eq!(swap.accounts[1].pubkey, pool.amm_id);
+        assert_eq!(
+            swap.accounts[15].pubkey,
+            spl_associated_token_account::get_associated_token_address(&user, &wsol_mint)
+        );
+        assert_eq!(
+            swap.accounts[16].pubkey,
+            spl_associated_token_account::get_associated_token_address(&user, &mint)
+        );
+        assert_eq!(swap.data[0], SWAP_BASE_OUT_TAG);
+        assert_eq!(swap.data[1..9], 2_000u64.to_le_bytes());
+        assert_eq!(swap.data[9..17], 1_000u64.to_le_bytes());
+
+        // 不是 mint / wsol 交易对的池子
+        pool.amm_info.pc_vault_mint = Pubkey::new_unique();
+        assert!(prepare_migrated_buy_instructions(&pool, &mint, &user, &args).is_err());
+    }
+}
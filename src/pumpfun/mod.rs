@@ -1 +1,6 @@
-pub mod buy_token;
\ No newline at end of file
+pub mod bonding_curve;
+pub mod buy_token;
+pub mod create_token;
+pub mod events;
+pub mod migration;
+pub mod pda;
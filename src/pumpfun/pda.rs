@@ -0,0 +1,72 @@
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+use std::str::FromStr;
+
+pub const PUMPFUN_PROGRAM_ID: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+pub const GLOBAL_SEED: &str = "global";
+pub const MINT_AUTHORITY_SEED: &str = "mint-authority";
+pub const BONDING_CURVE_SEED: &str = "bonding-curve";
+pub const EVENT_AUTHORITY_SEED: &str = "__event_authority";
+
+pub fn pumpfun_program_id() -> Pubkey {
+    Pubkey::from_str(PUMPFUN_PROGRAM_ID).unwrap()
+}
+
+// 全局状态账户
+pub fn get_global_pda() -> Pubkey {
+    Pubkey::find_program_address(&[GLOBAL_SEED.as_bytes()], &pumpfun_program_id()).0
+}
+
+// 铸币权限账户, pump.fun 发行的代币 mint authority 都是这个 pda
+pub fn get_mint_authority_pda() -> Pubkey {
+    Pubkey::find_program_address(&[MINT_AUTHORITY_SEED.as_bytes()], &pumpfun_program_id()).0
+}
+
+// 债券曲线账户 seed = ["bonding-curve", mint]
+pub fn get_bonding_curve_pda(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[BONDING_CURVE_SEED.as_bytes(), mint.to_bytes().as_ref()],
+        &pumpfun_program_id(),
+    )
+    .0
+}
+
+// 关联债券曲线账户, 即债券曲线账户持有该代币的 ata (legacy token program)
+pub fn get_associated_bonding_curve(mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(&get_bonding_curve_pda(mint), mint)
+}
+
+// anchor event cpi 使用的事件权限账户
+pub fn get_event_authority_pda() -> Pubkey {
+    Pubkey::find_program_address(&[EVENT_AUTHORITY_SEED.as_bytes()], &pumpfun_program_id()).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pumpfun_pda() {
+        let mint = Pubkey::from_str("CcQWG2M56Z1ESomovmzjvNPuDXBjHype7PoQPP2Zpump").unwrap();
+        assert_eq!(
+            get_bonding_curve_pda(&mint).to_string(),
+            "Ab4DiSUzi4tHLkE2W1k4W24mvmFoxsMvKCcpfNixNTJF"
+        );
+        assert_eq!(
+            get_associated_bonding_curve(&mint).to_string(),
+            "YhTHuJANfML4Zd54mpDpCXYCeJp7qjPUJhpQoX8d6BT"
+        );
+        assert_eq!(
+            get_global_pda().to_string(),
+            "4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf"
+        );
+        assert_eq!(
+            get_event_authority_pda().to_string(),
+            "Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1"
+        );
+        assert_eq!(
+            get_mint_authority_pda().to_string(),
+            "TSLvdd1pWpHVjahSpsvCXUbgwsL3JAcvokwaKt1eokM"
+        );
+    }
+}
@@ -1,14 +1,24 @@
-use anchor_client::Client;
-use anchor_client::Cluster;
+use anchor_client::anchor_lang::{InstructionData, ToAccountMetas};
 use anyhow::anyhow;
 use anyhow::Result;
-use solana_sdk::signature::Keypair;
-use solana_sdk::{instruction::Instruction, pubkey::Pubkey, system_program, sysvar};
+use derive_builder::Builder;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
 
 use raydium_cp_swap::accounts as raydium_cp_accounts;
 use raydium_cp_swap::instruction as raydium_cp_instructions;
+use raydium_cp_swap::states::PoolState;
 
-use std::rc::Rc;
+use super::quote::{PoolQuoter, SwapQuoter};
+use super::utils::amount_with_slippage;
+use crate::utils::SPL_TOKEN_PROGRAM_ID;
+use std::str::FromStr;
+
+pub const RAYDIUM_CP_PROGRAM_ID: &str = "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C";
+pub const RAYDIUM_CP_DEVNET_PROGRAM_ID: &str = "CPMDWBwJDtYax9qW7AyRuVC19Cc4L4Vcy4n2BHAbHkCW";
 
 pub const AMM_CONFIG_SEED: &str = "amm_config";
 /// Seed to derive account address and signature
@@ -22,23 +32,134 @@ pub const POOL_LP_MINT_SEED: &str = "pool_lp_mint";
 pub const POOL_VAULT_SEED: &str = "pool_vault";
 
 pub const AUTH_SEED: &str = "vault_and_lp_mint_auth_seed";
-fn read_keypair_file(s: &str) -> Result<Keypair> {
-    solana_sdk::signature::read_keypair_file(s)
-        .map_err(|_| anyhow!("failed to read keypair from {}", s))
+
+pub fn get_amm_config_address(program_id: &Pubkey, amm_config_index: u16) -> Pubkey {
+    Pubkey::find_program_address(
+        &[AMM_CONFIG_SEED.as_bytes(), &amm_config_index.to_be_bytes()],
+        program_id,
+    )
+    .0
+}
+
+pub fn get_pool_address(
+    program_id: &Pubkey,
+    amm_config: &Pubkey,
+    token_0_mint: &Pubkey,
+    token_1_mint: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POOL_SEED.as_bytes(),
+            amm_config.to_bytes().as_ref(),
+            token_0_mint.to_bytes().as_ref(),
+            token_1_mint.to_bytes().as_ref(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+// cp-swap 要求 token_0_mint < token_1_mint (按字节比较), 第三个返回值表示是否交换了顺序
+pub fn sort_mints(mint_a: Pubkey, mint_b: Pubkey) -> (Pubkey, Pubkey, bool) {
+    if mint_a < mint_b {
+        (mint_a, mint_b, false)
+    } else {
+        (mint_b, mint_a, true)
+    }
+}
+
+// 任意顺序的两个代币在 amm_config_index 费率档位下的池子地址
+pub fn get_pool_address_by_mints(
+    program_id: &Pubkey,
+    amm_config_index: u16,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+) -> Pubkey {
+    let (token_0_mint, token_1_mint, _) = sort_mints(*mint_a, *mint_b);
+    let amm_config = get_amm_config_address(program_id, amm_config_index);
+    get_pool_address(program_id, &amm_config, &token_0_mint, &token_1_mint)
+}
+
+pub fn get_authority_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[AUTH_SEED.as_bytes()], program_id).0
+}
+
+pub fn get_pool_vault_address(program_id: &Pubkey, pool_id: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POOL_VAULT_SEED.as_bytes(),
+            pool_id.to_bytes().as_ref(),
+            mint.to_bytes().as_ref(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+pub fn get_pool_lp_mint_address(program_id: &Pubkey, pool_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[POOL_LP_MINT_SEED.as_bytes(), pool_id.to_bytes().as_ref()],
+        program_id,
+    )
+    .0
+}
+
+pub fn get_observation_address(program_id: &Pubkey, pool_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[OBSERVATION_SEED.as_bytes(), pool_id.to_bytes().as_ref()],
+        program_id,
+    )
+    .0
 }
 
-#[derive(Clone, Debug, PartialEq)]
+// 指令构造只用到 raydium_cp_program / slippage / admin, http_url / ws_url 留给调用方拉取链上数据
+// let config = ClientConfigBuilder::default().slippage(0.005).build()?;
+#[derive(Clone, Debug, PartialEq, Builder)]
 pub struct ClientConfig {
+    #[builder(setter(into), default = "String::from(\"https://api.mainnet-beta.solana.com\")")]
     http_url: String,
+    #[builder(setter(into), default = "String::from(\"wss://api.mainnet-beta.solana.com\")")]
     ws_url: String,
-    payer_path: String,
-    admin_path: String,
+    /// fork 程序中写死的管理员, 只有管理员相关的指令需要设置
+    #[builder(default)]
+    admin: Pubkey,
+    #[builder(default = "Pubkey::from_str(RAYDIUM_CP_PROGRAM_ID).unwrap()")]
     raydium_cp_program: Pubkey,
+    /// 小数形式, 0.01 即 1%
+    #[builder(default = "0.01")]
     slippage: f64,
 }
 
+impl ClientConfig {
+    pub fn http_url(&self) -> &str {
+        &self.http_url
+    }
+
+    pub fn ws_url(&self) -> &str {
+        &self.ws_url
+    }
+
+    pub fn raydium_cp_program(&self) -> Pubkey {
+        self.raydium_cp_program
+    }
+
+    pub fn admin(&self) -> Result<Pubkey> {
+        if self.admin == Pubkey::default() {
+            return Err(anyhow!("admin is not set in ClientConfig"));
+        }
+        Ok(self.admin)
+    }
+
+    pub fn slippage(&self) -> f64 {
+        self.slippage
+    }
+}
+
+// 代币可以任意顺序传入, 内部按 sort_mints 规范顺序同时交换对应的 program / 账户 / 数量
 pub fn initialize_pool_instr(
     config: &ClientConfig,
+    payer: Pubkey,
+    amm_config_index: u16,
     token_0_mint: Pubkey,
     token_1_mint: Pubkey,
     token_0_program: Pubkey,
@@ -50,63 +171,53 @@ pub fn initialize_pool_instr(
     init_amount_1: u64,
     open_time: u64,
 ) -> Result<Vec<Instruction>> {
-    let payer = read_keypair_file(&config.payer_path)?;
-    let url = Cluster::Custom(config.http_url.clone(), config.ws_url.clone());
-    // Client.
-    let client = Client::new(url, Rc::new(payer));
-    let program = client.program(config.raydium_cp_program)?;
-
-    let amm_config_index = 0u16;
-    let (amm_config_key, __bump) = Pubkey::find_program_address(
-        &[AMM_CONFIG_SEED.as_bytes(), &amm_config_index.to_be_bytes()],
-        &program.id(),
-    );
+    let (
+        token_0_mint,
+        token_1_mint,
+        token_0_program,
+        token_1_program,
+        user_token_0_account,
+        user_token_1_account,
+        init_amount_0,
+        init_amount_1,
+    ) = if sort_mints(token_0_mint, token_1_mint).2 {
+        (
+            token_1_mint,
+            token_0_mint,
+            token_1_program,
+            token_0_program,
+            user_token_1_account,
+            user_token_0_account,
+            init_amount_1,
+            init_amount_0,
+        )
+    } else {
+        (
+            token_0_mint,
+            token_1_mint,
+            token_0_program,
+            token_1_program,
+            user_token_0_account,
+            user_token_1_account,
+            init_amount_0,
+            init_amount_1,
+        )
+    };
 
-    let (pool_account_key, __bump) = Pubkey::find_program_address(
-        &[
-            POOL_SEED.as_bytes(),
-            amm_config_key.to_bytes().as_ref(),
-            token_0_mint.to_bytes().as_ref(),
-            token_1_mint.to_bytes().as_ref(),
-        ],
-        &program.id(),
-    );
-    let (authority, __bump) = Pubkey::find_program_address(&[AUTH_SEED.as_bytes()], &program.id());
-    let (token_0_vault, __bump) = Pubkey::find_program_address(
-        &[
-            POOL_VAULT_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            token_0_mint.to_bytes().as_ref(),
-        ],
-        &program.id(),
-    );
-    let (token_1_vault, __bump) = Pubkey::find_program_address(
-        &[
-            POOL_VAULT_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-            token_1_mint.to_bytes().as_ref(),
-        ],
-        &program.id(),
-    );
-    let (lp_mint_key, __bump) = Pubkey::find_program_address(
-        &[
-            POOL_LP_MINT_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-        ],
-        &program.id(),
-    );
-    let (observation_key, __bump) = Pubkey::find_program_address(
-        &[
-            OBSERVATION_SEED.as_bytes(),
-            pool_account_key.to_bytes().as_ref(),
-        ],
-        &program.id(),
-    );
+    let program_id = config.raydium_cp_program;
+    let amm_config_key = get_amm_config_address(&program_id, amm_config_index);
+    let pool_account_key =
+        get_pool_address(&program_id, &amm_config_key, &token_0_mint, &token_1_mint);
+    let authority = get_authority_address(&program_id);
+    let token_0_vault = get_pool_vault_address(&program_id, &pool_account_key, &token_0_mint);
+    let token_1_vault = get_pool_vault_address(&program_id, &pool_account_key, &token_1_mint);
+    let lp_mint_key = get_pool_lp_mint_address(&program_id, &pool_account_key);
+    let observation_key = get_observation_address(&program_id, &pool_account_key);
 
-    let instructions = program
-        .request()
-        .accounts(raydium_cp_accounts::Initialize {
-            creator: program.payer(),
+    let instruction = Instruction {
+        program_id,
+        accounts: raydium_cp_accounts::Initialize {
+            creator: payer,
             amm_config: amm_config_key,
             authority,
             pool_state: pool_account_key,
@@ -116,25 +227,1093 @@ pub fn initialize_pool_instr(
             creator_token_0: user_token_0_account,
             creator_token_1: user_token_1_account,
             creator_lp_token: spl_associated_token_account::get_associated_token_address(
-                &program.payer(),
+                &payer,
                 &lp_mint_key,
             ),
             token_0_vault,
             token_1_vault,
             create_pool_fee,
             observation_state: observation_key,
-            token_program: spl_token_2022::id(),
+            // lp mint 由 legacy token program 创建
+            token_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
             token_0_program,
             token_1_program,
             associated_token_program: spl_associated_token_account::id(),
             system_program: system_program::id(),
             rent: sysvar::rent::id(),
-        })
-        .args(raydium_cp_instructions::Initialize {
+        }
+        .to_account_metas(None),
+        data: raydium_cp_instructions::Initialize {
             init_amount_0,
             init_amount_1,
             open_time,
+        }
+        .data(),
+    };
+    Ok(vec![instruction])
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn swap_base_input_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_id: Pubkey,
+    amm_config: Pubkey,
+    observation_key: Pubkey,
+    input_token_account: Pubkey,
+    output_token_account: Pubkey,
+    input_vault: Pubkey,
+    output_vault: Pubkey,
+    input_token_program: Pubkey,
+    output_token_program: Pubkey,
+    input_token_mint: Pubkey,
+    output_token_mint: Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Vec<Instruction>> {
+    let authority = get_authority_address(&config.raydium_cp_program);
+
+    let instruction = Instruction {
+        program_id: config.raydium_cp_program,
+        accounts: raydium_cp_accounts::Swap {
+            payer,
+            authority,
+            amm_config,
+            pool_state: pool_id,
+            input_token_account,
+            output_token_account,
+            input_vault,
+            output_vault,
+            input_token_program,
+            output_token_program,
+            input_token_mint,
+            output_token_mint,
+            observation_state: observation_key,
+        }
+        .to_account_metas(None),
+        data: raydium_cp_instructions::SwapBaseInput {
+            amount_in,
+            minimum_amount_out,
+        }
+        .data(),
+    };
+    Ok(vec![instruction])
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn swap_base_output_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_id: Pubkey,
+    amm_config: Pubkey,
+    observation_key: Pubkey,
+    input_token_account: Pubkey,
+    output_token_account: Pubkey,
+    input_vault: Pubkey,
+    output_vault: Pubkey,
+    input_token_program: Pubkey,
+    output_token_program: Pubkey,
+    input_token_mint: Pubkey,
+    output_token_mint: Pubkey,
+    max_amount_in: u64,
+    amount_out: u64,
+) -> Result<Vec<Instruction>> {
+    let authority = get_authority_address(&config.raydium_cp_program);
+
+    let instruction = Instruction {
+        program_id: config.raydium_cp_program,
+        accounts: raydium_cp_accounts::Swap {
+            payer,
+            authority,
+            amm_config,
+            pool_state: pool_id,
+            input_token_account,
+            output_token_account,
+            input_vault,
+            output_vault,
+            input_token_program,
+            output_token_program,
+            input_token_mint,
+            output_token_mint,
+            observation_state: observation_key,
+        }
+        .to_account_metas(None),
+        data: raydium_cp_instructions::SwapBaseOutput {
+            max_amount_in,
+            amount_out,
+        }
+        .data(),
+    };
+    Ok(vec![instruction])
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_id: Pubkey,
+    token_0_mint: Pubkey,
+    token_1_mint: Pubkey,
+    token_lp_mint: Pubkey,
+    token_0_vault: Pubkey,
+    token_1_vault: Pubkey,
+    user_token_0_account: Pubkey,
+    user_token_1_account: Pubkey,
+    user_token_lp_account: Pubkey,
+    lp_token_amount: u64,
+    maximum_token_0_amount: u64,
+    maximum_token_1_amount: u64,
+) -> Result<Vec<Instruction>> {
+    let authority = get_authority_address(&config.raydium_cp_program);
+
+    let instruction = Instruction {
+        program_id: config.raydium_cp_program,
+        accounts: raydium_cp_accounts::Deposit {
+            owner: payer,
+            authority,
+            pool_state: pool_id,
+            owner_lp_token: user_token_lp_account,
+            token_0_account: user_token_0_account,
+            token_1_account: user_token_1_account,
+            token_0_vault,
+            token_1_vault,
+            // lp mint 由 legacy token program 管理
+            token_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+            token_program_2022: spl_token_2022::id(),
+            vault_0_mint: token_0_mint,
+            vault_1_mint: token_1_mint,
+            lp_mint: token_lp_mint,
+        }
+        .to_account_metas(None),
+        data: raydium_cp_instructions::Deposit {
+            lp_token_amount,
+            maximum_token_0_amount,
+            maximum_token_1_amount,
+        }
+        .data(),
+    };
+    Ok(vec![instruction])
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_instr(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_id: Pubkey,
+    token_0_mint: Pubkey,
+    token_1_mint: Pubkey,
+    token_lp_mint: Pubkey,
+    token_0_vault: Pubkey,
+    token_1_vault: Pubkey,
+    user_token_0_account: Pubkey,
+    user_token_1_account: Pubkey,
+    user_token_lp_account: Pubkey,
+    lp_token_amount: u64,
+    minimum_token_0_amount: u64,
+    minimum_token_1_amount: u64,
+) -> Result<Vec<Instruction>> {
+    let authority = get_authority_address(&config.raydium_cp_program);
+
+    let instruction = Instruction {
+        program_id: config.raydium_cp_program,
+        accounts: raydium_cp_accounts::Withdraw {
+            owner: payer,
+            authority,
+            pool_state: pool_id,
+            owner_lp_token: user_token_lp_account,
+            token_0_account: user_token_0_account,
+            token_1_account: user_token_1_account,
+            token_0_vault,
+            token_1_vault,
+            token_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+            token_program_2022: spl_token_2022::id(),
+            vault_0_mint: token_0_mint,
+            vault_1_mint: token_1_mint,
+            lp_mint: token_lp_mint,
+            memo_program: spl_memo::id(),
+        }
+        .to_account_metas(None),
+        data: raydium_cp_instructions::Withdraw {
+            lp_token_amount,
+            minimum_token_0_amount,
+            minimum_token_1_amount,
+        }
+        .data(),
+    };
+    Ok(vec![instruction])
+}
+
+// 添加流动性: 需要转出的数量包含转入 vault 的转账手续费, 再按 config.slippage 放宽最大投入
+// lp ata 不存在时会创建
+pub fn prepare_deposit_instructions(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_id: Pubkey,
+    quoter: &PoolQuoter,
+    lp_token_amount: u64,
+) -> Result<Vec<Instruction>> {
+    let pool_state = &quoter.reserves.pool_state;
+    let quote = quoter.deposit(lp_token_amount)?;
+    let maximum_token_0_amount = amount_with_slippage(quote.token_0_amount, config.slippage, true);
+    let maximum_token_1_amount = amount_with_slippage(quote.token_1_amount, config.slippage, true);
+
+    let lp_token_program = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?;
+    let mut instructions = vec![create_user_ata_ix(
+        &payer,
+        &pool_state.lp_mint,
+        &lp_token_program,
+    )];
+    instructions.extend(deposit_instr(
+        config,
+        payer,
+        pool_id,
+        pool_state.token_0_mint,
+        pool_state.token_1_mint,
+        pool_state.lp_mint,
+        pool_state.token_0_vault,
+        pool_state.token_1_vault,
+        get_user_ata(&payer, &pool_state.token_0_mint, &pool_state.token_0_program),
+        get_user_ata(&payer, &pool_state.token_1_mint, &pool_state.token_1_program),
+        get_user_ata(&payer, &pool_state.lp_mint, &lp_token_program),
+        lp_token_amount,
+        maximum_token_0_amount,
+        maximum_token_1_amount,
+    )?);
+    Ok(instructions)
+}
+
+// 移除流动性: 实际到账数量扣掉从 vault 转出的转账手续费, 再按 config.slippage 收紧最小取回
+// 代币 ata 不存在时会创建
+pub fn prepare_withdraw_instructions(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_id: Pubkey,
+    quoter: &PoolQuoter,
+    lp_token_amount: u64,
+) -> Result<Vec<Instruction>> {
+    let pool_state = &quoter.reserves.pool_state;
+    let quote = quoter.withdraw(lp_token_amount)?;
+    let minimum_token_0_amount = amount_with_slippage(quote.token_0_amount, config.slippage, false);
+    let minimum_token_1_amount = amount_with_slippage(quote.token_1_amount, config.slippage, false);
+
+    let lp_token_program = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?;
+    let mut instructions = vec![
+        create_user_ata_ix(&payer, &pool_state.token_0_mint, &pool_state.token_0_program),
+        create_user_ata_ix(&payer, &pool_state.token_1_mint, &pool_state.token_1_program),
+    ];
+    instructions.extend(withdraw_instr(
+        config,
+        payer,
+        pool_id,
+        pool_state.token_0_mint,
+        pool_state.token_1_mint,
+        pool_state.lp_mint,
+        pool_state.token_0_vault,
+        pool_state.token_1_vault,
+        get_user_ata(&payer, &pool_state.token_0_mint, &pool_state.token_0_program),
+        get_user_ata(&payer, &pool_state.token_1_mint, &pool_state.token_1_program),
+        get_user_ata(&payer, &pool_state.lp_mint, &lp_token_program),
+        lp_token_amount,
+        minimum_token_0_amount,
+        minimum_token_1_amount,
+    )?);
+    Ok(instructions)
+}
+
+// 一次兑换涉及的两边账户, 按输入 / 输出方向整理好
+struct SwapSides {
+    input_vault: Pubkey,
+    output_vault: Pubkey,
+    input_token_program: Pubkey,
+    output_token_program: Pubkey,
+    input_token_mint: Pubkey,
+    output_token_mint: Pubkey,
+}
+
+fn swap_sides(pool_state: &PoolState, input_token_mint: &Pubkey) -> Result<SwapSides> {
+    let (token_0_mint, token_1_mint) = (pool_state.token_0_mint, pool_state.token_1_mint);
+    if *input_token_mint == token_0_mint {
+        Ok(SwapSides {
+            input_vault: pool_state.token_0_vault,
+            output_vault: pool_state.token_1_vault,
+            input_token_program: pool_state.token_0_program,
+            output_token_program: pool_state.token_1_program,
+            input_token_mint: token_0_mint,
+            output_token_mint: token_1_mint,
+        })
+    } else if *input_token_mint == token_1_mint {
+        Ok(SwapSides {
+            input_vault: pool_state.token_1_vault,
+            output_vault: pool_state.token_0_vault,
+            input_token_program: pool_state.token_1_program,
+            output_token_program: pool_state.token_0_program,
+            input_token_mint: token_1_mint,
+            output_token_mint: token_0_mint,
         })
-        .instructions()?;
+    } else {
+        Err(anyhow!("{} is not a mint of this pool", input_token_mint))
+    }
+}
+
+// 固定输入兑换: 按 PoolQuoter 计算用户实际到账数量 (扣除转账手续费), 再按 config.slippage 得到最小输出
+// 输出代币的 ata 不存在时会创建
+pub fn prepare_swap_base_input_instructions(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_id: Pubkey,
+    quoter: &PoolQuoter,
+    input_token_mint: Pubkey,
+    amount_in: u64,
+) -> Result<Vec<Instruction>> {
+    let pool_state = &quoter.reserves.pool_state;
+    let sides = swap_sides(pool_state, &input_token_mint)?;
+    let quote = quoter.swap_base_input(&input_token_mint, amount_in)?;
+    let minimum_amount_out = amount_with_slippage(quote.amount_received, config.slippage, false);
+
+    let mut instructions = vec![create_user_ata_ix(
+        &payer,
+        &sides.output_token_mint,
+        &sides.output_token_program,
+    )];
+    instructions.extend(swap_base_input_instr(
+        config,
+        payer,
+        pool_id,
+        pool_state.amm_config,
+        pool_state.observation_key,
+        get_user_ata(&payer, &sides.input_token_mint, &sides.input_token_program),
+        get_user_ata(&payer, &sides.output_token_mint, &sides.output_token_program),
+        sides.input_vault,
+        sides.output_vault,
+        sides.input_token_program,
+        sides.output_token_program,
+        sides.input_token_mint,
+        sides.output_token_mint,
+        amount_in,
+        minimum_amount_out,
+    )?);
+    Ok(instructions)
+}
+
+// 固定输出兑换: amount_out 为用户实际到账数量, 按 PoolQuoter 计算需要转出的输入 (含转账手续费)
+// 再按 config.slippage 得到最大输入, 输出代币的 ata 不存在时会创建
+pub fn prepare_swap_base_output_instructions(
+    config: &ClientConfig,
+    payer: Pubkey,
+    pool_id: Pubkey,
+    quoter: &PoolQuoter,
+    input_token_mint: Pubkey,
+    amount_out: u64,
+) -> Result<Vec<Instruction>> {
+    let pool_state = &quoter.reserves.pool_state;
+    let sides = swap_sides(pool_state, &input_token_mint)?;
+    let quote = quoter.swap_base_output(&input_token_mint, amount_out)?;
+    let max_amount_in = amount_with_slippage(quote.amount_in, config.slippage, true);
+
+    let mut instructions = vec![create_user_ata_ix(
+        &payer,
+        &sides.output_token_mint,
+        &sides.output_token_program,
+    )];
+    instructions.extend(swap_base_output_instr(
+        config,
+        payer,
+        pool_id,
+        pool_state.amm_config,
+        pool_state.observation_key,
+        get_user_ata(&payer, &sides.input_token_mint, &sides.input_token_program),
+        get_user_ata(&payer, &sides.output_token_mint, &sides.output_token_program),
+        sides.input_vault,
+        sides.output_vault,
+        sides.input_token_program,
+        sides.output_token_program,
+        sides.input_token_mint,
+        sides.output_token_mint,
+        max_amount_in,
+        amount_out,
+    )?);
+    Ok(instructions)
+}
+
+// 费率的分母, trade_fee_rate = 2500 即 0.25%; protocol / fund 费率按交易手续费的比例计算
+pub const FEE_RATE_DENOMINATOR_VALUE: u64 = 1_000_000;
+
+// pool_state.status 的位: 置 1 表示禁用对应操作
+pub const POOL_STATUS_DISABLE_DEPOSIT: u8 = 1 << 0;
+pub const POOL_STATUS_DISABLE_WITHDRAW: u8 = 1 << 1;
+pub const POOL_STATUS_DISABLE_SWAP: u8 = 1 << 2;
+
+// update_amm_config 支持修改的字段, 对应指令参数 param = 0..=6
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AmmConfigUpdate {
+    TradeFeeRate(u64),
+    ProtocolFeeRate(u64),
+    FundFeeRate(u64),
+    ProtocolOwner(Pubkey),
+    FundOwner(Pubkey),
+    CreatePoolFee(u64),
+    DisableCreatePool(bool),
+}
+
+fn check_fee_rates(trade_fee_rate: u64, protocol_fee_rate: u64, fund_fee_rate: u64) -> Result<()> {
+    if trade_fee_rate >= FEE_RATE_DENOMINATOR_VALUE {
+        return Err(anyhow!("trade fee rate {} is too large", trade_fee_rate));
+    }
+    if protocol_fee_rate + fund_fee_rate > FEE_RATE_DENOMINATOR_VALUE {
+        return Err(anyhow!(
+            "protocol fee rate {} + fund fee rate {} is too large",
+            protocol_fee_rate,
+            fund_fee_rate
+        ));
+    }
+    Ok(())
+}
+
+// 创建新的费率档位, 只有 config.admin 可以签名
+pub fn create_amm_config_instr(
+    config: &ClientConfig,
+    amm_config_index: u16,
+    trade_fee_rate: u64,
+    protocol_fee_rate: u64,
+    fund_fee_rate: u64,
+    create_pool_fee: u64,
+) -> Result<Vec<Instruction>> {
+    check_fee_rates(trade_fee_rate, protocol_fee_rate, fund_fee_rate)?;
+    let instruction = Instruction {
+        program_id: config.raydium_cp_program,
+        accounts: raydium_cp_accounts::CreateAmmConfig {
+            owner: config.admin()?,
+            amm_config: get_amm_config_address(&config.raydium_cp_program, amm_config_index),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None),
+        data: raydium_cp_instructions::CreateAmmConfig {
+            index: amm_config_index,
+            trade_fee_rate,
+            protocol_fee_rate,
+            fund_fee_rate,
+            create_pool_fee,
+        }
+        .data(),
+    };
+    Ok(vec![instruction])
+}
+
+// 修改费率档位中的一个字段, 修改 owner 时新 owner 通过 remaining accounts 传入
+pub fn update_amm_config_instr(
+    config: &ClientConfig,
+    amm_config: Pubkey,
+    update: AmmConfigUpdate,
+) -> Result<Vec<Instruction>> {
+    let mut accounts = raydium_cp_accounts::UpdateAmmConfig {
+        owner: config.admin()?,
+        amm_config,
+    }
+    .to_account_metas(None);
+    let (param, value) = match update {
+        AmmConfigUpdate::TradeFeeRate(rate) => {
+            check_fee_rates(rate, 0, 0)?;
+            (0, rate)
+        }
+        AmmConfigUpdate::ProtocolFeeRate(rate) => {
+            check_fee_rates(0, rate, 0)?;
+            (1, rate)
+        }
+        AmmConfigUpdate::FundFeeRate(rate) => {
+            check_fee_rates(0, 0, rate)?;
+            (2, rate)
+        }
+        AmmConfigUpdate::ProtocolOwner(owner) => {
+            accounts.push(AccountMeta::new_readonly(owner, false));
+            (3, 0)
+        }
+        AmmConfigUpdate::FundOwner(owner) => {
+            accounts.push(AccountMeta::new_readonly(owner, false));
+            (4, 0)
+        }
+        AmmConfigUpdate::CreatePoolFee(fee) => (5, fee),
+        AmmConfigUpdate::DisableCreatePool(disable) => (6, u64::from(disable)),
+    };
+    let instruction = Instruction {
+        program_id: config.raydium_cp_program,
+        accounts,
+        data: raydium_cp_instructions::UpdateAmmConfig { param, value }.data(),
+    };
+    Ok(vec![instruction])
+}
+
+// status 由 POOL_STATUS_DISABLE_* 组合, 0 表示全部开放
+pub fn update_pool_status_instr(
+    config: &ClientConfig,
+    pool_id: Pubkey,
+    status: u8,
+) -> Result<Vec<Instruction>> {
+    let instruction = Instruction {
+        program_id: config.raydium_cp_program,
+        accounts: raydium_cp_accounts::UpdatePoolStatus {
+            authority: config.admin()?,
+            pool_state: pool_id,
+        }
+        .to_account_metas(None),
+        data: raydium_cp_instructions::UpdatePoolStatus { status }.data(),
+    };
+    Ok(vec![instruction])
+}
+
+// owner 必须是 amm_config.protocol_owner 或管理员, 请求数量超过累计的协议费时按累计值领取
+#[allow(clippy::too_many_arguments)]
+pub fn collect_protocol_fee_instr(
+    config: &ClientConfig,
+    owner: Pubkey,
+    pool_id: Pubkey,
+    amm_config: Pubkey,
+    token_0_vault: Pubkey,
+    token_1_vault: Pubkey,
+    vault_0_mint: Pubkey,
+    vault_1_mint: Pubkey,
+    recipient_token_0_account: Pubkey,
+    recipient_token_1_account: Pubkey,
+    amount_0_requested: u64,
+    amount_1_requested: u64,
+) -> Result<Vec<Instruction>> {
+    let instruction = Instruction {
+        program_id: config.raydium_cp_program,
+        accounts: raydium_cp_accounts::CollectProtocolFee {
+            owner,
+            authority: get_authority_address(&config.raydium_cp_program),
+            pool_state: pool_id,
+            amm_config,
+            token_0_vault,
+            token_1_vault,
+            vault_0_mint,
+            vault_1_mint,
+            recipient_token_0_account,
+            recipient_token_1_account,
+            token_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+            token_program_2022: spl_token_2022::id(),
+        }
+        .to_account_metas(None),
+        data: raydium_cp_instructions::CollectProtocolFee {
+            amount_0_requested,
+            amount_1_requested,
+        }
+        .data(),
+    };
+    Ok(vec![instruction])
+}
+
+// owner 必须是 amm_config.fund_owner 或管理员, 请求数量超过累计的基金费时按累计值领取
+#[allow(clippy::too_many_arguments)]
+pub fn collect_fund_fee_instr(
+    config: &ClientConfig,
+    owner: Pubkey,
+    pool_id: Pubkey,
+    amm_config: Pubkey,
+    token_0_vault: Pubkey,
+    token_1_vault: Pubkey,
+    vault_0_mint: Pubkey,
+    vault_1_mint: Pubkey,
+    recipient_token_0_account: Pubkey,
+    recipient_token_1_account: Pubkey,
+    amount_0_requested: u64,
+    amount_1_requested: u64,
+) -> Result<Vec<Instruction>> {
+    let instruction = Instruction {
+        program_id: config.raydium_cp_program,
+        accounts: raydium_cp_accounts::CollectFundFee {
+            owner,
+            authority: get_authority_address(&config.raydium_cp_program),
+            pool_state: pool_id,
+            amm_config,
+            token_0_vault,
+            token_1_vault,
+            vault_0_mint,
+            vault_1_mint,
+            recipient_token_0_account,
+            recipient_token_1_account,
+            token_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+            token_program_2022: spl_token_2022::id(),
+        }
+        .to_account_metas(None),
+        data: raydium_cp_instructions::CollectFundFee {
+            amount_0_requested,
+            amount_1_requested,
+        }
+        .data(),
+    };
+    Ok(vec![instruction])
+}
+
+// 管理员领取池子累计的全部协议费到自己的 ata (请求 u64::MAX, 由程序按累计值截断), ata 不存在时会创建
+pub fn prepare_collect_protocol_fee_instructions(
+    config: &ClientConfig,
+    pool_id: Pubkey,
+    pool_state: &PoolState,
+) -> Result<Vec<Instruction>> {
+    let admin = config.admin()?;
+    let mut instructions = vec![
+        create_user_ata_ix(
+            &admin,
+            &pool_state.token_0_mint,
+            &pool_state.token_0_program,
+        ),
+        create_user_ata_ix(
+            &admin,
+            &pool_state.token_1_mint,
+            &pool_state.token_1_program,
+        ),
+    ];
+    instructions.extend(collect_protocol_fee_instr(
+        config,
+        admin,
+        pool_id,
+        pool_state.amm_config,
+        pool_state.token_0_vault,
+        pool_state.token_1_vault,
+        pool_state.token_0_mint,
+        pool_state.token_1_mint,
+        get_user_ata(
+            &admin,
+            &pool_state.token_0_mint,
+            &pool_state.token_0_program,
+        ),
+        get_user_ata(
+            &admin,
+            &pool_state.token_1_mint,
+            &pool_state.token_1_program,
+        ),
+        u64::MAX,
+        u64::MAX,
+    )?);
+    Ok(instructions)
+}
+
+// 管理员领取池子累计的全部基金费到自己的 ata (同上), ata 不存在时会创建
+pub fn prepare_collect_fund_fee_instructions(
+    config: &ClientConfig,
+    pool_id: Pubkey,
+    pool_state: &PoolState,
+) -> Result<Vec<Instruction>> {
+    let admin = config.admin()?;
+    let mut instructions = vec![
+        create_user_ata_ix(
+            &admin,
+            &pool_state.token_0_mint,
+            &pool_state.token_0_program,
+        ),
+        create_user_ata_ix(
+            &admin,
+            &pool_state.token_1_mint,
+            &pool_state.token_1_program,
+        ),
+    ];
+    instructions.extend(collect_fund_fee_instr(
+        config,
+        admin,
+        pool_id,
+        pool_state.amm_config,
+        pool_state.token_0_vault,
+        pool_state.token_1_vault,
+        pool_state.token_0_mint,
+        pool_state.token_1_mint,
+        get_user_ata(
+            &admin,
+            &pool_state.token_0_mint,
+            &pool_state.token_0_program,
+        ),
+        get_user_ata(
+            &admin,
+            &pool_state.token_1_mint,
+            &pool_state.token_1_program,
+        ),
+        u64::MAX,
+        u64::MAX,
+    )?);
     Ok(instructions)
 }
+
+fn get_user_ata(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address_with_program_id(
+        owner,
+        mint,
+        token_program,
+    )
+}
+
+fn create_user_ata_ix(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Instruction {
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+        owner,
+        owner,
+        mint,
+        token_program,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initialize_pool_instr_without_keypair_file() {
+        let config = ClientConfigBuilder::default()
+            .raydium_cp_program(Pubkey::from_str(RAYDIUM_CP_DEVNET_PROGRAM_ID).unwrap())
+            .slippage(0.005)
+            .build()
+            .unwrap();
+        assert_eq!(config.http_url(), "https://api.mainnet-beta.solana.com");
+
+        let payer = Pubkey::from_str("9WYirnyBy8RMBuoatC9yVCRQZ6AYpKpMyKmr2TrjypCG").unwrap();
+        let token_0_mint = Pubkey::from_str("5LdzEFRMQy2SCf2SD4TXkRao8ELh7FZAzqQGia5DNxKE").unwrap();
+        let token_1_mint = Pubkey::from_str("So11111111111111111111111111111111111111112").unwrap();
+        let token_0_program = spl_token_2022::id();
+        let token_1_program = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap();
+        let instructions = initialize_pool_instr(
+            &config,
+            payer,
+            0,
+            token_0_mint,
+            token_1_mint,
+            token_0_program,
+            token_1_program,
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &payer,
+                &token_0_mint,
+                &token_0_program,
+            ),
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &payer,
+                &token_1_mint,
+                &token_1_program,
+            ),
+            Pubkey::new_unique(),
+            1_000 * 10_u64.pow(9),
+            10_u64.pow(9),
+            0,
+        )
+        .unwrap();
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(instructions[0].program_id, config.raydium_cp_program());
+        assert_eq!(instructions[0].accounts.len(), 20);
+        assert_eq!(instructions[0].accounts[0].pubkey, payer);
+        assert!(instructions[0].accounts[0].is_signer);
+    }
+
+    #[test]
+    fn test_canonical_pool_address() {
+        let program_id = Pubkey::from_str(RAYDIUM_CP_PROGRAM_ID).unwrap();
+        let mint_a = Pubkey::from_str("5LdzEFRMQy2SCf2SD4TXkRao8ELh7FZAzqQGia5DNxKE").unwrap();
+        let mint_b = Pubkey::from_str("So11111111111111111111111111111111111111112").unwrap();
+        let (token_0_mint, token_1_mint, swapped) = sort_mints(mint_b, mint_a);
+        assert!(token_0_mint < token_1_mint);
+        assert_eq!(swapped, mint_b > mint_a);
+        assert_eq!(
+            get_pool_address_by_mints(&program_id, 0, &mint_a, &mint_b),
+            get_pool_address_by_mints(&program_id, 0, &mint_b, &mint_a)
+        );
+        assert_ne!(
+            get_pool_address_by_mints(&program_id, 0, &mint_a, &mint_b),
+            get_pool_address_by_mints(&program_id, 1, &mint_a, &mint_b)
+        );
+
+        // 反序传入时, 指令里的 token_0 仍然是较小的 mint
+        let config = ClientConfigBuilder::default().build().unwrap();
+        let payer = Pubkey::new_unique();
+        let instructions = initialize_pool_instr(
+            &config,
+            payer,
+            0,
+            token_1_mint,
+            token_0_mint,
+            Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+            spl_token_2022::id(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1,
+            2,
+            0,
+        )
+        .unwrap();
+        let accounts = &instructions[0].accounts;
+        assert_eq!(accounts[4].pubkey, token_0_mint);
+        assert_eq!(accounts[5].pubkey, token_1_mint);
+        assert_eq!(accounts[15].pubkey, spl_token_2022::id());
+    }
+
+    #[test]
+    fn test_swap_instrs() {
+        let config = ClientConfigBuilder::default().build().unwrap();
+        let keys: Vec<Pubkey> = (0..12).map(|_| Pubkey::new_unique()).collect();
+        let build = |base_input: bool| {
+            let instr = if base_input {
+                swap_base_input_instr
+            } else {
+                swap_base_output_instr
+            };
+            instr(
+                &config, keys[0], keys[1], keys[2], keys[3], keys[4], keys[5], keys[6], keys[7],
+                keys[8], keys[9], keys[10], keys[11], 100, 200,
+            )
+            .unwrap()
+        };
+        for (base_input, discriminator) in [
+            (true, [143, 190, 90, 218, 196, 30, 51, 222]),
+            (false, [55, 217, 98, 86, 163, 74, 180, 173]),
+        ] {
+            let instructions = build(base_input);
+            assert_eq!(instructions.len(), 1);
+            let instruction = &instructions[0];
+            assert_eq!(instruction.data[..8], discriminator);
+            assert_eq!(instruction.data[8..16], 100u64.to_le_bytes());
+            assert_eq!(instruction.data[16..24], 200u64.to_le_bytes());
+            // payer, authority, amm_config, pool_state, 输入 / 输出账户, 输入 / 输出 vault,
+            // 输入 / 输出 token program, 输入 / 输出 mint, observation
+            let accounts: Vec<Pubkey> = instruction.accounts.iter().map(|a| a.pubkey).collect();
+            assert_eq!(
+                accounts,
+                vec![
+                    keys[0],
+                    get_authority_address(&config.raydium_cp_program()),
+                    keys[2],
+                    keys[1],
+                    keys[4],
+                    keys[5],
+                    keys[6],
+                    keys[7],
+                    keys[8],
+                    keys[9],
+                    keys[10],
+                    keys[11],
+                    keys[3],
+                ]
+            );
+            assert!(instruction.accounts[0].is_signer);
+            assert!(instruction.accounts[3].is_writable);
+            assert!(!instruction.accounts[10].is_writable);
+        }
+    }
+
+    #[test]
+    fn test_liquidity_instrs() {
+        use crate::raydium::quote::get_transfer_fee;
+        use crate::raydium::utils::PoolReserves;
+        use raydium_cp_swap::states::AmmConfig;
+        use spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeConfig};
+
+        let config = ClientConfigBuilder::default()
+            .slippage(0.01)
+            .build()
+            .unwrap();
+        let payer = Pubkey::new_unique();
+        let pool_id = Pubkey::new_unique();
+        let pool_state = PoolState {
+            token_0_vault: Pubkey::new_unique(),
+            token_1_vault: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            token_0_mint: Pubkey::new_unique(),
+            token_1_mint: Pubkey::new_unique(),
+            token_0_program: spl_token_2022::id(),
+            token_1_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+            lp_supply: 1_000_000,
+            ..Default::default()
+        };
+        // token_0 有 1% 的转账手续费
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        let mint_0_transfer_fee = TransferFeeConfig {
+            older_transfer_fee: transfer_fee,
+            newer_transfer_fee: transfer_fee,
+            ..Default::default()
+        };
+        let quoter = PoolQuoter {
+            reserves: PoolReserves {
+                pool_state: pool_state.clone(),
+                amm_config: AmmConfig::default(),
+                vault_0_amount: 10_000_000,
+                vault_1_amount: 20_000_000,
+            },
+            mint_0_transfer_fee: Some(mint_0_transfer_fee),
+            mint_1_transfer_fee: None,
+            epoch: 1,
+        };
+
+        // 存入 10% 的 lp: vault 需要收到 1_000_000 / 2_000_000
+        let instructions =
+            prepare_deposit_instructions(&config, payer, pool_id, &quoter, 100_000).unwrap();
+        assert_eq!(instructions.len(), 2);
+        let deposit = &instructions[1];
+        assert_eq!(deposit.data[..8], [242, 35, 198, 137, 82, 225, 242, 182]);
+        assert_eq!(deposit.data[8..16], 100_000u64.to_le_bytes());
+        // token_0 转出的数量扣掉转账手续费后 vault 恰好收到 1_000_000
+        let token_0_amount = quoter.deposit(100_000).unwrap().token_0_amount;
+        assert_eq!(
+            token_0_amount
+                - get_transfer_fee(Some(&mint_0_transfer_fee), 1, token_0_amount).unwrap(),
+            1_000_000
+        );
+        assert_eq!(
+            deposit.data[16..24],
+            amount_with_slippage(token_0_amount, 0.01, true).to_le_bytes()
+        );
+        assert_eq!(deposit.data[24..32], 2_020_000u64.to_le_bytes());
+        let accounts: Vec<Pubkey> = deposit.accounts.iter().map(|a| a.pubkey).collect();
+        assert_eq!(
+            accounts,
+            vec![
+                payer,
+                get_authority_address(&config.raydium_cp_program()),
+                pool_id,
+                get_user_ata(&payer, &pool_state.lp_mint, &pool_state.token_1_program),
+                get_user_ata(&payer, &pool_state.token_0_mint, &spl_token_2022::id()),
+                get_user_ata(
+                    &payer,
+                    &pool_state.token_1_mint,
+                    &pool_state.token_1_program
+                ),
+                pool_state.token_0_vault,
+                pool_state.token_1_vault,
+                Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+                spl_token_2022::id(),
+                pool_state.token_0_mint,
+                pool_state.token_1_mint,
+                pool_state.lp_mint,
+            ]
+        );
+
+        // 取回 10% 的 lp: token_0 到账扣掉 1% 手续费
+        let instructions =
+            prepare_withdraw_instructions(&config, payer, pool_id, &quoter, 100_000).unwrap();
+        assert_eq!(instructions.len(), 3);
+        let withdraw = &instructions[2];
+        assert_eq!(withdraw.data[..8], [183, 18, 70, 156, 148, 109, 161, 34]);
+        assert_eq!(withdraw.data[8..16], 100_000u64.to_le_bytes());
+        assert_eq!(
+            withdraw.data[16..24],
+            amount_with_slippage(990_000, 0.01, false).to_le_bytes()
+        );
+        assert_eq!(withdraw.data[24..32], 1_980_000u64.to_le_bytes());
+        assert_eq!(withdraw.accounts.len(), 14);
+        assert_eq!(withdraw.accounts[3].pubkey, accounts[3]);
+        assert_eq!(withdraw.accounts[13].pubkey, spl_memo::id());
+        assert!(withdraw.accounts[0].is_signer);
+    }
+
+    #[test]
+    fn test_admin_instructions() {
+        let config = ClientConfigBuilder::default().build().unwrap();
+        assert!(create_amm_config_instr(&config, 1, 2500, 120_000, 40_000, 0).is_err());
+
+        let admin = Pubkey::new_unique();
+        let config = ClientConfigBuilder::default().admin(admin).build().unwrap();
+        let instructions = create_amm_config_instr(&config, 1, 2500, 120_000, 40_000, 0).unwrap();
+        let accounts = &instructions[0].accounts;
+        assert_eq!(accounts.len(), 3);
+        assert_eq!(accounts[0].pubkey, admin);
+        assert!(accounts[0].is_signer);
+        assert_eq!(
+            accounts[1].pubkey,
+            get_amm_config_address(&config.raydium_cp_program(), 1)
+        );
+        assert!(create_amm_config_instr(&config, 1, FEE_RATE_DENOMINATOR_VALUE, 0, 0, 0).is_err());
+        assert!(create_amm_config_instr(&config, 1, 2500, 600_000, 600_000, 0).is_err());
+
+        // 修改 owner 时新 owner 作为 remaining account 传入
+        let amm_config = get_amm_config_address(&config.raydium_cp_program(), 1);
+        let new_owner = Pubkey::new_unique();
+        let instructions =
+            update_amm_config_instr(&config, amm_config, AmmConfigUpdate::FundOwner(new_owner))
+                .unwrap();
+        assert_eq!(instructions[0].accounts.len(), 3);
+        assert_eq!(instructions[0].accounts[2].pubkey, new_owner);
+        let instructions =
+            update_amm_config_instr(&config, amm_config, AmmConfigUpdate::TradeFeeRate(3000))
+                .unwrap();
+        assert_eq!(instructions[0].accounts.len(), 2);
+
+        let pool_id = Pubkey::new_unique();
+        let instructions = update_pool_status_instr(
+            &config,
+            pool_id,
+            POOL_STATUS_DISABLE_DEPOSIT | POOL_STATUS_DISABLE_SWAP,
+        )
+        .unwrap();
+        assert_eq!(instructions[0].accounts[1].pubkey, pool_id);
+        assert_eq!(*instructions[0].data.last().unwrap(), 0b101);
+    }
+
+    #[test]
+    fn test_collect_fee_instructions() {
+        let admin = Pubkey::new_unique();
+        let config = ClientConfigBuilder::default().admin(admin).build().unwrap();
+        let pool_state = PoolState {
+            amm_config: get_amm_config_address(&config.raydium_cp_program(), 0),
+            token_0_vault: Pubkey::new_unique(),
+            token_1_vault: Pubkey::new_unique(),
+            token_0_mint: Pubkey::new_unique(),
+            token_1_mint: Pubkey::new_unique(),
+            token_0_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+            token_1_program: spl_token_2022::id(),
+            ..Default::default()
+        };
+        let pool_id = Pubkey::new_unique();
+        for instructions in [
+            prepare_collect_protocol_fee_instructions(&config, pool_id, &pool_state).unwrap(),
+            prepare_collect_fund_fee_instructions(&config, pool_id, &pool_state).unwrap(),
+        ] {
+            assert_eq!(instructions.len(), 3);
+            let accounts = &instructions[2].accounts;
+            assert_eq!(accounts.len(), 12);
+            assert_eq!(accounts[0].pubkey, admin);
+            assert_eq!(
+                accounts[9].pubkey,
+                get_user_ata(&admin, &pool_state.token_1_mint, &spl_token_2022::id())
+            );
+        }
+
+        // owner, authority, pool_state, amm_config, 两个 vault, 两个 mint, 两个接收账户,
+        // token program, token program 2022
+        let keys: Vec<Pubkey> = (0..9).map(|_| Pubkey::new_unique()).collect();
+        let expected = vec![
+            keys[0],
+            get_authority_address(&config.raydium_cp_program()),
+            keys[1],
+            keys[2],
+            keys[3],
+            keys[4],
+            keys[5],
+            keys[6],
+            keys[7],
+            keys[8],
+            Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+            spl_token_2022::id(),
+        ];
+        for (protocol_fee, discriminator) in [
+            (true, [136, 136, 252, 221, 194, 66, 126, 89]),
+            (false, [167, 138, 78, 149, 223, 194, 6, 126]),
+        ] {
+            let instr = if protocol_fee {
+                collect_protocol_fee_instr
+            } else {
+                collect_fund_fee_instr
+            };
+            let instructions = instr(
+                &config, keys[0], keys[1], keys[2], keys[3], keys[4], keys[5], keys[6], keys[7],
+                keys[8], 100, 200,
+            )
+            .unwrap();
+            let instruction = &instructions[0];
+            assert_eq!(instruction.data[..8], discriminator);
+            assert_eq!(instruction.data[8..16], 100u64.to_le_bytes());
+            assert_eq!(instruction.data[16..24], 200u64.to_le_bytes());
+            let accounts: Vec<Pubkey> = instruction.accounts.iter().map(|a| a.pubkey).collect();
+            assert_eq!(accounts, expected);
+            assert!(instruction.accounts[0].is_signer);
+            assert!(instruction.accounts[2].is_writable);
+            assert!(instruction.accounts[4].is_writable);
+            assert!(instruction.accounts[8].is_writable);
+        }
+    }
+}
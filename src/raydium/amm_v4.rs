@@ -0,0 +1,496 @@
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+use super::quote::{SwapQuote, SwapQuoter};
+use super::utils::{amount_with_slippage, unpack_token_amount};
+use crate::utils::SPL_TOKEN_PROGRAM_ID;
+
+// raydium amm v4 (基于 openbook 订单簿的老版本 amm)
+pub const RAYDIUM_AMM_V4_PROGRAM_ID: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+pub const RAYDIUM_AMM_V4_DEVNET_PROGRAM_ID: &str = "HWy1jotHpo6UqeQxx49dpYYdQB8wj9Qk9MdxwjLvDHB8";
+pub const OPENBOOK_PROGRAM_ID: &str = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
+pub const AMM_AUTHORITY_SEED: &str = "amm authority";
+
+pub const AMM_INFO_LEN: usize = 752;
+pub const MARKET_STATE_LEN: usize = 388;
+
+// 指令 tag
+pub const SWAP_BASE_IN_TAG: u8 = 9;
+pub const SWAP_BASE_OUT_TAG: u8 = 11;
+
+// AmmInfo.status 中允许 swap 的状态, WaitingTrade 在 pool_open_time 之后由第一笔 swap 切换为 SwapOnly
+pub const AMM_STATUS_INITIALIZED: u64 = 1;
+pub const AMM_STATUS_SWAP_ONLY: u64 = 6;
+pub const AMM_STATUS_WAITING_TRADE: u64 = 7;
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(data[offset..offset + 8].try_into()?))
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    Ok(Pubkey::try_from(&data[offset..offset + 32])?)
+}
+
+// AmmInfo 中 swap 和报价用得到的字段
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AmmInfo {
+    pub status: u64,
+    pub nonce: u64,
+    pub coin_decimals: u64,
+    pub pc_decimals: u64,
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+    pub swap_fee_numerator: u64,
+    pub swap_fee_denominator: u64,
+    pub need_take_pnl_coin: u64,
+    pub need_take_pnl_pc: u64,
+    pub pool_open_time: u64,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub coin_vault_mint: Pubkey,
+    pub pc_vault_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub open_orders: Pubkey,
+    pub market: Pubkey,
+    pub market_program: Pubkey,
+    pub target_orders: Pubkey,
+    pub amm_owner: Pubkey,
+    pub lp_amount: u64,
+}
+
+impl AmmInfo {
+    pub fn swap_enabled(&self) -> bool {
+        matches!(
+            self.status,
+            AMM_STATUS_INITIALIZED | AMM_STATUS_SWAP_ONLY | AMM_STATUS_WAITING_TRADE
+        )
+    }
+
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        if data.len() != AMM_INFO_LEN {
+            return Err(anyhow!("not an amm v4 account"));
+        }
+        Ok(Self {
+            status: read_u64(data, 0)?,
+            nonce: read_u64(data, 8)?,
+            coin_decimals: read_u64(data, 32)?,
+            pc_decimals: read_u64(data, 40)?,
+            // fees 从 128 开始
+            trade_fee_numerator: read_u64(data, 144)?,
+            trade_fee_denominator: read_u64(data, 152)?,
+            swap_fee_numerator: read_u64(data, 176)?,
+            swap_fee_denominator: read_u64(data, 184)?,
+            // state_data 从 192 开始
+            need_take_pnl_coin: read_u64(data, 192)?,
+            need_take_pnl_pc: read_u64(data, 200)?,
+            pool_open_time: read_u64(data, 224)?,
+            coin_vault: read_pubkey(data, 336)?,
+            pc_vault: read_pubkey(data, 368)?,
+            coin_vault_mint: read_pubkey(data, 400)?,
+            pc_vault_mint: read_pubkey(data, 432)?,
+            lp_mint: read_pubkey(data, 464)?,
+            open_orders: read_pubkey(data, 496)?,
+            market: read_pubkey(data, 528)?,
+            market_program: read_pubkey(data, 560)?,
+            target_orders: read_pubkey(data, 592)?,
+            amm_owner: read_pubkey(data, 688)?,
+            lp_amount: read_u64(data, 720)?,
+        })
+    }
+}
+
+// openbook MarketState 中 swap 需要的账户
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MarketState {
+    pub own_address: Pubkey,
+    pub vault_signer_nonce: u64,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub event_queue: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+}
+
+impl MarketState {
+    // 账户数据前面有 5 字节 "serum" 填充, 后面有 7 字节 "padding" 填充
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        if data.len() != MARKET_STATE_LEN {
+            return Err(anyhow!("not an openbook market account"));
+        }
+        Ok(Self {
+            own_address: read_pubkey(data, 13)?,
+            vault_signer_nonce: read_u64(data, 45)?,
+            coin_mint: read_pubkey(data, 53)?,
+            pc_mint: read_pubkey(data, 85)?,
+            coin_vault: read_pubkey(data, 117)?,
+            pc_vault: read_pubkey(data, 165)?,
+            event_queue: read_pubkey(data, 253)?,
+            bids: read_pubkey(data, 285)?,
+            asks: read_pubkey(data, 317)?,
+        })
+    }
+
+    pub fn vault_signer(&self, market_program: &Pubkey) -> Result<Pubkey> {
+        Ok(Pubkey::create_program_address(
+            &[
+                self.own_address.as_ref(),
+                &self.vault_signer_nonce.to_le_bytes(),
+            ],
+            market_program,
+        )?)
+    }
+}
+
+pub fn get_amm_authority_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[AMM_AUTHORITY_SEED.as_bytes()], program_id).0
+}
+
+// amm v4 池子快照: amm 账户 + 对应的 openbook 市场 + 两个 vault 余额
+#[derive(Clone, Debug, PartialEq)]
+pub struct AmmV4Pool {
+    pub program_id: Pubkey,
+    pub amm_id: Pubkey,
+    pub amm_info: AmmInfo,
+    pub market: MarketState,
+    pub coin_vault_amount: u64,
+    pub pc_vault_amount: u64,
+}
+
+impl AmmV4Pool {
+    pub fn fetch(client: &RpcClient, program_id: &Pubkey, amm_id: &Pubkey) -> Result<Self> {
+        let amm_info = AmmInfo::from_account_data(&client.get_account_data(amm_id)?)?;
+        let accounts = client.get_multiple_accounts(&[
+            amm_info.market,
+            amm_info.coin_vault,
+            amm_info.pc_vault,
+        ])?;
+        let [Some(market_account), Some(coin_vault_account), Some(pc_vault_account)] =
+            accounts.as_slice()
+        else {
+            return Err(anyhow!("amm v4 market or vault account not found"));
+        };
+        Ok(Self {
+            program_id: *program_id,
+            amm_id: *amm_id,
+            market: MarketState::from_account_data(&market_account.data)?,
+            coin_vault_amount: unpack_token_amount(coin_vault_account)?,
+            pc_vault_amount: unpack_token_amount(pc_vault_account)?,
+            amm_info,
+        })
+    }
+
+    // 与链上 calc_total_without_take_pnl_no_orderbook 一致 (订单簿已关闭的池子)
+    pub fn trading_reserves(&self) -> Result<(u64, u64)> {
+        let coin = self
+            .coin_vault_amount
+            .checked_sub(self.amm_info.need_take_pnl_coin)
+            .ok_or_else(|| anyhow!("coin vault less than pnl"))?;
+        let pc = self
+            .pc_vault_amount
+            .checked_sub(self.amm_info.need_take_pnl_pc)
+            .ok_or_else(|| anyhow!("pc vault less than pnl"))?;
+        Ok((coin, pc))
+    }
+
+    fn swap_accounts(
+        &self,
+        user_source_token: Pubkey,
+        user_destination_token: Pubkey,
+        user_owner: Pubkey,
+    ) -> Result<Vec<AccountMeta>> {
+        let amm_info = &self.amm_info;
+        let market_program = amm_info.market_program;
+        Ok(vec![
+            AccountMeta::new_readonly(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?, false),
+            AccountMeta::new(self.amm_id, false),
+            AccountMeta::new_readonly(get_amm_authority_address(&self.program_id), false),
+            AccountMeta::new(amm_info.open_orders, false),
+            AccountMeta::new(amm_info.target_orders, false),
+            AccountMeta::new(amm_info.coin_vault, false),
+            AccountMeta::new(amm_info.pc_vault, false),
+            AccountMeta::new_readonly(market_program, false),
+            AccountMeta::new(amm_info.market, false),
+            AccountMeta::new(self.market.bids, false),
+            AccountMeta::new(self.market.asks, false),
+            AccountMeta::new(self.market.event_queue, false),
+            AccountMeta::new(self.market.coin_vault, false),
+            AccountMeta::new(self.market.pc_vault, false),
+            AccountMeta::new_readonly(self.market.vault_signer(&market_program)?, false),
+            AccountMeta::new(user_source_token, false),
+            AccountMeta::new(user_destination_token, false),
+            AccountMeta::new_readonly(user_owner, true),
+        ])
+    }
+
+    // 输入方向: (输入储备, 输出储备, 输出 mint), 池子不允许 swap 时返回错误
+    fn sides(&self, input_token_mint: &Pubkey) -> Result<(u64, u64, Pubkey)> {
+        if !self.amm_info.swap_enabled() {
+            return Err(anyhow!(
+                "amm {} does not allow swap, status {}",
+                self.amm_id,
+                self.amm_info.status
+            ));
+        }
+        let (coin, pc) = self.trading_reserves()?;
+        if *input_token_mint == self.amm_info.coin_vault_mint {
+            Ok((coin, pc, self.amm_info.pc_vault_mint))
+        } else if *input_token_mint == self.amm_info.pc_vault_mint {
+            Ok((pc, coin, self.amm_info.coin_vault_mint))
+        } else {
+            Err(anyhow!("{} is not a mint of this pool", input_token_mint))
+        }
+    }
+}
+
+pub fn swap_base_in_instr(
+    pool: &AmmV4Pool,
+    user_source_token: Pubkey,
+    user_destination_token: Pubkey,
+    user_owner: Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Instruction> {
+    let mut data = vec![SWAP_BASE_IN_TAG];
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    Ok(Instruction {
+        program_id: pool.program_id,
+        accounts: pool.swap_accounts(user_source_token, user_destination_token, user_owner)?,
+        data,
+    })
+}
+
+pub fn swap_base_out_instr(
+    pool: &AmmV4Pool,
+    user_source_token: Pubkey,
+    user_destination_token: Pubkey,
+    user_owner: Pubkey,
+    max_amount_in: u64,
+    amount_out: u64,
+) -> Result<Instruction> {
+    let mut data = vec![SWAP_BASE_OUT_TAG];
+    data.extend_from_slice(&max_amount_in.to_le_bytes());
+    data.extend_from_slice(&amount_out.to_le_bytes());
+    Ok(Instruction {
+        program_id: pool.program_id,
+        accounts: pool.swap_accounts(user_source_token, user_destination_token, user_owner)?,
+        data,
+    })
+}
+
+// 与链上 CheckedCeilDiv 一致, 商为 0 时四舍五入
+fn checked_ceil_div(numerator: u128, denominator: u128) -> Option<u128> {
+    let quotient = numerator.checked_div(denominator)?;
+    if quotient == 0 {
+        return Some(if numerator.checked_mul(2)? >= denominator {
+            1
+        } else {
+            0
+        });
+    }
+    if numerator.checked_rem(denominator)? > 0 {
+        return quotient.checked_add(1);
+    }
+    Some(quotient)
+}
+
+impl SwapQuoter for AmmV4Pool {
+    fn mints(&self) -> (Pubkey, Pubkey) {
+        (self.amm_info.coin_vault_mint, self.amm_info.pc_vault_mint)
+    }
+
+    // swap_fee 向上取整, 剩余部分按 x * y = k 计算输出 (向下取整)
+    fn swap_base_input(&self, input_token_mint: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
+        let (input_reserve, output_reserve, _) = self.sides(input_token_mint)?;
+        let swap_fee = checked_ceil_div(
+            u128::from(amount_in) * u128::from(self.amm_info.swap_fee_numerator),
+            u128::from(self.amm_info.swap_fee_denominator),
+        )
+        .ok_or_else(|| anyhow!("swap fee calculation failed"))?;
+        let amount_in_after_fee = u128::from(amount_in)
+            .checked_sub(swap_fee)
+            .ok_or_else(|| anyhow!("swap fee exceeds amount in"))?;
+        let amount_out = u128::from(output_reserve)
+            .checked_mul(amount_in_after_fee)
+            .and_then(|n| n.checked_div(u128::from(input_reserve) + amount_in_after_fee))
+            .ok_or_else(|| anyhow!("swap base in calculation failed"))?;
+        let amount_out = u64::try_from(amount_out)?;
+        Ok(SwapQuote {
+            amount_in,
+            amount_out,
+            amount_received: amount_out,
+            trade_fee: u64::try_from(swap_fee)?,
+            ..Default::default()
+        })
+    }
+
+    // 先按 x * y = k 反推输入 (向上取整), 再按 swap_fee 放大
+    fn swap_base_output(
+        &self,
+        input_token_mint: &Pubkey,
+        amount_out_less_fee: u64,
+    ) -> Result<SwapQuote> {
+        let (input_reserve, output_reserve, _) = self.sides(input_token_mint)?;
+        let denominator = u128::from(output_reserve)
+            .checked_sub(u128::from(amount_out_less_fee))
+            .filter(|d| *d > 0)
+            .ok_or_else(|| anyhow!("amount out exceeds pool reserve"))?;
+        let amount_in_before_fee = checked_ceil_div(
+            u128::from(input_reserve) * u128::from(amount_out_less_fee),
+            denominator,
+        )
+        .ok_or_else(|| anyhow!("swap base out calculation failed"))?;
+        let swap_fee_denominator = u128::from(self.amm_info.swap_fee_denominator);
+        let fee_complement = swap_fee_denominator
+            .checked_sub(u128::from(self.amm_info.swap_fee_numerator))
+            .filter(|d| *d > 0)
+            .ok_or_else(|| anyhow!("invalid swap fee rate"))?;
+        let amount_in =
+            checked_ceil_div(amount_in_before_fee * swap_fee_denominator, fee_complement)
+                .ok_or_else(|| anyhow!("swap fee calculation failed"))?;
+        Ok(SwapQuote {
+            amount_in: u64::try_from(amount_in)?,
+            amount_out: amount_out_less_fee,
+            amount_received: amount_out_less_fee,
+            trade_fee: u64::try_from(amount_in - amount_in_before_fee)?,
+            ..Default::default()
+        })
+    }
+}
+
+fn get_user_ata(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(owner, mint)
+}
+
+// 固定输入兑换, slippage 为小数 (0.01 即 1%), 输出代币的 ata 不存在时会创建
+pub fn prepare_swap_base_in_instructions(
+    pool: &AmmV4Pool,
+    payer: Pubkey,
+    input_token_mint: Pubkey,
+    amount_in: u64,
+    slippage: f64,
+) -> Result<Vec<Instruction>> {
+    let (_, _, output_token_mint) = pool.sides(&input_token_mint)?;
+    let quote = pool.swap_base_input(&input_token_mint, amount_in)?;
+    let minimum_amount_out = amount_with_slippage(quote.amount_received, slippage, false);
+    Ok(vec![
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            &payer,
+            &payer,
+            &output_token_mint,
+            &Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+        ),
+        swap_base_in_instr(
+            pool,
+            get_user_ata(&payer, &input_token_mint),
+            get_user_ata(&payer, &output_token_mint),
+            payer,
+            amount_in,
+            minimum_amount_out,
+        )?,
+    ])
+}
+
+// 固定输出兑换, slippage 为小数 (0.01 即 1%), 输出代币的 ata 不存在时会创建
+pub fn prepare_swap_base_out_instructions(
+    pool: &AmmV4Pool,
+    payer: Pubkey,
+    input_token_mint: Pubkey,
+    amount_out: u64,
+    slippage: f64,
+) -> Result<Vec<Instruction>> {
+    let (_, _, output_token_mint) = pool.sides(&input_token_mint)?;
+    let quote = pool.swap_base_output(&input_token_mint, amount_out)?;
+    let max_amount_in = amount_with_slippage(quote.amount_in, slippage, true);
+    Ok(vec![
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            &payer,
+            &payer,
+            &output_token_mint,
+            &Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+        ),
+        swap_base_out_instr(
+            pool,
+            get_user_ata(&payer, &input_token_mint),
+            get_user_ata(&payer, &output_token_mint),
+            payer,
+            max_amount_in,
+            amount_out,
+        )?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_pool() -> AmmV4Pool {
+        AmmV4Pool {
+            program_id: Pubkey::from_str(RAYDIUM_AMM_V4_PROGRAM_ID).unwrap(),
+            amm_id: Pubkey::new_unique(),
+            amm_info: AmmInfo {
+                status: AMM_STATUS_SWAP_ONLY,
+                swap_fee_numerator: 25,
+                swap_fee_denominator: 10_000,
+                need_take_pnl_coin: 1_000,
+                need_take_pnl_pc: 0,
+                coin_vault_mint: Pubkey::new_unique(),
+                pc_vault_mint: Pubkey::new_unique(),
+                ..Default::default()
+            },
+            market: MarketState::default(),
+            coin_vault_amount: 1_000_001_000,
+            pc_vault_amount: 50_000_000_000,
+        }
+    }
+
+    #[test]
+    fn test_amm_v4_authority() {
+        let program_id = Pubkey::from_str(RAYDIUM_AMM_V4_PROGRAM_ID).unwrap();
+        assert_eq!(
+            get_amm_authority_address(&program_id).to_string(),
+            "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1"
+        );
+    }
+
+    #[test]
+    fn test_amm_v4_quote() {
+        let pool = test_pool();
+        let coin_mint = pool.amm_info.coin_vault_mint;
+        assert_eq!(
+            pool.trading_reserves().unwrap(),
+            (1_000_000_000, 50_000_000_000)
+        );
+
+        // 0.25% 手续费: 1_000_000 -> 手续费 2500, 997_500 进入曲线
+        let quote = pool.swap_base_input(&coin_mint, 1_000_000).unwrap();
+        assert_eq!(quote.trade_fee, 2_500);
+        assert_eq!(
+            quote.amount_out,
+            (50_000_000_000u128 * 997_500 / (1_000_000_000 + 997_500)) as u64
+        );
+
+        // 反向报价需要的输入不少于正向报价的输入
+        let quote_out = pool.swap_base_output(&coin_mint, quote.amount_out).unwrap();
+        assert!(quote_out.amount_in >= 1_000_000 - 1);
+        assert!(quote_out.amount_in <= 1_000_000 + 1);
+        assert!(pool.swap_base_input(&Pubkey::new_unique(), 1).is_err());
+
+        // 手续费率无效 / 池子禁用 swap 时报错
+        let mut invalid_fee = pool.clone();
+        invalid_fee.amm_info.swap_fee_numerator = invalid_fee.amm_info.swap_fee_denominator;
+        assert!(invalid_fee.swap_base_output(&coin_mint, 1_000).is_err());
+        invalid_fee.amm_info.swap_fee_numerator = invalid_fee.amm_info.swap_fee_denominator + 1;
+        assert!(invalid_fee.swap_base_output(&coin_mint, 1_000).is_err());
+        let mut disabled = pool.clone();
+        // Disabled
+        disabled.amm_info.status = 2;
+        assert!(disabled.swap_base_input(&coin_mint, 1_000).is_err());
+        assert!(disabled.swap_base_output(&coin_mint, 1_000).is_err());
+    }
+}
@@ -0,0 +1,820 @@
+use anyhow::{anyhow, Result};
+use mpl_token_metadata::accounts::Metadata;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{system_program, sysvar};
+use std::str::FromStr;
+
+use super::math::{get_amounts_from_liquidity, get_liquidity_from_amounts, get_sqrt_price_at_tick};
+use super::pool::ClmmPool;
+use super::state::{
+    check_ticks_order, get_array_start_index, get_personal_position_address,
+    get_protocol_position_address, get_tick_array_address, get_tick_array_bitmap_extension_address,
+    is_tick_array_initialized, tick_count, PersonalPositionState, TICK_ARRAY_BITMAP_SIZE,
+};
+use crate::raydium::quote::{get_transfer_fee, get_transfer_inverse_fee};
+use crate::raydium::utils::amount_with_slippage;
+use crate::utils::SPL_TOKEN_PROGRAM_ID;
+
+// anchor 指令 discriminator, sha256("global:<name>")[..8]
+pub const SWAP_V2_DISCRIMINATOR: [u8; 8] = [43, 4, 237, 11, 26, 201, 30, 98];
+pub const OPEN_POSITION_V2_DISCRIMINATOR: [u8; 8] = [77, 184, 74, 214, 112, 86, 241, 199];
+pub const INCREASE_LIQUIDITY_V2_DISCRIMINATOR: [u8; 8] = [133, 29, 89, 223, 69, 238, 176, 10];
+pub const DECREASE_LIQUIDITY_V2_DISCRIMINATOR: [u8; 8] = [58, 127, 188, 62, 79, 82, 196, 96];
+pub const CLOSE_POSITION_DISCRIMINATOR: [u8; 8] = [123, 134, 81, 0, 49, 68, 98, 98];
+
+pub struct SwapV2Args {
+    pub amount: u64,
+    /// 固定输入时为最少输出, 固定输出时为最多输入
+    pub other_amount_threshold: u64,
+    /// 0 表示不限制价格
+    pub sqrt_price_limit_x64: u128,
+    pub is_base_input: bool,
+}
+
+impl SwapV2Args {
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = SWAP_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&self.amount.to_le_bytes());
+        data.extend_from_slice(&self.other_amount_threshold.to_le_bytes());
+        data.extend_from_slice(&self.sqrt_price_limit_x64.to_le_bytes());
+        data.push(u8::from(self.is_base_input));
+        data
+    }
+}
+
+pub struct OpenPositionArgs {
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub liquidity: u128,
+    pub amount_0_max: u64,
+    pub amount_1_max: u64,
+    /// 是否为仓位 nft 创建 metaplex metadata
+    pub with_metadata: bool,
+}
+
+impl OpenPositionArgs {
+    pub fn data(&self, tick_spacing: u16) -> Vec<u8> {
+        let mut data = OPEN_POSITION_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&self.tick_lower_index.to_le_bytes());
+        data.extend_from_slice(&self.tick_upper_index.to_le_bytes());
+        data.extend_from_slice(
+            &get_array_start_index(self.tick_lower_index, tick_spacing).to_le_bytes(),
+        );
+        data.extend_from_slice(
+            &get_array_start_index(self.tick_upper_index, tick_spacing).to_le_bytes(),
+        );
+        data.extend_from_slice(&self.liquidity.to_le_bytes());
+        data.extend_from_slice(&self.amount_0_max.to_le_bytes());
+        data.extend_from_slice(&self.amount_1_max.to_le_bytes());
+        data.push(u8::from(self.with_metadata));
+        // base_flag: Option<bool> = None, 按 liquidity 计算
+        data.push(0);
+        data
+    }
+}
+
+pub struct IncreaseLiquidityArgs {
+    pub liquidity: u128,
+    pub amount_0_max: u64,
+    pub amount_1_max: u64,
+}
+
+impl IncreaseLiquidityArgs {
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = INCREASE_LIQUIDITY_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&self.liquidity.to_le_bytes());
+        data.extend_from_slice(&self.amount_0_max.to_le_bytes());
+        data.extend_from_slice(&self.amount_1_max.to_le_bytes());
+        // base_flag: Option<bool> = None
+        data.push(0);
+        data
+    }
+}
+
+pub struct DecreaseLiquidityArgs {
+    pub liquidity: u128,
+    pub amount_0_min: u64,
+    pub amount_1_min: u64,
+}
+
+impl DecreaseLiquidityArgs {
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = DECREASE_LIQUIDITY_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&self.liquidity.to_le_bytes());
+        data.extend_from_slice(&self.amount_0_min.to_le_bytes());
+        data.extend_from_slice(&self.amount_1_min.to_le_bytes());
+        data
+    }
+}
+
+fn get_user_ata(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address_with_program_id(
+        owner,
+        mint,
+        token_program,
+    )
+}
+
+fn create_user_ata_ix(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Instruction {
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+        owner,
+        owner,
+        mint,
+        token_program,
+    )
+}
+
+// 仓位 nft 使用 legacy token program
+fn get_position_nft_account(owner: &Pubkey, position_nft_mint: &Pubkey) -> Result<Pubkey> {
+    Ok(get_user_ata(
+        owner,
+        position_nft_mint,
+        &Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+    ))
+}
+
+// 仓位的 tick array 超出 pool 自带 bitmap 范围时, 需要额外传入 bitmap extension 账户
+fn bitmap_extension_accounts(
+    pool: &ClmmPool,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+) -> Vec<AccountMeta> {
+    let tick_spacing = pool.pool_state.tick_spacing;
+    let boundary = TICK_ARRAY_BITMAP_SIZE * tick_count(tick_spacing);
+    let overflow = [tick_lower_index, tick_upper_index].iter().any(|tick| {
+        let start_index = get_array_start_index(*tick, tick_spacing);
+        start_index < -boundary || start_index >= boundary
+    });
+    if overflow {
+        vec![AccountMeta::new(
+            get_tick_array_bitmap_extension_address(&pool.program_id, &pool.pool_id),
+            false,
+        )]
+    } else {
+        vec![]
+    }
+}
+
+// 仓位两端 tick 所在的 tick array
+fn position_tick_arrays(
+    pool: &ClmmPool,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+) -> (Pubkey, Pubkey) {
+    let tick_spacing = pool.pool_state.tick_spacing;
+    (
+        get_tick_array_address(
+            &pool.program_id,
+            &pool.pool_id,
+            get_array_start_index(tick_lower_index, tick_spacing),
+        ),
+        get_tick_array_address(
+            &pool.program_id,
+            &pool.pool_id,
+            get_array_start_index(tick_upper_index, tick_spacing),
+        ),
+    )
+}
+
+// tick_array_start_indices 为 swap 会经过的 tick array (见 ClmmPool::quote_swap)
+pub fn swap_v2_instr(
+    pool: &ClmmPool,
+    payer: Pubkey,
+    input_token_account: Pubkey,
+    output_token_account: Pubkey,
+    input_token_mint: &Pubkey,
+    args: &SwapV2Args,
+    tick_array_start_indices: &[i32],
+) -> Result<Instruction> {
+    let pool_state = &pool.pool_state;
+    let (input_vault, output_vault, output_token_mint) =
+        if *input_token_mint == pool_state.token_mint_0 {
+            (
+                pool_state.token_vault_0,
+                pool_state.token_vault_1,
+                pool_state.token_mint_1,
+            )
+        } else if *input_token_mint == pool_state.token_mint_1 {
+            (
+                pool_state.token_vault_1,
+                pool_state.token_vault_0,
+                pool_state.token_mint_0,
+            )
+        } else {
+            return Err(anyhow!("{} is not a mint of this pool", input_token_mint));
+        };
+    let mut accounts = vec![
+        AccountMeta::new_readonly(payer, true),
+        AccountMeta::new_readonly(pool_state.amm_config, false),
+        AccountMeta::new(pool.pool_id, false),
+        AccountMeta::new(input_token_account, false),
+        AccountMeta::new(output_token_account, false),
+        AccountMeta::new(input_vault, false),
+        AccountMeta::new(output_vault, false),
+        AccountMeta::new(pool_state.observation_key, false),
+        AccountMeta::new_readonly(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?, false),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(spl_memo::id(), false),
+        AccountMeta::new_readonly(*input_token_mint, false),
+        AccountMeta::new_readonly(output_token_mint, false),
+    ];
+    for start_index in tick_array_start_indices {
+        if !is_tick_array_initialized(
+            &pool_state.tick_array_bitmap,
+            *start_index,
+            pool_state.tick_spacing,
+        ) {
+            return Err(anyhow!("tick array {} is not initialized", start_index));
+        }
+        accounts.push(AccountMeta::new(
+            get_tick_array_address(&pool.program_id, &pool.pool_id, *start_index),
+            false,
+        ));
+    }
+    Ok(Instruction {
+        program_id: pool.program_id,
+        accounts,
+        data: args.data(),
+    })
+}
+
+// position_nft_mint 为新生成的 keypair, 需要和 payer 一起签名
+pub fn open_position_v2_instr(
+    pool: &ClmmPool,
+    payer: Pubkey,
+    position_nft_mint: Pubkey,
+    args: &OpenPositionArgs,
+) -> Result<Instruction> {
+    let pool_state = &pool.pool_state;
+    check_ticks_order(
+        args.tick_lower_index,
+        args.tick_upper_index,
+        pool_state.tick_spacing,
+    )?;
+    let (tick_array_lower, tick_array_upper) =
+        position_tick_arrays(pool, args.tick_lower_index, args.tick_upper_index);
+    let mut accounts = vec![
+        AccountMeta::new(payer, true),
+        AccountMeta::new_readonly(payer, false),
+        AccountMeta::new(position_nft_mint, true),
+        AccountMeta::new(get_position_nft_account(&payer, &position_nft_mint)?, false),
+        AccountMeta::new(Metadata::find_pda(&position_nft_mint).0, false),
+        AccountMeta::new(pool.pool_id, false),
+        AccountMeta::new(
+            get_protocol_position_address(
+                &pool.program_id,
+                &pool.pool_id,
+                args.tick_lower_index,
+                args.tick_upper_index,
+            ),
+            false,
+        ),
+        AccountMeta::new(tick_array_lower, false),
+        AccountMeta::new(tick_array_upper, false),
+        AccountMeta::new(
+            get_personal_position_address(&pool.program_id, &position_nft_mint),
+            false,
+        ),
+        AccountMeta::new(
+            get_user_ata(&payer, &pool_state.token_mint_0, &pool.mint_0_token_program),
+            false,
+        ),
+        AccountMeta::new(
+            get_user_ata(&payer, &pool_state.token_mint_1, &pool.mint_1_token_program),
+            false,
+        ),
+        AccountMeta::new(pool_state.token_vault_0, false),
+        AccountMeta::new(pool_state.token_vault_1, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?, false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(mpl_token_metadata::ID, false),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(pool_state.token_mint_0, false),
+        AccountMeta::new_readonly(pool_state.token_mint_1, false),
+    ];
+    accounts.extend(bitmap_extension_accounts(
+        pool,
+        args.tick_lower_index,
+        args.tick_upper_index,
+    ));
+    Ok(Instruction {
+        program_id: pool.program_id,
+        accounts,
+        data: args.data(pool_state.tick_spacing),
+    })
+}
+
+pub fn increase_liquidity_v2_instr(
+    pool: &ClmmPool,
+    nft_owner: Pubkey,
+    position: &PersonalPositionState,
+    args: &IncreaseLiquidityArgs,
+) -> Result<Instruction> {
+    let pool_state = &pool.pool_state;
+    let (tick_array_lower, tick_array_upper) =
+        position_tick_arrays(pool, position.tick_lower_index, position.tick_upper_index);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(nft_owner, true),
+        AccountMeta::new_readonly(
+            get_position_nft_account(&nft_owner, &position.nft_mint)?,
+            false,
+        ),
+        AccountMeta::new(pool.pool_id, false),
+        AccountMeta::new(
+            get_protocol_position_address(
+                &pool.program_id,
+                &pool.pool_id,
+                position.tick_lower_index,
+                position.tick_upper_index,
+            ),
+            false,
+        ),
+        AccountMeta::new(
+            get_personal_position_address(&pool.program_id, &position.nft_mint),
+            false,
+        ),
+        AccountMeta::new(tick_array_lower, false),
+        AccountMeta::new(tick_array_upper, false),
+        AccountMeta::new(
+            get_user_ata(
+                &nft_owner,
+                &pool_state.token_mint_0,
+                &pool.mint_0_token_program,
+            ),
+            false,
+        ),
+        AccountMeta::new(
+            get_user_ata(
+                &nft_owner,
+                &pool_state.token_mint_1,
+                &pool.mint_1_token_program,
+            ),
+            false,
+        ),
+        AccountMeta::new(pool_state.token_vault_0, false),
+        AccountMeta::new(pool_state.token_vault_1, false),
+        AccountMeta::new_readonly(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?, false),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(pool_state.token_mint_0, false),
+        AccountMeta::new_readonly(pool_state.token_mint_1, false),
+    ];
+    accounts.extend(bitmap_extension_accounts(
+        pool,
+        position.tick_lower_index,
+        position.tick_upper_index,
+    ));
+    Ok(Instruction {
+        program_id: pool.program_id,
+        accounts,
+        data: args.data(),
+    })
+}
+
+// 减少流动性时会同时领取手续费和奖励, 每个已初始化的奖励需要 (vault, 接收账户, mint) 三个账户
+pub fn decrease_liquidity_v2_instr(
+    pool: &ClmmPool,
+    nft_owner: Pubkey,
+    position: &PersonalPositionState,
+    args: &DecreaseLiquidityArgs,
+) -> Result<Instruction> {
+    let pool_state = &pool.pool_state;
+    let (tick_array_lower, tick_array_upper) =
+        position_tick_arrays(pool, position.tick_lower_index, position.tick_upper_index);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(nft_owner, true),
+        AccountMeta::new_readonly(
+            get_position_nft_account(&nft_owner, &position.nft_mint)?,
+            false,
+        ),
+        AccountMeta::new(
+            get_personal_position_address(&pool.program_id, &position.nft_mint),
+            false,
+        ),
+        AccountMeta::new(pool.pool_id, false),
+        AccountMeta::new(
+            get_protocol_position_address(
+                &pool.program_id,
+                &pool.pool_id,
+                position.tick_lower_index,
+                position.tick_upper_index,
+            ),
+            false,
+        ),
+        AccountMeta::new(pool_state.token_vault_0, false),
+        AccountMeta::new(pool_state.token_vault_1, false),
+        AccountMeta::new(tick_array_lower, false),
+        AccountMeta::new(tick_array_upper, false),
+        AccountMeta::new(
+            get_user_ata(
+                &nft_owner,
+                &pool_state.token_mint_0,
+                &pool.mint_0_token_program,
+            ),
+            false,
+        ),
+        AccountMeta::new(
+            get_user_ata(
+                &nft_owner,
+                &pool_state.token_mint_1,
+                &pool.mint_1_token_program,
+            ),
+            false,
+        ),
+        AccountMeta::new_readonly(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?, false),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(spl_memo::id(), false),
+        AccountMeta::new_readonly(pool_state.token_mint_0, false),
+        AccountMeta::new_readonly(pool_state.token_mint_1, false),
+    ];
+    let rewards = pool_state
+        .reward_infos
+        .iter()
+        .filter(|reward_info| reward_info.is_initialized());
+    for (reward_info, token_program) in rewards.zip(&pool.reward_token_programs) {
+        accounts.push(AccountMeta::new(reward_info.token_vault, false));
+        accounts.push(AccountMeta::new(
+            get_user_ata(&nft_owner, &reward_info.token_mint, token_program),
+            false,
+        ));
+        accounts.push(AccountMeta::new_readonly(reward_info.token_mint, false));
+    }
+    accounts.extend(bitmap_extension_accounts(
+        pool,
+        position.tick_lower_index,
+        position.tick_upper_index,
+    ));
+    Ok(Instruction {
+        program_id: pool.program_id,
+        accounts,
+        data: args.data(),
+    })
+}
+
+// 仓位流动性, 手续费和奖励都领取完之后才能关闭, 关闭时销毁仓位 nft
+pub fn close_position_instr(
+    program_id: &Pubkey,
+    nft_owner: Pubkey,
+    position_nft_mint: Pubkey,
+) -> Result<Instruction> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(nft_owner, true),
+            AccountMeta::new(position_nft_mint, false),
+            AccountMeta::new(
+                get_position_nft_account(&nft_owner, &position_nft_mint)?,
+                false,
+            ),
+            AccountMeta::new(
+                get_personal_position_address(program_id, &position_nft_mint),
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?, false),
+        ],
+        data: CLOSE_POSITION_DISCRIMINATOR.to_vec(),
+    })
+}
+
+fn output_mint_and_program(pool: &ClmmPool, input_token_mint: &Pubkey) -> (Pubkey, Pubkey, Pubkey) {
+    let pool_state = &pool.pool_state;
+    if *input_token_mint == pool_state.token_mint_0 {
+        (
+            pool.mint_0_token_program,
+            pool_state.token_mint_1,
+            pool.mint_1_token_program,
+        )
+    } else {
+        (
+            pool.mint_1_token_program,
+            pool_state.token_mint_0,
+            pool.mint_0_token_program,
+        )
+    }
+}
+
+// 固定输入兑换, slippage 为小数 (0.01 即 1%), 输出代币的 ata 不存在时会创建
+pub fn prepare_swap_base_input_instructions(
+    pool: &ClmmPool,
+    payer: Pubkey,
+    input_token_mint: Pubkey,
+    amount_in: u64,
+    slippage: f64,
+) -> Result<Vec<Instruction>> {
+    let (quote, tick_array_start_indices) = pool.quote_swap(&input_token_mint, amount_in, true)?;
+    let (input_token_program, output_token_mint, output_token_program) =
+        output_mint_and_program(pool, &input_token_mint);
+    Ok(vec![
+        create_user_ata_ix(&payer, &output_token_mint, &output_token_program),
+        swap_v2_instr(
+            pool,
+            payer,
+            get_user_ata(&payer, &input_token_mint, &input_token_program),
+            get_user_ata(&payer, &output_token_mint, &output_token_program),
+            &input_token_mint,
+            &SwapV2Args {
+                amount: amount_in,
+                other_amount_threshold: amount_with_slippage(
+                    quote.amount_received,
+                    slippage,
+                    false,
+                ),
+                sqrt_price_limit_x64: 0,
+                is_base_input: true,
+            },
+            &tick_array_start_indices,
+        )?,
+    ])
+}
+
+// 固定输出兑换, amount_out 为用户到账数量, 输出代币的 ata 不存在时会创建
+pub fn prepare_swap_base_output_instructions(
+    pool: &ClmmPool,
+    payer: Pubkey,
+    input_token_mint: Pubkey,
+    amount_out: u64,
+    slippage: f64,
+) -> Result<Vec<Instruction>> {
+    let (quote, tick_array_start_indices) =
+        pool.quote_swap(&input_token_mint, amount_out, false)?;
+    let (input_token_program, output_token_mint, output_token_program) =
+        output_mint_and_program(pool, &input_token_mint);
+    Ok(vec![
+        create_user_ata_ix(&payer, &output_token_mint, &output_token_program),
+        swap_v2_instr(
+            pool,
+            payer,
+            get_user_ata(&payer, &input_token_mint, &input_token_program),
+            get_user_ata(&payer, &output_token_mint, &output_token_program),
+            &input_token_mint,
+            &SwapV2Args {
+                amount: amount_out,
+                other_amount_threshold: amount_with_slippage(quote.amount_in, slippage, true),
+                sqrt_price_limit_x64: 0,
+                is_base_input: false,
+            },
+            &tick_array_start_indices,
+        )?,
+    ])
+}
+
+// 按用户愿意投入的数量计算流动性, 返回 (流动性, 含转账手续费和滑点的 amount_0_max, amount_1_max)
+fn liquidity_for_amounts(
+    pool: &ClmmPool,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    amount_0: u64,
+    amount_1: u64,
+    slippage: f64,
+) -> Result<(u128, u64, u64)> {
+    let sqrt_price_x64 = pool.pool_state.sqrt_price_x64;
+    let sqrt_price_lower_x64 = get_sqrt_price_at_tick(tick_lower_index)?;
+    let sqrt_price_upper_x64 = get_sqrt_price_at_tick(tick_upper_index)?;
+    // 转账手续费从用户转出的数量中扣除, 先扣掉再算流动性
+    let amount_0 =
+        amount_0 - get_transfer_fee(pool.mint_0_transfer_fee.as_ref(), pool.epoch, amount_0)?;
+    let amount_1 =
+        amount_1 - get_transfer_fee(pool.mint_1_transfer_fee.as_ref(), pool.epoch, amount_1)?;
+    let liquidity = get_liquidity_from_amounts(
+        sqrt_price_x64,
+        sqrt_price_lower_x64,
+        sqrt_price_upper_x64,
+        amount_0,
+        amount_1,
+    )?;
+    let (amount_0, amount_1) = get_amounts_from_liquidity(
+        sqrt_price_x64,
+        sqrt_price_lower_x64,
+        sqrt_price_upper_x64,
+        liquidity,
+        true,
+    )?;
+    let amount_0_max = amount_0
+        + get_transfer_inverse_fee(pool.mint_0_transfer_fee.as_ref(), pool.epoch, amount_0)?;
+    let amount_1_max = amount_1
+        + get_transfer_inverse_fee(pool.mint_1_transfer_fee.as_ref(), pool.epoch, amount_1)?;
+    Ok((
+        liquidity,
+        amount_with_slippage(amount_0_max, slippage, true),
+        amount_with_slippage(amount_1_max, slippage, true),
+    ))
+}
+
+// 在 [tick_lower_index, tick_upper_index) 区间开仓, amount_0 / amount_1 为愿意投入的数量
+// position_nft_mint 为新生成的 keypair, 需要和 payer 一起签名
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_open_position_instructions(
+    pool: &ClmmPool,
+    payer: Pubkey,
+    position_nft_mint: Pubkey,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    amount_0: u64,
+    amount_1: u64,
+    slippage: f64,
+) -> Result<Vec<Instruction>> {
+    check_ticks_order(
+        tick_lower_index,
+        tick_upper_index,
+        pool.pool_state.tick_spacing,
+    )?;
+    let (liquidity, amount_0_max, amount_1_max) = liquidity_for_amounts(
+        pool,
+        tick_lower_index,
+        tick_upper_index,
+        amount_0,
+        amount_1,
+        slippage,
+    )?;
+    if liquidity == 0 {
+        return Err(anyhow!("amounts too small to provide liquidity"));
+    }
+    Ok(vec![open_position_v2_instr(
+        pool,
+        payer,
+        position_nft_mint,
+        &OpenPositionArgs {
+            tick_lower_index,
+            tick_upper_index,
+            liquidity,
+            amount_0_max,
+            amount_1_max,
+            with_metadata: true,
+        },
+    )?])
+}
+
+pub fn prepare_increase_liquidity_instructions(
+    pool: &ClmmPool,
+    payer: Pubkey,
+    position: &PersonalPositionState,
+    amount_0: u64,
+    amount_1: u64,
+    slippage: f64,
+) -> Result<Vec<Instruction>> {
+    let (liquidity, amount_0_max, amount_1_max) = liquidity_for_amounts(
+        pool,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        amount_0,
+        amount_1,
+        slippage,
+    )?;
+    if liquidity == 0 {
+        return Err(anyhow!("amounts too small to provide liquidity"));
+    }
+    Ok(vec![increase_liquidity_v2_instr(
+        pool,
+        payer,
+        position,
+        &IncreaseLiquidityArgs {
+            liquidity,
+            amount_0_max,
+            amount_1_max,
+        },
+    )?])
+}
+
+// 减少 liquidity 数量的流动性, liquidity 为 0 时只领取手续费和奖励
+// 两种代币以及奖励代币的 ata 不存在时会创建
+pub fn prepare_decrease_liquidity_instructions(
+    pool: &ClmmPool,
+    payer: Pubkey,
+    position: &PersonalPositionState,
+    liquidity: u128,
+    slippage: f64,
+) -> Result<Vec<Instruction>> {
+    if liquidity > position.liquidity {
+        return Err(anyhow!("liquidity exceeds position liquidity"));
+    }
+    let pool_state = &pool.pool_state;
+    let (amount_0, amount_1) = get_amounts_from_liquidity(
+        pool_state.sqrt_price_x64,
+        get_sqrt_price_at_tick(position.tick_lower_index)?,
+        get_sqrt_price_at_tick(position.tick_upper_index)?,
+        liquidity,
+        false,
+    )?;
+    let amount_0 =
+        amount_0 - get_transfer_fee(pool.mint_0_transfer_fee.as_ref(), pool.epoch, amount_0)?;
+    let amount_1 =
+        amount_1 - get_transfer_fee(pool.mint_1_transfer_fee.as_ref(), pool.epoch, amount_1)?;
+
+    let mut instructions = vec![
+        create_user_ata_ix(&payer, &pool_state.token_mint_0, &pool.mint_0_token_program),
+        create_user_ata_ix(&payer, &pool_state.token_mint_1, &pool.mint_1_token_program),
+    ];
+    let rewards = pool_state
+        .reward_infos
+        .iter()
+        .filter(|reward_info| reward_info.is_initialized());
+    for (reward_info, token_program) in rewards.zip(&pool.reward_token_programs) {
+        instructions.push(create_user_ata_ix(
+            &payer,
+            &reward_info.token_mint,
+            token_program,
+        ));
+    }
+    instructions.push(decrease_liquidity_v2_instr(
+        pool,
+        payer,
+        position,
+        &DecreaseLiquidityArgs {
+            liquidity,
+            amount_0_min: amount_with_slippage(amount_0, slippage, false),
+            amount_1_min: amount_with_slippage(amount_1, slippage, false),
+        },
+    )?);
+    Ok(instructions)
+}
+
+// 取出全部流动性 (同时领取手续费和奖励) 后关闭仓位
+pub fn prepare_close_position_instructions(
+    pool: &ClmmPool,
+    payer: Pubkey,
+    position: &PersonalPositionState,
+    slippage: f64,
+) -> Result<Vec<Instruction>> {
+    let mut instructions = prepare_decrease_liquidity_instructions(
+        pool,
+        payer,
+        position,
+        position.liquidity,
+        slippage,
+    )?;
+    instructions.push(close_position_instr(
+        &pool.program_id,
+        payer,
+        position.nft_mint,
+    )?);
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raydium::clmm::pool::tests::test_pool;
+
+    #[test]
+    fn test_clmm_swap_instructions() {
+        let pool = test_pool(1_000_000_000_000);
+        let payer = Pubkey::new_unique();
+        let mint_0 = pool.pool_state.token_mint_0;
+        let instructions =
+            prepare_swap_base_input_instructions(&pool, payer, mint_0, 1_000_000, 0.01).unwrap();
+        assert_eq!(instructions.len(), 2);
+        let swap_ix = &instructions[1];
+        assert_eq!(swap_ix.data[..8], SWAP_V2_DISCRIMINATOR);
+        // 13 个固定账户 + 1 个 tick array
+        assert_eq!(swap_ix.accounts.len(), 14);
+        assert_eq!(
+            swap_ix.accounts[13].pubkey,
+            get_tick_array_address(&pool.program_id, &pool.pool_id, -600)
+        );
+        assert_eq!(swap_ix.data.len(), 8 + 8 + 8 + 16 + 1);
+    }
+
+    #[test]
+    fn test_clmm_position_instructions() {
+        let pool = test_pool(1_000_000_000_000);
+        let payer = Pubkey::new_unique();
+        let nft_mint = Pubkey::new_unique();
+        assert!(prepare_open_position_instructions(
+            &pool, payer, nft_mint, -605, 600, 1_000_000, 1_000_000, 0.01
+        )
+        .is_err());
+
+        let instructions = prepare_open_position_instructions(
+            &pool, payer, nft_mint, -600, 600, 1_000_000, 1_000_000, 0.01,
+        )
+        .unwrap();
+        let open_ix = &instructions[0];
+        assert_eq!(open_ix.data[..8], OPEN_POSITION_V2_DISCRIMINATOR);
+        assert_eq!(open_ix.accounts.len(), 22);
+        assert_eq!(
+            open_ix.accounts[9].pubkey,
+            get_personal_position_address(&pool.program_id, &nft_mint)
+        );
+
+        let position = PersonalPositionState {
+            nft_mint,
+            pool_id: pool.pool_id,
+            tick_lower_index: -600,
+            tick_upper_index: 600,
+            liquidity: 1_000_000,
+            ..Default::default()
+        };
+        let instructions =
+            prepare_close_position_instructions(&pool, payer, &position, 0.01).unwrap();
+        // 两个 ata + 减少流动性 + 关闭仓位
+        assert_eq!(instructions.len(), 4);
+        assert_eq!(
+            instructions[2].data[..8],
+            DECREASE_LIQUIDITY_V2_DISCRIMINATOR
+        );
+        assert_eq!(instructions[3].data, CLOSE_POSITION_DISCRIMINATOR);
+    }
+}
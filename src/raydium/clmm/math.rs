@@ -0,0 +1,654 @@
+use anyhow::{anyhow, Result};
+
+// 与链上 libraries 一致的定点数运算, 价格均为 sqrt(price) 的 Q64.64 格式
+#[allow(clippy::assign_op_pattern)]
+#[allow(clippy::ptr_offset_with_cast)]
+#[allow(clippy::manual_range_contains)]
+mod big_num {
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
+}
+
+pub use big_num::U256;
+
+pub const Q64: u128 = 1 << 64;
+pub const RESOLUTION: u8 = 64;
+
+pub const MIN_TICK: i32 = -443636;
+pub const MAX_TICK: i32 = -MIN_TICK;
+pub const MIN_SQRT_PRICE_X64: u128 = 4295048016;
+pub const MAX_SQRT_PRICE_X64: u128 = 79226673521066979257578248091;
+
+// 费率分母, trade_fee_rate 等均为百万分之一
+pub const FEE_RATE_DENOMINATOR_VALUE: u32 = 1_000_000;
+
+const BIT_PRECISION: u32 = 16;
+
+// (a * b) / denominator, 结果超过 u128 时报错
+fn mul_div(a: U256, b: U256, denominator: U256, round_up: bool) -> Result<U256> {
+    if denominator.is_zero() {
+        return Err(anyhow!("mul_div denominator is zero"));
+    }
+    let product = a
+        .checked_mul(b)
+        .ok_or_else(|| anyhow!("mul_div overflow"))?;
+    let mut result = product / denominator;
+    if round_up && !(product % denominator).is_zero() {
+        result += U256::one();
+    }
+    if result > U256::from(u128::MAX) {
+        return Err(anyhow!("mul_div result exceeds u128"));
+    }
+    Ok(result)
+}
+
+fn div_rounding_up(x: U256, y: U256) -> U256 {
+    let quotient = x / y;
+    if (x % y).is_zero() {
+        quotient
+    } else {
+        quotient + U256::one()
+    }
+}
+
+fn to_u64(value: U256) -> Result<u64> {
+    if value > U256::from(u64::MAX) {
+        return Err(anyhow!("value exceeds u64"));
+    }
+    Ok(value.as_u64())
+}
+
+fn to_u128(value: U256) -> Result<u128> {
+    if value > U256::from(u128::MAX) {
+        return Err(anyhow!("value exceeds u128"));
+    }
+    Ok(value.as_u128())
+}
+
+// sqrt(1.0001^tick) * 2^64
+pub fn get_sqrt_price_at_tick(tick: i32) -> Result<u128> {
+    let abs_tick = tick.unsigned_abs();
+    if abs_tick > MAX_TICK as u32 {
+        return Err(anyhow!("tick {} out of range", tick));
+    }
+    // 每一位对应 1 / sqrt(1.0001)^(2^i), Q64 格式
+    const FACTORS: [(u32, u128); 18] = [
+        (0x2, 0xfff97272373d4000),
+        (0x4, 0xfff2e50f5f657000),
+        (0x8, 0xffe5caca7e10f000),
+        (0x10, 0xffcb9843d60f7000),
+        (0x20, 0xff973b41fa98e800),
+        (0x40, 0xff2ea16466c9b000),
+        (0x80, 0xfe5dee046a9a3800),
+        (0x100, 0xfcbe86c7900bb000),
+        (0x200, 0xf987a7253ac65800),
+        (0x400, 0xf3392b0822bb6000),
+        (0x800, 0xe7159475a2caf000),
+        (0x1000, 0xd097f3bdfd2f2000),
+        (0x2000, 0xa9f746462d9f8000),
+        (0x4000, 0x70d869a156f31c00),
+        (0x8000, 0x31be135f97ed3200),
+        (0x10000, 0x9aa508b5b85a500),
+        (0x20000, 0x5d6af8dedc582c),
+        (0x40000, 0x2216e584f5fa),
+    ];
+    let mut ratio: u128 = if abs_tick & 0x1 != 0 {
+        0xfffcb933bd6fb800
+    } else {
+        Q64
+    };
+    for (mask, factor) in FACTORS {
+        if abs_tick & mask != 0 {
+            ratio = (ratio * factor) >> RESOLUTION;
+        }
+    }
+    if tick > 0 {
+        ratio = u128::MAX / ratio;
+    }
+    Ok(ratio)
+}
+
+// 返回满足 get_sqrt_price_at_tick(tick) <= sqrt_price_x64 的最大 tick
+pub fn get_tick_at_sqrt_price(sqrt_price_x64: u128) -> Result<i32> {
+    if !(MIN_SQRT_PRICE_X64..MAX_SQRT_PRICE_X64).contains(&sqrt_price_x64) {
+        return Err(anyhow!("sqrt price {} out of range", sqrt_price_x64));
+    }
+    let msb: u32 = 128 - sqrt_price_x64.leading_zeros() - 1;
+    let log2p_integer_x32 = (msb as i128 - 64) << 32;
+
+    let mut bit: i128 = 0x8000_0000_0000_0000i128;
+    let mut precision = 0;
+    let mut log2p_fraction_x64 = 0;
+    let mut r = if msb >= 64 {
+        sqrt_price_x64 >> (msb - 63)
+    } else {
+        sqrt_price_x64 << (63 - msb)
+    };
+    while bit > 0 && precision < BIT_PRECISION {
+        r *= r;
+        let is_r_more_than_two = r >> 127_u32;
+        r >>= 63 + is_r_more_than_two;
+        log2p_fraction_x64 += bit * is_r_more_than_two as i128;
+        bit >>= 1;
+        precision += 1;
+    }
+    let log2p_fraction_x32 = log2p_fraction_x64 >> 32;
+    let log2p_x32 = log2p_integer_x32 + log2p_fraction_x32;
+
+    // log_sqrt(1.0001)(p) = log2(p) / log2(sqrt(1.0001))
+    let log_sqrt_10001_x64 = log2p_x32 * 59543866431248i128;
+    let tick_low = ((log_sqrt_10001_x64 - 184467440737095516i128) >> 64) as i32;
+    let tick_high = ((log_sqrt_10001_x64 + 15793534762490258745i128) >> 64) as i32;
+    Ok(if tick_low == tick_high {
+        tick_low
+    } else if get_sqrt_price_at_tick(tick_high)? <= sqrt_price_x64 {
+        tick_high
+    } else {
+        tick_low
+    })
+}
+
+// Δx = L * (√P_upper - √P_lower) / (√P_upper * √P_lower)
+pub fn get_delta_amount_0_unsigned(
+    mut sqrt_ratio_a_x64: u128,
+    mut sqrt_ratio_b_x64: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u64> {
+    if sqrt_ratio_a_x64 > sqrt_ratio_b_x64 {
+        std::mem::swap(&mut sqrt_ratio_a_x64, &mut sqrt_ratio_b_x64);
+    }
+    if sqrt_ratio_a_x64 == 0 {
+        return Err(anyhow!("sqrt price is zero"));
+    }
+    let numerator_1 = U256::from(liquidity) << RESOLUTION;
+    let numerator_2 = U256::from(sqrt_ratio_b_x64 - sqrt_ratio_a_x64);
+    let result = if round_up {
+        div_rounding_up(
+            mul_div(numerator_1, numerator_2, U256::from(sqrt_ratio_b_x64), true)?,
+            U256::from(sqrt_ratio_a_x64),
+        )
+    } else {
+        mul_div(
+            numerator_1,
+            numerator_2,
+            U256::from(sqrt_ratio_b_x64),
+            false,
+        )? / U256::from(sqrt_ratio_a_x64)
+    };
+    to_u64(result)
+}
+
+// Δy = L * (√P_upper - √P_lower)
+pub fn get_delta_amount_1_unsigned(
+    mut sqrt_ratio_a_x64: u128,
+    mut sqrt_ratio_b_x64: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u64> {
+    if sqrt_ratio_a_x64 > sqrt_ratio_b_x64 {
+        std::mem::swap(&mut sqrt_ratio_a_x64, &mut sqrt_ratio_b_x64);
+    }
+    to_u64(mul_div(
+        U256::from(liquidity),
+        U256::from(sqrt_ratio_b_x64 - sqrt_ratio_a_x64),
+        U256::from(Q64),
+        round_up,
+    )?)
+}
+
+// √P' = √P * L / (L ± Δx * √P), 向上取整
+fn get_next_sqrt_price_from_amount_0_rounding_up(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount: u64,
+    add: bool,
+) -> Result<u128> {
+    if amount == 0 {
+        return Ok(sqrt_price_x64);
+    }
+    let numerator_1 = U256::from(liquidity) << RESOLUTION;
+    let product = U256::from(amount) * U256::from(sqrt_price_x64);
+    if add {
+        let denominator = numerator_1 + product;
+        match mul_div(numerator_1, U256::from(sqrt_price_x64), denominator, true) {
+            Ok(price) => to_u128(price),
+            // 溢出时使用等价形式 √P' = L / (L / √P + Δx)
+            Err(_) => to_u128(div_rounding_up(
+                numerator_1,
+                numerator_1 / U256::from(sqrt_price_x64) + U256::from(amount),
+            )),
+        }
+    } else {
+        let denominator = numerator_1
+            .checked_sub(product)
+            .filter(|d| !d.is_zero())
+            .ok_or_else(|| anyhow!("amount out exceeds liquidity"))?;
+        to_u128(mul_div(
+            numerator_1,
+            U256::from(sqrt_price_x64),
+            denominator,
+            true,
+        )?)
+    }
+}
+
+// √P' = √P ± Δy / L, 向下取整
+fn get_next_sqrt_price_from_amount_1_rounding_down(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount: u64,
+    add: bool,
+) -> Result<u128> {
+    let numerator = U256::from(u128::from(amount) << RESOLUTION);
+    if add {
+        let quotient = to_u128(numerator / U256::from(liquidity))?;
+        sqrt_price_x64
+            .checked_add(quotient)
+            .ok_or_else(|| anyhow!("sqrt price overflow"))
+    } else {
+        let quotient = to_u128(div_rounding_up(numerator, U256::from(liquidity)))?;
+        sqrt_price_x64
+            .checked_sub(quotient)
+            .ok_or_else(|| anyhow!("sqrt price underflow"))
+    }
+}
+
+pub fn get_next_sqrt_price_from_input(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount_in: u64,
+    zero_for_one: bool,
+) -> Result<u128> {
+    if sqrt_price_x64 == 0 || liquidity == 0 {
+        return Err(anyhow!("sqrt price or liquidity is zero"));
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price_x64, liquidity, amount_in, true)
+    } else {
+        get_next_sqrt_price_from_amount_1_rounding_down(sqrt_price_x64, liquidity, amount_in, true)
+    }
+}
+
+pub fn get_next_sqrt_price_from_output(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount_out: u64,
+    zero_for_one: bool,
+) -> Result<u128> {
+    if sqrt_price_x64 == 0 || liquidity == 0 {
+        return Err(anyhow!("sqrt price or liquidity is zero"));
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_1_rounding_down(
+            sqrt_price_x64,
+            liquidity,
+            amount_out,
+            false,
+        )
+    } else {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price_x64, liquidity, amount_out, false)
+    }
+}
+
+// 流动性变化, liquidity_delta 为负时减少
+pub fn add_delta(liquidity: u128, liquidity_delta: i128) -> Result<u128> {
+    if liquidity_delta < 0 {
+        liquidity
+            .checked_sub(liquidity_delta.unsigned_abs())
+            .ok_or_else(|| anyhow!("liquidity underflow"))
+    } else {
+        liquidity
+            .checked_add(liquidity_delta.unsigned_abs())
+            .ok_or_else(|| anyhow!("liquidity overflow"))
+    }
+}
+
+// ΔL = Δx * (√P_upper * √P_lower) / (√P_upper - √P_lower)
+pub fn get_liquidity_from_amount_0(
+    mut sqrt_ratio_a_x64: u128,
+    mut sqrt_ratio_b_x64: u128,
+    amount_0: u64,
+) -> Result<u128> {
+    if sqrt_ratio_a_x64 > sqrt_ratio_b_x64 {
+        std::mem::swap(&mut sqrt_ratio_a_x64, &mut sqrt_ratio_b_x64);
+    }
+    let intermediate = mul_div(
+        U256::from(sqrt_ratio_a_x64),
+        U256::from(sqrt_ratio_b_x64),
+        U256::from(Q64),
+        false,
+    )?;
+    to_u128(mul_div(
+        U256::from(amount_0),
+        intermediate,
+        U256::from(sqrt_ratio_b_x64 - sqrt_ratio_a_x64),
+        false,
+    )?)
+}
+
+// ΔL = Δy / (√P_upper - √P_lower)
+pub fn get_liquidity_from_amount_1(
+    mut sqrt_ratio_a_x64: u128,
+    mut sqrt_ratio_b_x64: u128,
+    amount_1: u64,
+) -> Result<u128> {
+    if sqrt_ratio_a_x64 > sqrt_ratio_b_x64 {
+        std::mem::swap(&mut sqrt_ratio_a_x64, &mut sqrt_ratio_b_x64);
+    }
+    to_u128(mul_div(
+        U256::from(amount_1),
+        U256::from(Q64),
+        U256::from(sqrt_ratio_b_x64 - sqrt_ratio_a_x64),
+        false,
+    )?)
+}
+
+// 给定两种代币的最大数量, 在 [√P_a, √P_b] 区间内最多能提供的流动性
+pub fn get_liquidity_from_amounts(
+    sqrt_price_x64: u128,
+    mut sqrt_ratio_a_x64: u128,
+    mut sqrt_ratio_b_x64: u128,
+    amount_0: u64,
+    amount_1: u64,
+) -> Result<u128> {
+    if sqrt_ratio_a_x64 > sqrt_ratio_b_x64 {
+        std::mem::swap(&mut sqrt_ratio_a_x64, &mut sqrt_ratio_b_x64);
+    }
+    if sqrt_price_x64 <= sqrt_ratio_a_x64 {
+        // 当前价格低于区间, 只需要 token_0
+        get_liquidity_from_amount_0(sqrt_ratio_a_x64, sqrt_ratio_b_x64, amount_0)
+    } else if sqrt_price_x64 < sqrt_ratio_b_x64 {
+        Ok(u128::min(
+            get_liquidity_from_amount_0(sqrt_price_x64, sqrt_ratio_b_x64, amount_0)?,
+            get_liquidity_from_amount_1(sqrt_ratio_a_x64, sqrt_price_x64, amount_1)?,
+        ))
+    } else {
+        // 当前价格高于区间, 只需要 token_1
+        get_liquidity_from_amount_1(sqrt_ratio_a_x64, sqrt_ratio_b_x64, amount_1)
+    }
+}
+
+// 与链上 modify_position 一致: 增加流动性向上取整, 减少流动性向下取整
+pub fn get_amounts_from_liquidity(
+    sqrt_price_x64: u128,
+    mut sqrt_ratio_a_x64: u128,
+    mut sqrt_ratio_b_x64: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<(u64, u64)> {
+    if sqrt_ratio_a_x64 > sqrt_ratio_b_x64 {
+        std::mem::swap(&mut sqrt_ratio_a_x64, &mut sqrt_ratio_b_x64);
+    }
+    if sqrt_price_x64 <= sqrt_ratio_a_x64 {
+        Ok((
+            get_delta_amount_0_unsigned(sqrt_ratio_a_x64, sqrt_ratio_b_x64, liquidity, round_up)?,
+            0,
+        ))
+    } else if sqrt_price_x64 < sqrt_ratio_b_x64 {
+        Ok((
+            get_delta_amount_0_unsigned(sqrt_price_x64, sqrt_ratio_b_x64, liquidity, round_up)?,
+            get_delta_amount_1_unsigned(sqrt_ratio_a_x64, sqrt_price_x64, liquidity, round_up)?,
+        ))
+    } else {
+        Ok((
+            0,
+            get_delta_amount_1_unsigned(sqrt_ratio_a_x64, sqrt_ratio_b_x64, liquidity, round_up)?,
+        ))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwapStep {
+    /// 本步结束后的价格, 不会越过 target
+    pub sqrt_price_next_x64: u128,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+}
+
+// 在当前流动性不变的区间内兑换一步, 与链上 swap_math::compute_swap_step 一致
+pub fn compute_swap_step(
+    sqrt_price_current_x64: u128,
+    sqrt_price_target_x64: u128,
+    liquidity: u128,
+    amount_remaining: u64,
+    fee_rate: u32,
+    is_base_input: bool,
+    zero_for_one: bool,
+) -> Result<SwapStep> {
+    let mut swap_step = SwapStep::default();
+    if is_base_input {
+        let amount_remaining_less_fee = to_u64(mul_div(
+            U256::from(amount_remaining),
+            U256::from(FEE_RATE_DENOMINATOR_VALUE - fee_rate),
+            U256::from(FEE_RATE_DENOMINATOR_VALUE),
+            false,
+        )?)?;
+        swap_step.amount_in = if zero_for_one {
+            get_delta_amount_0_unsigned(
+                sqrt_price_target_x64,
+                sqrt_price_current_x64,
+                liquidity,
+                true,
+            )?
+        } else {
+            get_delta_amount_1_unsigned(
+                sqrt_price_current_x64,
+                sqrt_price_target_x64,
+                liquidity,
+                true,
+            )?
+        };
+        swap_step.sqrt_price_next_x64 = if amount_remaining_less_fee >= swap_step.amount_in {
+            sqrt_price_target_x64
+        } else {
+            get_next_sqrt_price_from_input(
+                sqrt_price_current_x64,
+                liquidity,
+                amount_remaining_less_fee,
+                zero_for_one,
+            )?
+        };
+    } else {
+        swap_step.amount_out = if zero_for_one {
+            get_delta_amount_1_unsigned(
+                sqrt_price_target_x64,
+                sqrt_price_current_x64,
+                liquidity,
+                false,
+            )?
+        } else {
+            get_delta_amount_0_unsigned(
+                sqrt_price_current_x64,
+                sqrt_price_target_x64,
+                liquidity,
+                false,
+            )?
+        };
+        swap_step.sqrt_price_next_x64 = if amount_remaining >= swap_step.amount_out {
+            sqrt_price_target_x64
+        } else {
+            get_next_sqrt_price_from_output(
+                sqrt_price_current_x64,
+                liquidity,
+                amount_remaining,
+                zero_for_one,
+            )?
+        };
+    }
+
+    // 到达 target 时 amount_in (固定输入) 或 amount_out (固定输出) 已经算好, 否则按新价格重算
+    let max = sqrt_price_target_x64 == swap_step.sqrt_price_next_x64;
+    if zero_for_one {
+        if !(max && is_base_input) {
+            swap_step.amount_in = get_delta_amount_0_unsigned(
+                swap_step.sqrt_price_next_x64,
+                sqrt_price_current_x64,
+                liquidity,
+                true,
+            )?;
+        }
+        if !(max && !is_base_input) {
+            swap_step.amount_out = get_delta_amount_1_unsigned(
+                swap_step.sqrt_price_next_x64,
+                sqrt_price_current_x64,
+                liquidity,
+                false,
+            )?;
+        }
+    } else {
+        if !(max && is_base_input) {
+            swap_step.amount_in = get_delta_amount_1_unsigned(
+                sqrt_price_current_x64,
+                swap_step.sqrt_price_next_x64,
+                liquidity,
+                true,
+            )?;
+        }
+        if !(max && !is_base_input) {
+            swap_step.amount_out = get_delta_amount_0_unsigned(
+                sqrt_price_current_x64,
+                swap_step.sqrt_price_next_x64,
+                liquidity,
+                false,
+            )?;
+        }
+    }
+
+    if !is_base_input && swap_step.amount_out > amount_remaining {
+        swap_step.amount_out = amount_remaining;
+    }
+
+    swap_step.fee_amount =
+        if is_base_input && swap_step.sqrt_price_next_x64 != sqrt_price_target_x64 {
+            // 没有到达 target, 剩余的输入全部作为手续费
+            amount_remaining
+                .checked_sub(swap_step.amount_in)
+                .ok_or_else(|| anyhow!("swap step amount in exceeds remaining"))?
+        } else {
+            to_u64(mul_div(
+                U256::from(swap_step.amount_in),
+                U256::from(fee_rate),
+                U256::from(FEE_RATE_DENOMINATOR_VALUE - fee_rate),
+                true,
+            )?)?
+        };
+    Ok(swap_step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_math() {
+        assert_eq!(get_sqrt_price_at_tick(0).unwrap(), Q64);
+        assert_eq!(
+            get_sqrt_price_at_tick(MIN_TICK).unwrap(),
+            MIN_SQRT_PRICE_X64
+        );
+        assert_eq!(
+            get_sqrt_price_at_tick(MAX_TICK).unwrap(),
+            MAX_SQRT_PRICE_X64
+        );
+        assert!(get_sqrt_price_at_tick(MAX_TICK + 1).is_err());
+
+        assert_eq!(
+            get_tick_at_sqrt_price(MIN_SQRT_PRICE_X64).unwrap(),
+            MIN_TICK
+        );
+        assert_eq!(
+            get_tick_at_sqrt_price(MAX_SQRT_PRICE_X64 - 1).unwrap(),
+            MAX_TICK - 1
+        );
+        for tick in [-28861, -1, 1, 28861] {
+            let sqrt_price_x64 = get_sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(get_tick_at_sqrt_price(sqrt_price_x64).unwrap(), tick);
+            assert_eq!(get_tick_at_sqrt_price(sqrt_price_x64 + 1).unwrap(), tick);
+            assert_eq!(
+                get_tick_at_sqrt_price(sqrt_price_x64 - 1).unwrap(),
+                tick - 1
+            );
+        }
+    }
+
+    #[test]
+    fn test_liquidity_amounts_round_trip() {
+        let sqrt_price_x64 = get_sqrt_price_at_tick(0).unwrap();
+        let sqrt_lower = get_sqrt_price_at_tick(-600).unwrap();
+        let sqrt_upper = get_sqrt_price_at_tick(600).unwrap();
+        let liquidity = get_liquidity_from_amounts(
+            sqrt_price_x64,
+            sqrt_lower,
+            sqrt_upper,
+            1_000_000,
+            1_000_000,
+        )
+        .unwrap();
+        let (amount_0, amount_1) =
+            get_amounts_from_liquidity(sqrt_price_x64, sqrt_lower, sqrt_upper, liquidity, true)
+                .unwrap();
+        // 价格在区间中点, 两边需要的数量接近, 且不超过给定的最大值
+        assert!(amount_0 <= 1_000_000 && amount_1 <= 1_000_000);
+        assert!(amount_0 >= 999_000 && amount_1 >= 999_000);
+
+        // 区间在当前价格之上只需要 token_0
+        let (amount_0, amount_1) =
+            get_amounts_from_liquidity(sqrt_lower, sqrt_price_x64, sqrt_upper, liquidity, false)
+                .unwrap();
+        assert!(amount_0 > 0);
+        assert_eq!(amount_1, 0);
+    }
+
+    #[test]
+    fn test_compute_swap_step() {
+        let sqrt_price_current_x64 = get_sqrt_price_at_tick(0).unwrap();
+        let sqrt_price_target_x64 = get_sqrt_price_at_tick(-10).unwrap();
+        let liquidity = 1_000_000_000_000u128;
+
+        // 输入很少, 到不了 target
+        let step = compute_swap_step(
+            sqrt_price_current_x64,
+            sqrt_price_target_x64,
+            liquidity,
+            1_000_000,
+            2500,
+            true,
+            true,
+        )
+        .unwrap();
+        assert!(step.sqrt_price_next_x64 > sqrt_price_target_x64);
+        assert_eq!(step.amount_in + step.fee_amount, 1_000_000);
+        assert!(step.amount_out < step.amount_in);
+
+        // 输入足够多, 停在 target
+        let step = compute_swap_step(
+            sqrt_price_current_x64,
+            sqrt_price_target_x64,
+            liquidity,
+            u64::MAX / 2,
+            2500,
+            true,
+            true,
+        )
+        .unwrap();
+        assert_eq!(step.sqrt_price_next_x64, sqrt_price_target_x64);
+        assert!(step.amount_in + step.fee_amount < u64::MAX / 2);
+
+        // 固定输出不超过要求的数量
+        let step = compute_swap_step(
+            sqrt_price_current_x64,
+            sqrt_price_target_x64,
+            liquidity,
+            500_000,
+            2500,
+            false,
+            true,
+        )
+        .unwrap();
+        assert_eq!(step.amount_out, 500_000);
+        assert!(step.amount_in > 500_000);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod instructions;
+pub mod math;
+pub mod pool;
+pub mod state;
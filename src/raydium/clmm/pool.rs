@@ -0,0 +1,508 @@
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use std::collections::BTreeMap;
+
+use super::math::{
+    add_delta, compute_swap_step, get_sqrt_price_at_tick, get_tick_at_sqrt_price,
+    FEE_RATE_DENOMINATOR_VALUE, MAX_SQRT_PRICE_X64, MAX_TICK, MIN_SQRT_PRICE_X64, MIN_TICK,
+};
+use super::state::{
+    fetch_pool_state, fetch_tick_arrays, next_initialized_tick_array_start_index, AmmConfig,
+    PoolState, TickArrayState,
+};
+use crate::raydium::quote::{
+    get_transfer_fee, get_transfer_fee_config, get_transfer_inverse_fee, SwapQuote, SwapQuoter,
+};
+
+// fetch 时每个方向预先读取的 tick array 数量
+pub const TICK_ARRAY_FETCH_NUM: usize = 5;
+
+// PoolState.status 中禁用 swap 的位
+const POOL_STATUS_SWAP_BIT: u8 = 4;
+
+// 一次 swap 在池子内的结果, 不含 token 2022 转账手续费
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClmmSwapResult {
+    /// 池子收到的输入数量 (含交易手续费)
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub trade_fee: u64,
+    pub protocol_fee: u64,
+    pub fund_fee: u64,
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+    /// 按顺序经过的 tick array, swap 指令需要把它们作为 remaining accounts 传入
+    pub tick_array_start_indices: Vec<i32>,
+}
+
+// clmm 池子快照: 池子状态 + 费率配置 + 当前价格附近的 tick array + mint 信息
+#[derive(Clone, Debug)]
+pub struct ClmmPool {
+    pub program_id: Pubkey,
+    pub pool_id: Pubkey,
+    pub pool_state: PoolState,
+    pub amm_config: AmmConfig,
+    pub tick_arrays: BTreeMap<i32, TickArrayState>,
+    pub mint_0_token_program: Pubkey,
+    pub mint_1_token_program: Pubkey,
+    /// 与 pool_state.reward_infos 中已初始化的奖励一一对应
+    pub reward_token_programs: Vec<Pubkey>,
+    pub mint_0_transfer_fee: Option<TransferFeeConfig>,
+    pub mint_1_transfer_fee: Option<TransferFeeConfig>,
+    pub epoch: u64,
+}
+
+// 从当前价格开始, 按方向依次需要的 tick array
+pub fn swap_tick_array_start_indices(
+    pool_state: &PoolState,
+    zero_for_one: bool,
+    count: usize,
+) -> Vec<i32> {
+    let mut start_indices = Vec::with_capacity(count);
+    let Some((_, mut start_index)) = pool_state.first_initialized_tick_array(zero_for_one) else {
+        return start_indices;
+    };
+    start_indices.push(start_index);
+    while start_indices.len() < count {
+        match next_initialized_tick_array_start_index(
+            &pool_state.tick_array_bitmap,
+            start_index,
+            pool_state.tick_spacing,
+            zero_for_one,
+        ) {
+            Some(next) => {
+                start_indices.push(next);
+                start_index = next;
+            }
+            None => break,
+        }
+    }
+    start_indices
+}
+
+impl ClmmPool {
+    pub fn fetch(client: &RpcClient, program_id: &Pubkey, pool_id: &Pubkey) -> Result<Self> {
+        let pool_state = fetch_pool_state(client, pool_id)?;
+        let mut addresses = vec![
+            pool_state.amm_config,
+            pool_state.token_mint_0,
+            pool_state.token_mint_1,
+        ];
+        addresses.extend(
+            pool_state
+                .reward_infos
+                .iter()
+                .filter(|reward_info| reward_info.is_initialized())
+                .map(|reward_info| reward_info.token_mint),
+        );
+        let accounts = client.get_multiple_accounts(&addresses)?;
+        let accounts = accounts
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow!("clmm amm config or mint account not found"))?;
+        let [amm_config_account, mint_0_account, mint_1_account, reward_mint_accounts @ ..] =
+            accounts.as_slice()
+        else {
+            return Err(anyhow!("unexpected account count"));
+        };
+
+        let mut start_indices =
+            swap_tick_array_start_indices(&pool_state, true, TICK_ARRAY_FETCH_NUM);
+        for start_index in swap_tick_array_start_indices(&pool_state, false, TICK_ARRAY_FETCH_NUM) {
+            if !start_indices.contains(&start_index) {
+                start_indices.push(start_index);
+            }
+        }
+        let tick_arrays = fetch_tick_arrays(client, program_id, pool_id, &start_indices)?
+            .into_iter()
+            .map(|tick_array| (tick_array.start_tick_index, tick_array))
+            .collect();
+
+        Ok(Self {
+            program_id: *program_id,
+            pool_id: *pool_id,
+            amm_config: AmmConfig::from_account_data(&amm_config_account.data)?,
+            tick_arrays,
+            mint_0_token_program: mint_0_account.owner,
+            mint_1_token_program: mint_1_account.owner,
+            reward_token_programs: reward_mint_accounts.iter().map(|a| a.owner).collect(),
+            mint_0_transfer_fee: get_transfer_fee_config(&mint_0_account.data)?,
+            mint_1_transfer_fee: get_transfer_fee_config(&mint_1_account.data)?,
+            epoch: client.get_epoch_info()?.epoch,
+            pool_state,
+        })
+    }
+
+    fn tick_array(&self, start_index: i32) -> Result<&TickArrayState> {
+        self.tick_arrays
+            .get(&start_index)
+            .ok_or_else(|| anyhow!("tick array {} not loaded", start_index))
+    }
+
+    // 与链上 swap_internal 一致, 逐个 tick 推进价格直到用完数量或到达价格限制
+    // sqrt_price_limit_x64 为 0 时不限制价格
+    pub fn compute_swap(
+        &self,
+        zero_for_one: bool,
+        is_base_input: bool,
+        amount_specified: u64,
+        sqrt_price_limit_x64: u128,
+    ) -> Result<ClmmSwapResult> {
+        let pool_state = &self.pool_state;
+        if amount_specified == 0 {
+            return Err(anyhow!("swap amount is zero"));
+        }
+        if pool_state.status & (1 << POOL_STATUS_SWAP_BIT) != 0 {
+            return Err(anyhow!("swap is disabled for this pool"));
+        }
+        let sqrt_price_limit_x64 = match sqrt_price_limit_x64 {
+            0 if zero_for_one => MIN_SQRT_PRICE_X64 + 1,
+            0 => MAX_SQRT_PRICE_X64 - 1,
+            limit => limit,
+        };
+        let limit_valid = if zero_for_one {
+            sqrt_price_limit_x64 < pool_state.sqrt_price_x64
+                && sqrt_price_limit_x64 > MIN_SQRT_PRICE_X64
+        } else {
+            sqrt_price_limit_x64 > pool_state.sqrt_price_x64
+                && sqrt_price_limit_x64 < MAX_SQRT_PRICE_X64
+        };
+        if !limit_valid {
+            return Err(anyhow!("invalid sqrt price limit"));
+        }
+
+        let (mut is_match_pool_current_tick_array, mut current_start_index) = pool_state
+            .first_initialized_tick_array(zero_for_one)
+            .ok_or_else(|| anyhow!("insufficient liquidity for this direction"))?;
+        let mut tick_array = self.tick_array(current_start_index)?;
+        let mut result = ClmmSwapResult {
+            tick_array_start_indices: vec![current_start_index],
+            ..Default::default()
+        };
+        let mut amount_remaining = amount_specified;
+        let mut amount_calculated = 0u64;
+        let mut sqrt_price_x64 = pool_state.sqrt_price_x64;
+        let mut tick = pool_state.tick_current;
+        let mut liquidity = pool_state.liquidity;
+
+        while amount_remaining != 0 && sqrt_price_x64 != sqrt_price_limit_x64 {
+            let sqrt_price_start_x64 = sqrt_price_x64;
+            let mut next_tick = tick_array
+                .next_initialized_tick(tick, pool_state.tick_spacing, zero_for_one)
+                .copied();
+            if next_tick.is_none() && !is_match_pool_current_tick_array {
+                is_match_pool_current_tick_array = true;
+                next_tick = tick_array.first_initialized_tick(zero_for_one).copied();
+            }
+            let next_tick = match next_tick {
+                Some(next_tick) => next_tick,
+                None => {
+                    // 当前 tick array 走完了, 切换到下一个已初始化的 tick array
+                    current_start_index = next_initialized_tick_array_start_index(
+                        &pool_state.tick_array_bitmap,
+                        current_start_index,
+                        pool_state.tick_spacing,
+                        zero_for_one,
+                    )
+                    .ok_or_else(|| anyhow!("insufficient liquidity"))?;
+                    tick_array = self.tick_array(current_start_index)?;
+                    result.tick_array_start_indices.push(current_start_index);
+                    *tick_array
+                        .first_initialized_tick(zero_for_one)
+                        .ok_or_else(|| anyhow!("tick array {} is empty", current_start_index))?
+                }
+            };
+
+            let tick_next = next_tick.tick.clamp(MIN_TICK, MAX_TICK);
+            let sqrt_price_next_x64 = get_sqrt_price_at_tick(tick_next)?;
+            let target_price = if (zero_for_one && sqrt_price_next_x64 < sqrt_price_limit_x64)
+                || (!zero_for_one && sqrt_price_next_x64 > sqrt_price_limit_x64)
+            {
+                sqrt_price_limit_x64
+            } else {
+                sqrt_price_next_x64
+            };
+            let step = compute_swap_step(
+                sqrt_price_start_x64,
+                target_price,
+                liquidity,
+                amount_remaining,
+                self.amm_config.trade_fee_rate,
+                is_base_input,
+                zero_for_one,
+            )?;
+            sqrt_price_x64 = step.sqrt_price_next_x64;
+
+            let (used, calculated) = if is_base_input {
+                (step.amount_in + step.fee_amount, step.amount_out)
+            } else {
+                (step.amount_out, step.amount_in + step.fee_amount)
+            };
+            amount_remaining = amount_remaining
+                .checked_sub(used)
+                .ok_or_else(|| anyhow!("swap amount underflow"))?;
+            amount_calculated = amount_calculated
+                .checked_add(calculated)
+                .ok_or_else(|| anyhow!("swap amount overflow"))?;
+
+            result.trade_fee += step.fee_amount;
+            result.protocol_fee += (u128::from(step.fee_amount)
+                * u128::from(self.amm_config.protocol_fee_rate)
+                / u128::from(FEE_RATE_DENOMINATOR_VALUE)) as u64;
+            result.fund_fee += (u128::from(step.fee_amount)
+                * u128::from(self.amm_config.fund_fee_rate)
+                / u128::from(FEE_RATE_DENOMINATOR_VALUE)) as u64;
+
+            if sqrt_price_x64 == sqrt_price_next_x64 {
+                // 穿过已初始化的 tick, 更新有效流动性
+                let liquidity_net = if zero_for_one {
+                    -next_tick.liquidity_net
+                } else {
+                    next_tick.liquidity_net
+                };
+                liquidity = add_delta(liquidity, liquidity_net)?;
+                tick = if zero_for_one {
+                    tick_next - 1
+                } else {
+                    tick_next
+                };
+            } else if sqrt_price_x64 != sqrt_price_start_x64 {
+                tick = get_tick_at_sqrt_price(sqrt_price_x64)?;
+            }
+        }
+
+        let (amount_in, amount_out) = if is_base_input {
+            (amount_specified - amount_remaining, amount_calculated)
+        } else {
+            (amount_calculated, amount_specified - amount_remaining)
+        };
+        result.amount_in = amount_in;
+        result.amount_out = amount_out;
+        result.sqrt_price_x64 = sqrt_price_x64;
+        result.tick_current = tick;
+        Ok(result)
+    }
+
+    // 输入方向: (zero_for_one, 输入 mint 手续费, 输出 mint 手续费)
+    fn sides(
+        &self,
+        input_token_mint: &Pubkey,
+    ) -> Result<(bool, Option<&TransferFeeConfig>, Option<&TransferFeeConfig>)> {
+        if *input_token_mint == self.pool_state.token_mint_0 {
+            Ok((
+                true,
+                self.mint_0_transfer_fee.as_ref(),
+                self.mint_1_transfer_fee.as_ref(),
+            ))
+        } else if *input_token_mint == self.pool_state.token_mint_1 {
+            Ok((
+                false,
+                self.mint_1_transfer_fee.as_ref(),
+                self.mint_0_transfer_fee.as_ref(),
+            ))
+        } else {
+            Err(anyhow!("{} is not a mint of this pool", input_token_mint))
+        }
+    }
+
+    // 报价并返回 swap 需要经过的 tick array, 价格不限制, 流动性不足以完成兑换时报错
+    pub fn quote_swap(
+        &self,
+        input_token_mint: &Pubkey,
+        amount: u64,
+        is_base_input: bool,
+    ) -> Result<(SwapQuote, Vec<i32>)> {
+        let (zero_for_one, input_transfer_fee_config, output_transfer_fee_config) =
+            self.sides(input_token_mint)?;
+        if is_base_input {
+            let input_transfer_fee =
+                get_transfer_fee(input_transfer_fee_config, self.epoch, amount)?;
+            let result = self.compute_swap(zero_for_one, true, amount - input_transfer_fee, 0)?;
+            if result.amount_in != amount - input_transfer_fee {
+                return Err(anyhow!("insufficient liquidity"));
+            }
+            let output_transfer_fee =
+                get_transfer_fee(output_transfer_fee_config, self.epoch, result.amount_out)?;
+            Ok((
+                SwapQuote {
+                    amount_in: amount,
+                    input_transfer_fee,
+                    amount_out: result.amount_out,
+                    output_transfer_fee,
+                    amount_received: result.amount_out - output_transfer_fee,
+                    trade_fee: result.trade_fee,
+                    protocol_fee: result.protocol_fee,
+                    fund_fee: result.fund_fee,
+                },
+                result.tick_array_start_indices,
+            ))
+        } else {
+            let output_transfer_fee =
+                get_transfer_inverse_fee(output_transfer_fee_config, self.epoch, amount)?;
+            let amount_out = amount + output_transfer_fee;
+            let result = self.compute_swap(zero_for_one, false, amount_out, 0)?;
+            if result.amount_out != amount_out {
+                return Err(anyhow!("insufficient liquidity"));
+            }
+            let input_transfer_fee =
+                get_transfer_inverse_fee(input_transfer_fee_config, self.epoch, result.amount_in)?;
+            Ok((
+                SwapQuote {
+                    amount_in: result.amount_in + input_transfer_fee,
+                    input_transfer_fee,
+                    amount_out,
+                    output_transfer_fee,
+                    amount_received: amount,
+                    trade_fee: result.trade_fee,
+                    protocol_fee: result.protocol_fee,
+                    fund_fee: result.fund_fee,
+                },
+                result.tick_array_start_indices,
+            ))
+        }
+    }
+}
+
+impl SwapQuoter for ClmmPool {
+    fn mints(&self) -> (Pubkey, Pubkey) {
+        (self.pool_state.token_mint_0, self.pool_state.token_mint_1)
+    }
+
+    fn swap_base_input(&self, input_token_mint: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
+        Ok(self.quote_swap(input_token_mint, amount_in, true)?.0)
+    }
+
+    fn swap_base_output(
+        &self,
+        input_token_mint: &Pubkey,
+        amount_out_less_fee: u64,
+    ) -> Result<SwapQuote> {
+        Ok(self
+            .quote_swap(input_token_mint, amount_out_less_fee, false)?
+            .0)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::raydium::clmm::math::get_amounts_from_liquidity;
+    use crate::raydium::clmm::state::{TickState, TICK_ARRAY_SIZE};
+    use crate::utils::SPL_TOKEN_PROGRAM_ID;
+    use std::str::FromStr;
+
+    fn set_bitmap(pool_state: &mut PoolState, start_index: i32) {
+        let position =
+            (start_index / (TICK_ARRAY_SIZE * i32::from(pool_state.tick_spacing)) + 512) as usize;
+        pool_state.tick_array_bitmap[position / 64] |= 1 << (position % 64);
+    }
+
+    // 两个仓位: [-600, 600) 流动性 L, [-1200, -600) 流动性 L, tick_spacing 10, 价格为 1
+    pub(crate) fn test_pool(liquidity: u128) -> ClmmPool {
+        let mut pool_state = PoolState {
+            token_mint_0: Pubkey::new_unique(),
+            token_mint_1: Pubkey::new_unique(),
+            tick_spacing: 10,
+            liquidity,
+            sqrt_price_x64: get_sqrt_price_at_tick(0).unwrap(),
+            tick_current: 0,
+            ..Default::default()
+        };
+        let mut tick_arrays = BTreeMap::new();
+        for (start_index, ticks) in [
+            (-1200, vec![(-1200, liquidity as i128), (-600, 0)]),
+            (-600, vec![(-600, 0)]),
+            (600, vec![(600, -(liquidity as i128))]),
+        ] {
+            let mut tick_array = TickArrayState {
+                start_tick_index: start_index,
+                ticks: vec![TickState::default(); TICK_ARRAY_SIZE as usize],
+                ..Default::default()
+            };
+            for (tick, liquidity_net) in ticks {
+                if tick - start_index >= 600 {
+                    continue;
+                }
+                tick_array.ticks[((tick - start_index) / 10) as usize] = TickState {
+                    tick,
+                    liquidity_net,
+                    liquidity_gross: liquidity,
+                    ..Default::default()
+                };
+            }
+            set_bitmap(&mut pool_state, start_index);
+            tick_arrays.insert(start_index, tick_array);
+        }
+        ClmmPool {
+            program_id: Pubkey::new_unique(),
+            pool_id: Pubkey::new_unique(),
+            pool_state,
+            amm_config: AmmConfig {
+                trade_fee_rate: 2500,
+                protocol_fee_rate: 120_000,
+                fund_fee_rate: 40_000,
+                tick_spacing: 10,
+                ..Default::default()
+            },
+            tick_arrays,
+            mint_0_token_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+            mint_1_token_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+            reward_token_programs: vec![],
+            mint_0_transfer_fee: None,
+            mint_1_transfer_fee: None,
+            epoch: 0,
+        }
+    }
+
+    #[test]
+    fn test_swap_within_current_range() {
+        let pool = test_pool(1_000_000_000_000);
+        let mint_0 = pool.pool_state.token_mint_0;
+        let (quote, tick_arrays) = pool.quote_swap(&mint_0, 1_000_000, true).unwrap();
+        assert_eq!(tick_arrays, vec![-600]);
+        // 价格约为 1, 扣掉 0.25% 手续费
+        assert!(quote.amount_out < 997_500 && quote.amount_out > 997_000);
+        assert!((2_500..=2_501).contains(&quote.trade_fee));
+        assert_eq!(quote.protocol_fee, 300);
+        assert_eq!(quote.fund_fee, 100);
+
+        let quote_out = pool
+            .swap_base_output(&mint_0, quote.amount_received)
+            .unwrap();
+        assert!(quote_out.amount_in <= 1_000_000);
+        assert!(quote_out.amount_in >= 999_990);
+    }
+
+    #[test]
+    fn test_swap_across_ticks() {
+        let liquidity = 1_000_000_000u128;
+        let pool = test_pool(liquidity);
+        let mint_0 = pool.pool_state.token_mint_0;
+        let mint_1 = pool.pool_state.token_mint_1;
+        let sqrt_price_x64 = pool.pool_state.sqrt_price_x64;
+
+        // 把 [-600, 0] 区间的 token_1 全部换出需要的 token_0 (不含手续费)
+        let (range_amount_0, range_amount_1) = get_amounts_from_liquidity(
+            get_sqrt_price_at_tick(-600).unwrap(),
+            get_sqrt_price_at_tick(-600).unwrap(),
+            sqrt_price_x64,
+            liquidity,
+            true,
+        )
+        .unwrap();
+        assert_eq!(range_amount_1, 0);
+
+        // 输入超过该区间容量, 需要穿过 tick -600 进入下一个 tick array
+        let amount_in = range_amount_0 * 2;
+        let result = pool.compute_swap(true, true, amount_in, 0).unwrap();
+        assert_eq!(result.tick_array_start_indices, vec![-600, -1200]);
+        assert!(result.tick_current < -600);
+        assert_eq!(result.amount_in, amount_in);
+
+        // 反方向穿过 tick 600 之后没有流动性
+        assert!(pool.quote_swap(&mint_1, u64::MAX / 4, true).is_err());
+        assert!(pool.quote_swap(&Pubkey::new_unique(), 1, true).is_err());
+    }
+}
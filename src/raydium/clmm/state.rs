@@ -0,0 +1,576 @@
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use super::math::{MAX_TICK, MIN_TICK};
+
+// raydium clmm (集中流动性)
+pub const RAYDIUM_CLMM_PROGRAM_ID: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
+pub const RAYDIUM_CLMM_DEVNET_PROGRAM_ID: &str = "devi51mZmdwUJGU9hjN27vEz64Gps7uUefqxg27EAtH";
+
+pub const AMM_CONFIG_SEED: &str = "amm_config";
+pub const POOL_SEED: &str = "pool";
+pub const POOL_VAULT_SEED: &str = "pool_vault";
+pub const POSITION_SEED: &str = "position";
+pub const TICK_ARRAY_SEED: &str = "tick_array";
+pub const POOL_TICK_ARRAY_BITMAP_SEED: &str = "pool_tick_array_bitmap_extension";
+
+// anchor 账户 discriminator, sha256("account:<Name>")[..8]
+pub const AMM_CONFIG_DISCRIMINATOR: [u8; 8] = [218, 244, 33, 104, 203, 203, 43, 111];
+pub const POOL_STATE_DISCRIMINATOR: [u8; 8] = [247, 237, 227, 245, 215, 195, 222, 70];
+pub const TICK_ARRAY_STATE_DISCRIMINATOR: [u8; 8] = [192, 155, 85, 205, 49, 249, 129, 42];
+pub const PERSONAL_POSITION_STATE_DISCRIMINATOR: [u8; 8] = [70, 111, 150, 126, 230, 15, 25, 117];
+
+// 每个 tick array 存 60 个 tick, pool 自带的 bitmap 覆盖 [-512, 512) 个 tick array
+pub const TICK_ARRAY_SIZE: i32 = 60;
+pub const TICK_ARRAY_BITMAP_SIZE: i32 = 512;
+pub const REWARD_NUM: usize = 3;
+
+const TICK_STATE_LEN: usize = 168;
+const REWARD_INFO_LEN: usize = 169;
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    data.get(offset..offset + N)
+        .ok_or_else(|| anyhow!("account data too short"))?
+        .try_into()
+        .map_err(Into::into)
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    Ok(Pubkey::new_from_array(read_bytes(data, offset)?))
+}
+
+fn check_discriminator(data: &[u8], discriminator: &[u8; 8], name: &str) -> Result<()> {
+    if data.len() < 8 || data[..8] != discriminator[..] {
+        return Err(anyhow!("not a clmm {} account", name));
+    }
+    Ok(())
+}
+
+// AmmConfig 中报价用得到的费率, 费率分母为 1_000_000
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AmmConfig {
+    pub index: u16,
+    pub protocol_fee_rate: u32,
+    pub trade_fee_rate: u32,
+    pub tick_spacing: u16,
+    pub fund_fee_rate: u32,
+}
+
+impl AmmConfig {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        check_discriminator(data, &AMM_CONFIG_DISCRIMINATOR, "amm config")?;
+        Ok(Self {
+            index: u16::from_le_bytes(read_bytes(data, 9)?),
+            protocol_fee_rate: u32::from_le_bytes(read_bytes(data, 43)?),
+            trade_fee_rate: u32::from_le_bytes(read_bytes(data, 47)?),
+            tick_spacing: u16::from_le_bytes(read_bytes(data, 51)?),
+            fund_fee_rate: u32::from_le_bytes(read_bytes(data, 53)?),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RewardInfo {
+    /// 0 表示未初始化
+    pub reward_state: u8,
+    pub token_mint: Pubkey,
+    pub token_vault: Pubkey,
+}
+
+impl RewardInfo {
+    pub fn is_initialized(&self) -> bool {
+        self.reward_state != 0
+    }
+}
+
+// PoolState 中 swap / 仓位 / 报价用得到的字段
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PoolState {
+    pub amm_config: Pubkey,
+    pub owner: Pubkey,
+    pub token_mint_0: Pubkey,
+    pub token_mint_1: Pubkey,
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    pub observation_key: Pubkey,
+    pub mint_decimals_0: u8,
+    pub mint_decimals_1: u8,
+    pub tick_spacing: u16,
+    /// 当前价格所在区间的有效流动性
+    pub liquidity: u128,
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+    pub fee_growth_global_0_x64: u128,
+    pub fee_growth_global_1_x64: u128,
+    pub status: u8,
+    pub reward_infos: Vec<RewardInfo>,
+    /// 每一位表示对应的 tick array 是否有已初始化的 tick
+    pub tick_array_bitmap: [u64; 16],
+}
+
+impl PoolState {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        check_discriminator(data, &POOL_STATE_DISCRIMINATOR, "pool state")?;
+        // 只解析到 tick_array_bitmap 为止
+        if data.len() < 904 + 16 * 8 {
+            return Err(anyhow!("account data too short"));
+        }
+        let mut reward_infos = Vec::with_capacity(REWARD_NUM);
+        for i in 0..REWARD_NUM {
+            let offset = 397 + i * REWARD_INFO_LEN;
+            reward_infos.push(RewardInfo {
+                reward_state: data[offset],
+                token_mint: read_pubkey(data, offset + 57)?,
+                token_vault: read_pubkey(data, offset + 89)?,
+            });
+        }
+        let mut tick_array_bitmap = [0u64; 16];
+        for (i, word) in tick_array_bitmap.iter_mut().enumerate() {
+            *word = u64::from_le_bytes(read_bytes(data, 904 + i * 8)?);
+        }
+        Ok(Self {
+            amm_config: read_pubkey(data, 9)?,
+            owner: read_pubkey(data, 41)?,
+            token_mint_0: read_pubkey(data, 73)?,
+            token_mint_1: read_pubkey(data, 105)?,
+            token_vault_0: read_pubkey(data, 137)?,
+            token_vault_1: read_pubkey(data, 169)?,
+            observation_key: read_pubkey(data, 201)?,
+            mint_decimals_0: data[233],
+            mint_decimals_1: data[234],
+            tick_spacing: u16::from_le_bytes(read_bytes(data, 235)?),
+            liquidity: u128::from_le_bytes(read_bytes(data, 237)?),
+            sqrt_price_x64: u128::from_le_bytes(read_bytes(data, 253)?),
+            tick_current: i32::from_le_bytes(read_bytes(data, 269)?),
+            fee_growth_global_0_x64: u128::from_le_bytes(read_bytes(data, 277)?),
+            fee_growth_global_1_x64: u128::from_le_bytes(read_bytes(data, 293)?),
+            status: data[389],
+            reward_infos,
+            tick_array_bitmap,
+        })
+    }
+
+    // 1 个 token_0 值多少 token_1, 已按 decimals 调整
+    pub fn price(&self) -> f64 {
+        let sqrt_price = self.sqrt_price_x64 as f64 / super::math::Q64 as f64;
+        sqrt_price * sqrt_price * 10f64.powi(i32::from(self.mint_decimals_0))
+            / 10f64.powi(i32::from(self.mint_decimals_1))
+    }
+
+    // 当前价格所在的 tick array 是否已初始化, 没有时按方向找下一个, 都没有返回 None
+    pub fn first_initialized_tick_array(&self, zero_for_one: bool) -> Option<(bool, i32)> {
+        let start_index = get_array_start_index(self.tick_current, self.tick_spacing);
+        if is_tick_array_initialized(&self.tick_array_bitmap, start_index, self.tick_spacing) {
+            return Some((true, start_index));
+        }
+        next_initialized_tick_array_start_index(
+            &self.tick_array_bitmap,
+            start_index,
+            self.tick_spacing,
+            zero_for_one,
+        )
+        .map(|start_index| (false, start_index))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TickState {
+    pub tick: i32,
+    /// 从左往右穿过该 tick 时流动性的变化量 (从右往左取反)
+    pub liquidity_net: i128,
+    pub liquidity_gross: u128,
+    pub fee_growth_outside_0_x64: u128,
+    pub fee_growth_outside_1_x64: u128,
+}
+
+impl TickState {
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(Self {
+            tick: i32::from_le_bytes(read_bytes(data, 0)?),
+            liquidity_net: i128::from_le_bytes(read_bytes(data, 4)?),
+            liquidity_gross: u128::from_le_bytes(read_bytes(data, 20)?),
+            fee_growth_outside_0_x64: u128::from_le_bytes(read_bytes(data, 36)?),
+            fee_growth_outside_1_x64: u128::from_le_bytes(read_bytes(data, 52)?),
+        })
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.liquidity_gross != 0
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TickArrayState {
+    pub pool_id: Pubkey,
+    pub start_tick_index: i32,
+    pub ticks: Vec<TickState>,
+    pub initialized_tick_count: u8,
+}
+
+impl TickArrayState {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        check_discriminator(data, &TICK_ARRAY_STATE_DISCRIMINATOR, "tick array")?;
+        let ticks_end = 44 + TICK_STATE_LEN * TICK_ARRAY_SIZE as usize;
+        let ticks = data
+            .get(44..ticks_end)
+            .ok_or_else(|| anyhow!("account data too short"))?
+            .chunks_exact(TICK_STATE_LEN)
+            .map(TickState::from_bytes)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            pool_id: read_pubkey(data, 8)?,
+            start_tick_index: i32::from_le_bytes(read_bytes(data, 40)?),
+            ticks,
+            initialized_tick_count: *data
+                .get(ticks_end)
+                .ok_or_else(|| anyhow!("account data too short"))?,
+        })
+    }
+
+    // 按方向返回数组内第一个已初始化的 tick
+    pub fn first_initialized_tick(&self, zero_for_one: bool) -> Option<&TickState> {
+        if zero_for_one {
+            self.ticks.iter().rev().find(|tick| tick.is_initialized())
+        } else {
+            self.ticks.iter().find(|tick| tick.is_initialized())
+        }
+    }
+
+    // 从 current_tick_index 出发的下一个已初始化 tick: 向左为 <= current, 向右为 > current
+    // current_tick_index 不在本数组内时返回 None
+    pub fn next_initialized_tick(
+        &self,
+        current_tick_index: i32,
+        tick_spacing: u16,
+        zero_for_one: bool,
+    ) -> Option<&TickState> {
+        if get_array_start_index(current_tick_index, tick_spacing) != self.start_tick_index {
+            return None;
+        }
+        let offset =
+            ((current_tick_index - self.start_tick_index) / i32::from(tick_spacing)) as usize;
+        if zero_for_one {
+            self.ticks[..=offset]
+                .iter()
+                .rev()
+                .find(|tick| tick.is_initialized())
+        } else {
+            self.ticks[offset + 1..]
+                .iter()
+                .find(|tick| tick.is_initialized())
+        }
+    }
+}
+
+// 用户仓位 (每个仓位对应一个 nft)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PersonalPositionState {
+    pub nft_mint: Pubkey,
+    pub pool_id: Pubkey,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub liquidity: u128,
+    pub fee_growth_inside_0_last_x64: u128,
+    pub fee_growth_inside_1_last_x64: u128,
+    pub token_fees_owed_0: u64,
+    pub token_fees_owed_1: u64,
+}
+
+impl PersonalPositionState {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        check_discriminator(
+            data,
+            &PERSONAL_POSITION_STATE_DISCRIMINATOR,
+            "personal position",
+        )?;
+        Ok(Self {
+            nft_mint: read_pubkey(data, 9)?,
+            pool_id: read_pubkey(data, 41)?,
+            tick_lower_index: i32::from_le_bytes(read_bytes(data, 73)?),
+            tick_upper_index: i32::from_le_bytes(read_bytes(data, 77)?),
+            liquidity: u128::from_le_bytes(read_bytes(data, 81)?),
+            fee_growth_inside_0_last_x64: u128::from_le_bytes(read_bytes(data, 97)?),
+            fee_growth_inside_1_last_x64: u128::from_le_bytes(read_bytes(data, 113)?),
+            token_fees_owed_0: u64::from_le_bytes(read_bytes(data, 129)?),
+            token_fees_owed_1: u64::from_le_bytes(read_bytes(data, 137)?),
+        })
+    }
+}
+
+pub fn tick_count(tick_spacing: u16) -> i32 {
+    TICK_ARRAY_SIZE * i32::from(tick_spacing)
+}
+
+// tick 所在 tick array 的起始 tick (向负无穷取整)
+pub fn get_array_start_index(tick_index: i32, tick_spacing: u16) -> i32 {
+    tick_index.div_euclid(tick_count(tick_spacing)) * tick_count(tick_spacing)
+}
+
+// 仓位边界必须是 tick_spacing 的整数倍, 且在 [MIN_TICK, MAX_TICK] 内
+pub fn check_ticks_order(tick_lower: i32, tick_upper: i32, tick_spacing: u16) -> Result<()> {
+    if tick_lower >= tick_upper {
+        return Err(anyhow!("tick_lower must be less than tick_upper"));
+    }
+    if tick_lower < MIN_TICK || tick_upper > MAX_TICK {
+        return Err(anyhow!("tick out of range"));
+    }
+    if tick_lower % i32::from(tick_spacing) != 0 || tick_upper % i32::from(tick_spacing) != 0 {
+        return Err(anyhow!(
+            "ticks must be multiples of tick spacing {}",
+            tick_spacing
+        ));
+    }
+    Ok(())
+}
+
+// tick array 在 bitmap 中的位置, 超出 pool 自带 bitmap 的范围时返回 None
+fn bitmap_position(tick_array_start_index: i32, tick_spacing: u16) -> Option<usize> {
+    let position = tick_array_start_index / tick_count(tick_spacing) + TICK_ARRAY_BITMAP_SIZE;
+    (0..TICK_ARRAY_BITMAP_SIZE * 2)
+        .contains(&position)
+        .then_some(position as usize)
+}
+
+fn bitmap_is_set(bitmap: &[u64; 16], position: usize) -> bool {
+    bitmap[position / 64] >> (position % 64) & 1 == 1
+}
+
+pub fn is_tick_array_initialized(
+    bitmap: &[u64; 16],
+    tick_array_start_index: i32,
+    tick_spacing: u16,
+) -> bool {
+    bitmap_position(tick_array_start_index, tick_spacing)
+        .map(|position| bitmap_is_set(bitmap, position))
+        .unwrap_or(false)
+}
+
+// 按方向找下一个已初始化的 tick array
+// 只查 pool 自带的 bitmap, 超出范围 (需要 bitmap extension 账户) 的 tick array 视为不存在
+pub fn next_initialized_tick_array_start_index(
+    bitmap: &[u64; 16],
+    last_tick_array_start_index: i32,
+    tick_spacing: u16,
+    zero_for_one: bool,
+) -> Option<i32> {
+    let last_start_index = get_array_start_index(last_tick_array_start_index, tick_spacing);
+    let position = last_start_index / tick_count(tick_spacing) + TICK_ARRAY_BITMAP_SIZE;
+    let found = if zero_for_one {
+        (0..position.min(TICK_ARRAY_BITMAP_SIZE * 2))
+            .rev()
+            .find(|p| bitmap_is_set(bitmap, *p as usize))
+    } else {
+        (position.max(-1) + 1..TICK_ARRAY_BITMAP_SIZE * 2)
+            .find(|p| bitmap_is_set(bitmap, *p as usize))
+    };
+    found.map(|p| (p - TICK_ARRAY_BITMAP_SIZE) * tick_count(tick_spacing))
+}
+
+pub fn get_amm_config_address(program_id: &Pubkey, index: u16) -> Pubkey {
+    Pubkey::find_program_address(
+        &[AMM_CONFIG_SEED.as_bytes(), &index.to_be_bytes()],
+        program_id,
+    )
+    .0
+}
+
+// clmm 池子要求 token_mint_0 < token_mint_1
+pub fn get_pool_address(
+    program_id: &Pubkey,
+    amm_config: &Pubkey,
+    token_mint_0: &Pubkey,
+    token_mint_1: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POOL_SEED.as_bytes(),
+            amm_config.as_ref(),
+            token_mint_0.as_ref(),
+            token_mint_1.as_ref(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+pub fn get_pool_vault_address(program_id: &Pubkey, pool_id: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[POOL_VAULT_SEED.as_bytes(), pool_id.as_ref(), mint.as_ref()],
+        program_id,
+    )
+    .0
+}
+
+pub fn get_tick_array_address(
+    program_id: &Pubkey,
+    pool_id: &Pubkey,
+    start_tick_index: i32,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            TICK_ARRAY_SEED.as_bytes(),
+            pool_id.as_ref(),
+            &start_tick_index.to_be_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+pub fn get_tick_array_bitmap_extension_address(program_id: &Pubkey, pool_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[POOL_TICK_ARRAY_BITMAP_SEED.as_bytes(), pool_id.as_ref()],
+        program_id,
+    )
+    .0
+}
+
+pub fn get_protocol_position_address(
+    program_id: &Pubkey,
+    pool_id: &Pubkey,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POSITION_SEED.as_bytes(),
+            pool_id.as_ref(),
+            &tick_lower_index.to_be_bytes(),
+            &tick_upper_index.to_be_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+pub fn get_personal_position_address(program_id: &Pubkey, nft_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[POSITION_SEED.as_bytes(), nft_mint.as_ref()], program_id).0
+}
+
+pub fn fetch_pool_state(client: &RpcClient, pool_id: &Pubkey) -> Result<PoolState> {
+    PoolState::from_account_data(&client.get_account_data(pool_id)?)
+}
+
+pub fn fetch_amm_config(client: &RpcClient, amm_config: &Pubkey) -> Result<AmmConfig> {
+    AmmConfig::from_account_data(&client.get_account_data(amm_config)?)
+}
+
+pub fn fetch_personal_position(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    nft_mint: &Pubkey,
+) -> Result<PersonalPositionState> {
+    PersonalPositionState::from_account_data(
+        &client.get_account_data(&get_personal_position_address(program_id, nft_mint))?,
+    )
+}
+
+// 批量读取 tick array, 不存在的账户会被跳过
+pub fn fetch_tick_arrays(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    pool_id: &Pubkey,
+    start_indices: &[i32],
+) -> Result<Vec<TickArrayState>> {
+    let addresses: Vec<Pubkey> = start_indices
+        .iter()
+        .map(|start_index| get_tick_array_address(program_id, pool_id, *start_index))
+        .collect();
+    let mut tick_arrays = Vec::with_capacity(addresses.len());
+    for account in client
+        .get_multiple_accounts(&addresses)?
+        .into_iter()
+        .flatten()
+    {
+        tick_arrays.push(TickArrayState::from_account_data(&account.data)?);
+    }
+    Ok(tick_arrays)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_get_array_start_index() {
+        assert_eq!(get_array_start_index(120, 3), 0);
+        assert_eq!(get_array_start_index(180, 3), 180);
+        assert_eq!(get_array_start_index(-1, 10), -600);
+        assert_eq!(get_array_start_index(-600, 10), -600);
+        assert_eq!(get_array_start_index(-601, 10), -1200);
+    }
+
+    #[test]
+    fn test_tick_array_bitmap() {
+        let tick_spacing = 10;
+        let mut bitmap = [0u64; 16];
+        // tick array -1200, 0, 1800 已初始化
+        for start_index in [-1200, 0, 1800] {
+            let position = (start_index / 600 + 512) as usize;
+            bitmap[position / 64] |= 1 << (position % 64);
+        }
+        assert!(is_tick_array_initialized(&bitmap, 0, tick_spacing));
+        assert!(!is_tick_array_initialized(&bitmap, 600, tick_spacing));
+
+        assert_eq!(
+            next_initialized_tick_array_start_index(&bitmap, 0, tick_spacing, false),
+            Some(1800)
+        );
+        assert_eq!(
+            next_initialized_tick_array_start_index(&bitmap, 0, tick_spacing, true),
+            Some(-1200)
+        );
+        assert_eq!(
+            next_initialized_tick_array_start_index(&bitmap, 1800, tick_spacing, false),
+            None
+        );
+        assert_eq!(
+            next_initialized_tick_array_start_index(&bitmap, -1200, tick_spacing, true),
+            None
+        );
+    }
+
+    #[test]
+    fn test_next_initialized_tick() {
+        let mut tick_array = TickArrayState {
+            start_tick_index: -600,
+            ticks: vec![TickState::default(); TICK_ARRAY_SIZE as usize],
+            ..Default::default()
+        };
+        for offset in [10, 30] {
+            tick_array.ticks[offset] = TickState {
+                tick: -600 + offset as i32 * 10,
+                liquidity_gross: 1,
+                ..Default::default()
+            };
+        }
+        let next = |tick, zero_for_one| {
+            tick_array
+                .next_initialized_tick(tick, 10, zero_for_one)
+                .map(|tick| tick.tick)
+        };
+        assert_eq!(next(-301, true), Some(-500));
+        assert_eq!(next(-300, true), Some(-300));
+        assert_eq!(next(-300, false), None);
+        assert_eq!(next(-305, false), Some(-300));
+        assert_eq!(next(0, false), None);
+        assert_eq!(tick_array.first_initialized_tick(true).unwrap().tick, -300);
+        assert_eq!(tick_array.first_initialized_tick(false).unwrap().tick, -500);
+    }
+
+    #[test]
+    fn test_clmm_pda() {
+        let program_id = Pubkey::from_str(RAYDIUM_CLMM_PROGRAM_ID).unwrap();
+        let pool_id = Pubkey::new_unique();
+        assert_ne!(
+            get_tick_array_address(&program_id, &pool_id, -600),
+            get_tick_array_address(&program_id, &pool_id, 600)
+        );
+        assert_ne!(
+            get_protocol_position_address(&program_id, &pool_id, -600, 600),
+            get_protocol_position_address(&program_id, &pool_id, 600, -600)
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{anyhow, Result};
+use raydium_cp_swap::curve::{CurveCalculator, RoundDirection};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+use super::utils::{get_pool_reserves, unpack_token_amount, PoolReserves};
+use crate::utils::SPL_TOKEN_PROGRAM_ID;
+
+// 钱包持有的 cp-swap lp 及其对应的底层代币数量 (原始数量, 未扣 token 2022 转账手续费)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LpPosition {
+    pub pool_id: Pubkey,
+    pub lp_token_amount: u64,
+    pub lp_supply: u64,
+    pub token_0_amount: u64,
+    pub token_1_amount: u64,
+    pub mint_0_decimals: u8,
+    pub mint_1_decimals: u8,
+    /// 按当前储备计算的价格: 1 个 token_0 值多少 token_1, 已按 decimals 调整
+    pub price: f64,
+}
+
+// 相对于一直持有两种代币的无常损失, 价值都以 token_1 计价 (按 decimals 调整后的数量)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImpermanentLoss {
+    pub entry_price: f64,
+    pub current_price: f64,
+    /// 同样的流动性在入场价格下对应的代币数量, 即一直持有的数量
+    pub hold_token_0_amount: f64,
+    pub hold_token_1_amount: f64,
+    pub hold_value: f64,
+    pub lp_value: f64,
+    /// lp_value / hold_value - 1, 不大于 0, 不包含期间累计的手续费收入
+    pub impermanent_loss: f64,
+}
+
+fn ui_amount(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10_f64.powi(i32::from(decimals))
+}
+
+impl LpPosition {
+    // 按池子快照计算 lp_token_amount 可以取回的数量, 与链上 withdraw 一致向下取整
+    pub fn from_reserves(
+        pool_id: Pubkey,
+        reserves: &PoolReserves,
+        lp_token_amount: u64,
+    ) -> Result<Self> {
+        let pool_state = &reserves.pool_state;
+        let lp_supply = pool_state.lp_supply;
+        let (reserve_0, reserve_1) = reserves.trading_reserves();
+        if reserve_0 == 0 || reserve_1 == 0 {
+            return Err(anyhow!("pool {} has no liquidity", pool_id));
+        }
+        let (token_0_amount, token_1_amount) = if lp_token_amount == 0 {
+            (0, 0)
+        } else {
+            let result = CurveCalculator::lp_tokens_to_trading_tokens(
+                u128::from(lp_token_amount),
+                u128::from(lp_supply),
+                u128::from(reserve_0),
+                u128::from(reserve_1),
+                RoundDirection::Floor,
+            )
+            .ok_or_else(|| anyhow!("lp token amount calculation failed"))?;
+            (
+                u64::try_from(result.token_0_amount)?,
+                u64::try_from(result.token_1_amount)?,
+            )
+        };
+        let (mint_0_decimals, mint_1_decimals) =
+            (pool_state.mint_0_decimals, pool_state.mint_1_decimals);
+        Ok(Self {
+            pool_id,
+            lp_token_amount,
+            lp_supply,
+            token_0_amount,
+            token_1_amount,
+            mint_0_decimals,
+            mint_1_decimals,
+            price: ui_amount(reserve_1, mint_1_decimals) / ui_amount(reserve_0, mint_0_decimals),
+        })
+    }
+
+    // lp 占池子的比例
+    pub fn share(&self) -> f64 {
+        if self.lp_supply == 0 {
+            return 0.0;
+        }
+        self.lp_token_amount as f64 / self.lp_supply as f64
+    }
+
+    // 以 token_1 计价的仓位价值
+    pub fn value(&self) -> f64 {
+        ui_amount(self.token_0_amount, self.mint_0_decimals) * self.price
+            + ui_amount(self.token_1_amount, self.mint_1_decimals)
+    }
+
+    // entry_price 为入场时 1 个 token_0 值多少 token_1 (按 decimals 调整)
+    // 恒定乘积下 x * y = k 不变, 由当前数量反推入场价格下的数量作为持有对照
+    pub fn impermanent_loss(&self, entry_price: f64) -> Result<ImpermanentLoss> {
+        if !(entry_price.is_finite() && entry_price > 0.0) {
+            return Err(anyhow!("invalid entry price {}", entry_price));
+        }
+        let k = ui_amount(self.token_0_amount, self.mint_0_decimals)
+            * ui_amount(self.token_1_amount, self.mint_1_decimals);
+        let hold_token_0_amount = (k / entry_price).sqrt();
+        let hold_token_1_amount = (k * entry_price).sqrt();
+        let hold_value = hold_token_0_amount * self.price + hold_token_1_amount;
+        let lp_value = self.value();
+        Ok(ImpermanentLoss {
+            entry_price,
+            current_price: self.price,
+            hold_token_0_amount,
+            hold_token_1_amount,
+            hold_value,
+            lp_value,
+            impermanent_loss: if hold_value > 0.0 {
+                lp_value / hold_value - 1.0
+            } else {
+                0.0
+            },
+        })
+    }
+}
+
+// 只和价格变化倍数有关: 2 * sqrt(r) / (1 + r) - 1, r = current_price / entry_price
+pub fn impermanent_loss(entry_price: f64, current_price: f64) -> Result<f64> {
+    if !(entry_price.is_finite() && entry_price > 0.0) {
+        return Err(anyhow!("invalid entry price {}", entry_price));
+    }
+    if !(current_price.is_finite() && current_price >= 0.0) {
+        return Err(anyhow!("invalid current price {}", current_price));
+    }
+    let ratio = current_price / entry_price;
+    Ok(2.0 * ratio.sqrt() / (1.0 + ratio) - 1.0)
+}
+
+// 读取钱包的 lp 余额 (lp mint 由 legacy token program 创建, 只查 ata) 和池子储备
+pub fn fetch_lp_position(
+    client: &RpcClient,
+    pool_id: &Pubkey,
+    owner: &Pubkey,
+) -> Result<LpPosition> {
+    let reserves = get_pool_reserves(client, pool_id)?;
+    let lp_ata = spl_associated_token_account::get_associated_token_address_with_program_id(
+        owner,
+        &reserves.pool_state.lp_mint,
+        &Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+    );
+    let lp_token_amount = match client
+        .get_account_with_commitment(&lp_ata, client.commitment())?
+        .value
+    {
+        Some(account) => unpack_token_amount(&account)?,
+        None => 0,
+    };
+    LpPosition::from_reserves(*pool_id, &reserves, lp_token_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raydium_cp_swap::states::{AmmConfig, PoolState};
+
+    fn pool_reserves(vault_0_amount: u64, vault_1_amount: u64, lp_supply: u64) -> PoolReserves {
+        PoolReserves {
+            pool_state: PoolState {
+                lp_supply,
+                mint_0_decimals: 6,
+                mint_1_decimals: 9,
+                ..Default::default()
+            },
+            amm_config: AmmConfig::default(),
+            vault_0_amount,
+            vault_1_amount,
+        }
+    }
+
+    #[test]
+    fn test_lp_position() {
+        // 100 token_0 / 200 token_1, 持有 10% 的 lp
+        let reserves = pool_reserves(100_000_000, 200_000_000_000, 1_000_000);
+        let position = LpPosition::from_reserves(Pubkey::new_unique(), &reserves, 100_000).unwrap();
+        assert_eq!(position.token_0_amount, 10_000_000);
+        assert_eq!(position.token_1_amount, 20_000_000_000);
+        assert_eq!(position.share(), 0.1);
+        assert_eq!(position.price, 2.0);
+        assert_eq!(position.value(), 40.0);
+
+        // 价格没变, 没有无常损失
+        let report = position.impermanent_loss(2.0).unwrap();
+        assert!(report.impermanent_loss.abs() < 1e-12);
+        assert!((report.hold_token_0_amount - 10.0).abs() < 1e-9);
+
+        // 价格从 0.5 涨到 2 (4 倍), 无常损失为 2 * 2 / 5 - 1 = -20%
+        let report = position.impermanent_loss(0.5).unwrap();
+        assert!((report.hold_token_0_amount - 20.0).abs() < 1e-9);
+        assert!((report.hold_token_1_amount - 10.0).abs() < 1e-9);
+        assert!((report.hold_value - 50.0).abs() < 1e-9);
+        assert!((report.impermanent_loss + 0.2).abs() < 1e-9);
+        assert!((impermanent_loss(0.5, 2.0).unwrap() - report.impermanent_loss).abs() < 1e-9);
+
+        assert!(position.impermanent_loss(0.0).is_err());
+        assert!(impermanent_loss(0.0, 2.0).is_err());
+        assert!(impermanent_loss(-1.0, 2.0).is_err());
+        assert!(impermanent_loss(f64::NAN, 2.0).is_err());
+        assert!(impermanent_loss(1.0, -2.0).is_err());
+        assert!(
+            LpPosition::from_reserves(Pubkey::new_unique(), &pool_reserves(0, 1, 1), 1).is_err()
+        );
+    }
+}
@@ -1,4 +1,10 @@
 pub mod amm_instructions;
+pub mod amm_v4;
+pub mod clmm;
+pub mod lp;
+pub mod quote;
+pub mod state;
+pub mod utils;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,517 @@
+use anyhow::{anyhow, Result};
+use raydium_cp_swap::curve::{CurveCalculator, RoundDirection};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::transfer_fee::{TransferFeeConfig, MAX_FEE_BASIS_POINTS};
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use spl_token_2022::state::Mint;
+
+use super::utils::{get_pool_reserves, PoolReserves};
+
+// 解析 mint 的 TransferFeeConfig 扩展, legacy mint 或没有该扩展时返回 None
+pub fn get_transfer_fee_config(mint_data: &[u8]) -> Result<Option<TransferFeeConfig>> {
+    let mint = StateWithExtensions::<Mint>::unpack(mint_data)?;
+    Ok(mint.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+// 与链上 get_transfer_fee 一致: 转出 pre_fee_amount 时被扣的手续费
+pub fn get_transfer_fee(
+    transfer_fee_config: Option<&TransferFeeConfig>,
+    epoch: u64,
+    pre_fee_amount: u64,
+) -> Result<u64> {
+    match transfer_fee_config {
+        Some(config) => config
+            .calculate_epoch_fee(epoch, pre_fee_amount)
+            .ok_or_else(|| anyhow!("transfer fee calculation failed")),
+        None => Ok(0),
+    }
+}
+
+// 与链上 get_transfer_inverse_fee 一致: 要到账 post_fee_amount 需要额外支付的手续费
+pub fn get_transfer_inverse_fee(
+    transfer_fee_config: Option<&TransferFeeConfig>,
+    epoch: u64,
+    post_fee_amount: u64,
+) -> Result<u64> {
+    if post_fee_amount == 0 {
+        return Ok(0);
+    }
+    let Some(config) = transfer_fee_config else {
+        return Ok(0);
+    };
+    let transfer_fee = config.get_epoch_fee(epoch);
+    if u16::from(transfer_fee.transfer_fee_basis_points) == MAX_FEE_BASIS_POINTS {
+        Ok(u64::from(transfer_fee.maximum_fee))
+    } else {
+        config
+            .calculate_inverse_epoch_fee(epoch, post_fee_amount)
+            .ok_or_else(|| anyhow!("transfer inverse fee calculation failed"))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwapQuote {
+    /// 用户转出的输入代币数量 (含输入 mint 的转账手续费)
+    pub amount_in: u64,
+    pub input_transfer_fee: u64,
+    /// vault 转出的输出代币数量
+    pub amount_out: u64,
+    pub output_transfer_fee: u64,
+    /// 用户实际到账的输出代币数量
+    pub amount_received: u64,
+    pub trade_fee: u64,
+    pub protocol_fee: u64,
+    pub fund_fee: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LiquidityQuote {
+    pub lp_token_amount: u64,
+    /// deposit 为用户需要转出的数量, withdraw 为用户实际到账的数量
+    pub token_0_amount: u64,
+    pub token_1_amount: u64,
+    pub token_0_transfer_fee: u64,
+    pub token_1_transfer_fee: u64,
+}
+
+// 各类池子 (cp-swap / amm v4 / ...) 共用的兑换报价接口
+pub trait SwapQuoter {
+    // 池子的两个代币 (token_0, token_1)
+    fn mints(&self) -> (Pubkey, Pubkey);
+
+    // 固定输入, 返回预期输出
+    fn swap_base_input(&self, input_token_mint: &Pubkey, amount_in: u64) -> Result<SwapQuote>;
+
+    // 固定输出 (用户到账数量), 返回需要的输入
+    fn swap_base_output(
+        &self,
+        input_token_mint: &Pubkey,
+        amount_out_less_fee: u64,
+    ) -> Result<SwapQuote>;
+}
+
+// 离线报价: 池子快照 + 两个 mint 的转账手续费配置 + 当前 epoch
+#[derive(Clone)]
+pub struct PoolQuoter {
+    pub reserves: PoolReserves,
+    pub mint_0_transfer_fee: Option<TransferFeeConfig>,
+    pub mint_1_transfer_fee: Option<TransferFeeConfig>,
+    pub epoch: u64,
+}
+
+impl PoolQuoter {
+    pub fn fetch(client: &RpcClient, pool_id: &Pubkey) -> Result<Self> {
+        let reserves = get_pool_reserves(client, pool_id)?;
+        let mint_0_data = client.get_account_data(&reserves.pool_state.token_0_mint)?;
+        let mint_1_data = client.get_account_data(&reserves.pool_state.token_1_mint)?;
+        Ok(Self {
+            mint_0_transfer_fee: get_transfer_fee_config(&mint_0_data)?,
+            mint_1_transfer_fee: get_transfer_fee_config(&mint_1_data)?,
+            epoch: client.get_epoch_info()?.epoch,
+            reserves,
+        })
+    }
+
+    // 输入方向: (输入储备, 输出储备, 输入 mint 手续费, 输出 mint 手续费)
+    fn sides(
+        &self,
+        input_token_mint: &Pubkey,
+    ) -> Result<(
+        u64,
+        u64,
+        Option<&TransferFeeConfig>,
+        Option<&TransferFeeConfig>,
+    )> {
+        let (reserve_0, reserve_1) = self.reserves.trading_reserves();
+        let pool_state = &self.reserves.pool_state;
+        if *input_token_mint == pool_state.token_0_mint {
+            Ok((
+                reserve_0,
+                reserve_1,
+                self.mint_0_transfer_fee.as_ref(),
+                self.mint_1_transfer_fee.as_ref(),
+            ))
+        } else if *input_token_mint == pool_state.token_1_mint {
+            Ok((
+                reserve_1,
+                reserve_0,
+                self.mint_1_transfer_fee.as_ref(),
+                self.mint_0_transfer_fee.as_ref(),
+            ))
+        } else {
+            Err(anyhow!("{} is not a mint of this pool", input_token_mint))
+        }
+    }
+
+    fn lp_to_token_amounts(
+        &self,
+        lp_token_amount: u64,
+        round_direction: RoundDirection,
+    ) -> Result<(u64, u64)> {
+        let (reserve_0, reserve_1) = self.reserves.trading_reserves();
+        let result = CurveCalculator::lp_tokens_to_trading_tokens(
+            u128::from(lp_token_amount),
+            u128::from(self.reserves.pool_state.lp_supply),
+            u128::from(reserve_0),
+            u128::from(reserve_1),
+            round_direction,
+        )
+        .ok_or_else(|| anyhow!("lp token amount calculation failed"))?;
+        Ok((
+            u64::try_from(result.token_0_amount)?,
+            u64::try_from(result.token_1_amount)?,
+        ))
+    }
+
+    // 对应链上 deposit: 向上取整, 再加上转入 vault 的转账手续费
+    pub fn deposit(&self, lp_token_amount: u64) -> Result<LiquidityQuote> {
+        let (token_0_amount, token_1_amount) =
+            self.lp_to_token_amounts(lp_token_amount, RoundDirection::Ceiling)?;
+        let token_0_transfer_fee = get_transfer_inverse_fee(
+            self.mint_0_transfer_fee.as_ref(),
+            self.epoch,
+            token_0_amount,
+        )?;
+        let token_1_transfer_fee = get_transfer_inverse_fee(
+            self.mint_1_transfer_fee.as_ref(),
+            self.epoch,
+            token_1_amount,
+        )?;
+        Ok(LiquidityQuote {
+            lp_token_amount,
+            token_0_amount: token_0_amount
+                .checked_add(token_0_transfer_fee)
+                .ok_or_else(|| anyhow!("token 0 amount overflow"))?,
+            token_1_amount: token_1_amount
+                .checked_add(token_1_transfer_fee)
+                .ok_or_else(|| anyhow!("token 1 amount overflow"))?,
+            token_0_transfer_fee,
+            token_1_transfer_fee,
+        })
+    }
+
+    // 对应链上 withdraw: 向下取整, 再扣掉从 vault 转出的转账手续费
+    pub fn withdraw(&self, lp_token_amount: u64) -> Result<LiquidityQuote> {
+        let (token_0_amount, token_1_amount) =
+            self.lp_to_token_amounts(lp_token_amount, RoundDirection::Floor)?;
+        let token_0_transfer_fee = get_transfer_fee(
+            self.mint_0_transfer_fee.as_ref(),
+            self.epoch,
+            token_0_amount,
+        )?;
+        let token_1_transfer_fee = get_transfer_fee(
+            self.mint_1_transfer_fee.as_ref(),
+            self.epoch,
+            token_1_amount,
+        )?;
+        Ok(LiquidityQuote {
+            lp_token_amount,
+            token_0_amount: token_0_amount
+                .checked_sub(token_0_transfer_fee)
+                .ok_or_else(|| anyhow!("token 0 transfer fee exceeds amount"))?,
+            token_1_amount: token_1_amount
+                .checked_sub(token_1_transfer_fee)
+                .ok_or_else(|| anyhow!("token 1 transfer fee exceeds amount"))?,
+            token_0_transfer_fee,
+            token_1_transfer_fee,
+        })
+    }
+}
+
+impl SwapQuoter for PoolQuoter {
+    fn mints(&self) -> (Pubkey, Pubkey) {
+        let pool_state = &self.reserves.pool_state;
+        (pool_state.token_0_mint, pool_state.token_1_mint)
+    }
+
+    // 对应链上 swap_base_input
+    fn swap_base_input(&self, input_token_mint: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
+        let (input_reserve, output_reserve, input_fee, output_fee) =
+            self.sides(input_token_mint)?;
+        let input_transfer_fee = get_transfer_fee(input_fee, self.epoch, amount_in)?;
+        let actual_amount_in = amount_in.saturating_sub(input_transfer_fee);
+        if actual_amount_in == 0 {
+            return Err(anyhow!("amount in is zero after transfer fee"));
+        }
+        let amm_config = &self.reserves.amm_config;
+        let result = CurveCalculator::swap_base_input(
+            u128::from(actual_amount_in),
+            u128::from(input_reserve),
+            u128::from(output_reserve),
+            amm_config.trade_fee_rate,
+            amm_config.protocol_fee_rate,
+            amm_config.fund_fee_rate,
+        )
+        .ok_or_else(|| anyhow!("swap base input calculation failed"))?;
+        let amount_out = u64::try_from(result.destination_amount_swapped)?;
+        let output_transfer_fee = get_transfer_fee(output_fee, self.epoch, amount_out)?;
+        let amount_received = amount_out
+            .checked_sub(output_transfer_fee)
+            .ok_or_else(|| anyhow!("output transfer fee exceeds amount out"))?;
+        Ok(SwapQuote {
+            amount_in,
+            input_transfer_fee,
+            amount_out,
+            output_transfer_fee,
+            amount_received,
+            trade_fee: u64::try_from(result.trade_fee)?,
+            protocol_fee: u64::try_from(result.protocol_fee)?,
+            fund_fee: u64::try_from(result.fund_fee)?,
+        })
+    }
+
+    // 对应链上 swap_base_output, amount_out_less_fee 为用户希望到账的数量
+    fn swap_base_output(
+        &self,
+        input_token_mint: &Pubkey,
+        amount_out_less_fee: u64,
+    ) -> Result<SwapQuote> {
+        let (input_reserve, output_reserve, input_fee, output_fee) =
+            self.sides(input_token_mint)?;
+        let output_transfer_fee =
+            get_transfer_inverse_fee(output_fee, self.epoch, amount_out_less_fee)?;
+        let actual_amount_out = amount_out_less_fee
+            .checked_add(output_transfer_fee)
+            .ok_or_else(|| anyhow!("amount out overflow"))?;
+        let amm_config = &self.reserves.amm_config;
+        let result = CurveCalculator::swap_base_output(
+            u128::from(actual_amount_out),
+            u128::from(input_reserve),
+            u128::from(output_reserve),
+            amm_config.trade_fee_rate,
+            amm_config.protocol_fee_rate,
+            amm_config.fund_fee_rate,
+        )
+        .ok_or_else(|| anyhow!("swap base output calculation failed"))?;
+        let source_amount_swapped = u64::try_from(result.source_amount_swapped)?;
+        let input_transfer_fee =
+            get_transfer_inverse_fee(input_fee, self.epoch, source_amount_swapped)?;
+        let amount_in = source_amount_swapped
+            .checked_add(input_transfer_fee)
+            .ok_or_else(|| anyhow!("amount in overflow"))?;
+        Ok(SwapQuote {
+            amount_in,
+            input_transfer_fee,
+            amount_out: actual_amount_out,
+            output_transfer_fee,
+            amount_received: amount_out_less_fee,
+            trade_fee: u64::try_from(result.trade_fee)?,
+            protocol_fee: u64::try_from(result.protocol_fee)?,
+            fund_fee: u64::try_from(result.fund_fee)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raydium_cp_swap::states::{AmmConfig, PoolState};
+    use spl_token_2022::extension::transfer_fee::TransferFee;
+
+    fn transfer_fee_config(basis_points: u16, maximum_fee: u64) -> TransferFeeConfig {
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: maximum_fee.into(),
+            transfer_fee_basis_points: basis_points.into(),
+        };
+        TransferFeeConfig {
+            older_transfer_fee: transfer_fee,
+            newer_transfer_fee: transfer_fee,
+            ..Default::default()
+        }
+    }
+
+    // 储备 1_000_000_000 / 2_000_000_000, lp 供应 1_500_000_000, 0.25% 交易手续费
+    fn test_quoter(
+        mint_0_transfer_fee: Option<TransferFeeConfig>,
+        mint_1_transfer_fee: Option<TransferFeeConfig>,
+    ) -> PoolQuoter {
+        PoolQuoter {
+            reserves: PoolReserves {
+                pool_state: PoolState {
+                    token_0_mint: Pubkey::new_unique(),
+                    token_1_mint: Pubkey::new_unique(),
+                    lp_supply: 1_500_000_000,
+                    ..Default::default()
+                },
+                amm_config: AmmConfig {
+                    trade_fee_rate: 2_500,
+                    protocol_fee_rate: 120_000,
+                    fund_fee_rate: 40_000,
+                    ..Default::default()
+                },
+                vault_0_amount: 1_000_000_000,
+                vault_1_amount: 2_000_000_000,
+            },
+            mint_0_transfer_fee,
+            mint_1_transfer_fee,
+            epoch: 10,
+        }
+    }
+
+    #[test]
+    fn test_transfer_fee() {
+        // 1% 手续费, 上限 5000
+        let config = transfer_fee_config(100, 5_000);
+        assert_eq!(get_transfer_fee(Some(&config), 10, 100_000).unwrap(), 1_000);
+        assert_eq!(
+            get_transfer_fee(Some(&config), 10, 1_000_000).unwrap(),
+            5_000
+        );
+        assert_eq!(get_transfer_fee(None, 10, 1_000_000).unwrap(), 0);
+
+        let fee = get_transfer_inverse_fee(Some(&config), 10, 99_000).unwrap();
+        assert_eq!(fee, 1_000);
+        assert_eq!(
+            get_transfer_fee(Some(&config), 10, 99_000 + fee).unwrap(),
+            fee
+        );
+        assert_eq!(get_transfer_inverse_fee(Some(&config), 10, 0).unwrap(), 0);
+
+        // 100% 手续费时直接取上限
+        let config = transfer_fee_config(MAX_FEE_BASIS_POINTS, 42);
+        assert_eq!(get_transfer_inverse_fee(Some(&config), 10, 1).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_swap_base_input_quote() {
+        let quoter = test_quoter(None, None);
+        let (token_0_mint, token_1_mint) = quoter.mints();
+        let quote = quoter.swap_base_input(&token_0_mint, 1_000_000).unwrap();
+        let result = CurveCalculator::swap_base_input(
+            1_000_000,
+            1_000_000_000,
+            2_000_000_000,
+            2_500,
+            120_000,
+            40_000,
+        )
+        .unwrap();
+        assert_eq!(
+            u128::from(quote.amount_out),
+            result.destination_amount_swapped
+        );
+        assert_eq!(u128::from(quote.trade_fee), result.trade_fee);
+        // 链上公式: 手续费向上取整 2500, 协议费 / 基金费向下取整, 997_500 按 x * y = k 向下取整
+        assert_eq!(
+            quote,
+            SwapQuote {
+                amount_in: 1_000_000,
+                input_transfer_fee: 0,
+                amount_out: 1_993_011,
+                output_transfer_fee: 0,
+                amount_received: 1_993_011,
+                trade_fee: 2_500,
+                protocol_fee: 300,
+                fund_fee: 100,
+            }
+        );
+
+        // 输入 mint 有 1% 转账手续费时只有 990_000 进入曲线
+        let quoter = test_quoter(Some(transfer_fee_config(100, u64::MAX)), None);
+        let quote = quoter.swap_base_input(&token_0_mint, 1_000_000).unwrap();
+        assert_eq!(quote.input_transfer_fee, 10_000);
+        assert_eq!(quote.trade_fee, 2_475);
+        assert_eq!(quote.amount_received, 1_973_101);
+        // 反方向输出 token_0, 到账时扣 1% 转账手续费
+        let quote = quoter.swap_base_input(&token_1_mint, 1_000_000).unwrap();
+        assert_eq!(
+            quote.amount_received,
+            quote.amount_out - quote.output_transfer_fee
+        );
+        assert_eq!(
+            quote.output_transfer_fee,
+            get_transfer_fee(quoter.mint_0_transfer_fee.as_ref(), 10, quote.amount_out).unwrap()
+        );
+        assert!(quoter.swap_base_input(&Pubkey::new_unique(), 1).is_err());
+    }
+
+    #[test]
+    fn test_swap_base_output_quote() {
+        let quoter = test_quoter(None, None);
+        let (token_0_mint, _) = quoter.mints();
+        let quote = quoter.swap_base_output(&token_0_mint, 1_000_000).unwrap();
+        let result = CurveCalculator::swap_base_output(
+            1_000_000,
+            1_000_000_000,
+            2_000_000_000,
+            2_500,
+            120_000,
+            40_000,
+        )
+        .unwrap();
+        assert_eq!(u128::from(quote.amount_in), result.source_amount_swapped);
+        assert_eq!(u128::from(quote.trade_fee), result.trade_fee);
+        assert_eq!(quote.amount_out, 1_000_000);
+        assert_eq!(quote.amount_received, 1_000_000);
+        // 用报价的输入做固定输入兑换, 到账不少于目标数量
+        let quote_in = quoter
+            .swap_base_input(&token_0_mint, quote.amount_in)
+            .unwrap();
+        assert!(quote_in.amount_received >= 1_000_000);
+
+        // 两边都有 1% 转账手续费: vault 需要多转出, 用户需要多转入
+        let quoter = test_quoter(
+            Some(transfer_fee_config(100, u64::MAX)),
+            Some(transfer_fee_config(100, u64::MAX)),
+        );
+        let quote = quoter.swap_base_output(&token_0_mint, 1_000_000).unwrap();
+        assert_eq!(quote.amount_received, 1_000_000);
+        assert_eq!(
+            quote.amount_out
+                - get_transfer_fee(quoter.mint_1_transfer_fee.as_ref(), 10, quote.amount_out)
+                    .unwrap(),
+            1_000_000
+        );
+        let quote_in = quoter
+            .swap_base_input(&token_0_mint, quote.amount_in)
+            .unwrap();
+        assert!(quote_in.amount_received >= 1_000_000);
+    }
+
+    #[test]
+    fn test_liquidity_quote() {
+        let quoter = test_quoter(None, None);
+        let result = CurveCalculator::lp_tokens_to_trading_tokens(
+            1_000_000,
+            1_500_000_000,
+            1_000_000_000,
+            2_000_000_000,
+            RoundDirection::Ceiling,
+        )
+        .unwrap();
+        // deposit 向上取整, withdraw 向下取整
+        let deposit = quoter.deposit(1_000_000).unwrap();
+        assert_eq!(u128::from(deposit.token_0_amount), result.token_0_amount);
+        assert_eq!(u128::from(deposit.token_1_amount), result.token_1_amount);
+        assert_eq!(
+            (deposit.token_0_amount, deposit.token_1_amount),
+            (666_667, 1_333_334)
+        );
+        let withdraw = quoter.withdraw(1_000_000).unwrap();
+        assert_eq!(
+            (withdraw.token_0_amount, withdraw.token_1_amount),
+            (666_666, 1_333_333)
+        );
+
+        // token_1 有 1% 转账手续费
+        let quoter = test_quoter(None, Some(transfer_fee_config(100, u64::MAX)));
+        let deposit = quoter.deposit(1_000_000).unwrap();
+        assert_eq!(deposit.token_0_transfer_fee, 0);
+        assert_eq!(deposit.token_1_amount, 1_346_803);
+        assert_eq!(
+            deposit.token_1_amount
+                - get_transfer_fee(
+                    quoter.mint_1_transfer_fee.as_ref(),
+                    10,
+                    deposit.token_1_amount
+                )
+                .unwrap(),
+            1_333_334
+        );
+        let withdraw = quoter.withdraw(1_000_000).unwrap();
+        assert_eq!(withdraw.token_1_transfer_fee, 13_334);
+        assert_eq!(withdraw.token_1_amount, 1_319_999);
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use raydium_cp_swap::states::{AmmConfig, Observation, ObservationState, PoolState};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use super::amm_instructions::OBSERVATION_NUM;
+use super::utils::deserialize_anchor_account;
+
+// Q32 定点数, observation 中的价格都是 x32 格式
+pub const Q32: u128 = 1 << 32;
+
+pub fn fetch_pool_state(client: &RpcClient, pool_id: &Pubkey) -> Result<PoolState> {
+    deserialize_anchor_account(&client.get_account(pool_id)?)
+}
+
+pub fn fetch_amm_config(client: &RpcClient, amm_config: &Pubkey) -> Result<AmmConfig> {
+    deserialize_anchor_account(&client.get_account(amm_config)?)
+}
+
+pub fn fetch_observation_state(
+    client: &RpcClient,
+    observation_key: &Pubkey,
+) -> Result<ObservationState> {
+    deserialize_anchor_account(&client.get_account(observation_key)?)
+}
+
+// 时间加权平均价格, 价格均为原始数量之比 (未按 decimals 调整)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Twap {
+    pub start_timestamp: u64,
+    pub end_timestamp: u64,
+    /// 1 个 token_0 值多少 token_1, x32 格式
+    pub token_0_price_x32: u128,
+    /// 1 个 token_1 值多少 token_0, x32 格式
+    pub token_1_price_x32: u128,
+}
+
+impl Twap {
+    pub fn token_0_price(&self) -> f64 {
+        self.token_0_price_x32 as f64 / Q32 as f64
+    }
+
+    pub fn token_1_price(&self) -> f64 {
+        self.token_1_price_x32 as f64 / Q32 as f64
+    }
+}
+
+// 从环形缓冲区里取最新的 observation, 以及窗口内最早的一个, 用累计价格之差除以时间差
+// 窗口内只有一个有效 observation 时返回 None
+pub fn twap_from_observations(
+    observations: &[Observation],
+    latest_index: usize,
+    window_secs: u64,
+) -> Option<Twap> {
+    let len = observations.len();
+    let latest = observations.get(latest_index)?;
+    let end_timestamp = latest.block_timestamp;
+    if end_timestamp == 0 {
+        return None;
+    }
+    let mut start: Option<&Observation> = None;
+    for k in 1..len {
+        let observation = &observations[(latest_index + len - k) % len];
+        let block_timestamp = observation.block_timestamp;
+        if block_timestamp == 0
+            || block_timestamp >= end_timestamp
+            || end_timestamp - block_timestamp > window_secs
+        {
+            break;
+        }
+        start = Some(observation);
+    }
+    let start = start?;
+    let start_timestamp = start.block_timestamp;
+    let elapsed = u128::from(end_timestamp - start_timestamp);
+    // 链上累计价格使用 wrapping_add, 这里对应使用 wrapping_sub
+    let token_0_price_x32 = latest
+        .cumulative_token_0_price_x32
+        .wrapping_sub(start.cumulative_token_0_price_x32)
+        / elapsed;
+    let token_1_price_x32 = latest
+        .cumulative_token_1_price_x32
+        .wrapping_sub(start.cumulative_token_1_price_x32)
+        / elapsed;
+    Some(Twap {
+        start_timestamp,
+        end_timestamp,
+        token_0_price_x32,
+        token_1_price_x32,
+    })
+}
+
+pub fn observation_twap(observation_state: &ObservationState, window_secs: u64) -> Option<Twap> {
+    if !observation_state.initialized {
+        return None;
+    }
+    let observations: [Observation; OBSERVATION_NUM] = observation_state.observations;
+    twap_from_observations(
+        &observations,
+        usize::from(observation_state.observation_index),
+        window_secs,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(block_timestamp: u64, price_0_x32: u128, price_1_x32: u128) -> Observation {
+        Observation {
+            block_timestamp,
+            cumulative_token_0_price_x32: price_0_x32 * u128::from(block_timestamp),
+            cumulative_token_1_price_x32: price_1_x32 * u128::from(block_timestamp),
+        }
+    }
+
+    #[test]
+    fn test_twap_from_observations() {
+        let empty = Observation {
+            block_timestamp: 0,
+            cumulative_token_0_price_x32: 0,
+            cumulative_token_1_price_x32: 0,
+        };
+        let mut observations = [empty; 4];
+        // 价格恒定为 2 (token_0 -> token_1) 和 0.5 (token_1 -> token_0), 环形缓冲区从 index 2 开始写
+        observations[2] = observation(100, 2 * Q32, Q32 / 2);
+        observations[3] = observation(115, 2 * Q32, Q32 / 2);
+        observations[0] = observation(130, 2 * Q32, Q32 / 2);
+        observations[1] = observation(145, 2 * Q32, Q32 / 2);
+
+        let twap = twap_from_observations(&observations, 1, 60).unwrap();
+        assert_eq!(twap.start_timestamp, 100);
+        assert_eq!(twap.end_timestamp, 145);
+        assert_eq!(twap.token_0_price(), 2.0);
+        assert_eq!(twap.token_1_price(), 0.5);
+
+        let twap = twap_from_observations(&observations, 1, 20).unwrap();
+        assert_eq!(twap.start_timestamp, 130);
+
+        assert!(twap_from_observations(&observations, 1, 10).is_none());
+        assert!(twap_from_observations(&[empty; 4], 0, 60).is_none());
+    }
+}
@@ -0,0 +1,105 @@
+use anchor_client::anchor_lang::AccountDeserialize;
+use anyhow::{anyhow, Result};
+use raydium_cp_swap::states::{AmmConfig, PoolState};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use spl_token_2022::extension::StateWithExtensions;
+use std::ops::Mul;
+
+use super::amm_instructions::{get_pool_address_by_mints, ClientConfig};
+
+pub fn deserialize_anchor_account<T: AccountDeserialize>(account: &Account) -> Result<T> {
+    let mut data: &[u8] = &account.data;
+    T::try_deserialize(&mut data).map_err(Into::into)
+}
+
+// 解析 token account (legacy 和 token 2022 都支持) 的余额
+pub fn unpack_token_amount(account: &Account) -> Result<u64> {
+    let token_account =
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)?;
+    Ok(token_account.base.amount)
+}
+
+// slippage 为小数, 0.01 即 1%
+pub fn amount_with_slippage(amount: u64, slippage: f64, round_up: bool) -> u64 {
+    if round_up {
+        (amount as f64).mul(1_f64 + slippage).ceil() as u64
+    } else {
+        (amount as f64).mul(1_f64 - slippage).floor() as u64
+    }
+}
+
+// 计算滑点需要的池子快照: 池子状态 + 费率配置 + 两个 vault 余额
+#[derive(Clone)]
+pub struct PoolReserves {
+    pub pool_state: PoolState,
+    pub amm_config: AmmConfig,
+    pub vault_0_amount: u64,
+    pub vault_1_amount: u64,
+}
+
+impl PoolReserves {
+    // 扣除未领取的协议费和基金费之后的可交易储备
+    pub fn trading_reserves(&self) -> (u64, u64) {
+        self.pool_state
+            .vault_amount_without_fee(self.vault_0_amount, self.vault_1_amount)
+    }
+}
+
+pub fn get_pool_reserves(client: &RpcClient, pool_id: &Pubkey) -> Result<PoolReserves> {
+    let pool_state: PoolState = deserialize_anchor_account(&client.get_account(pool_id)?)?;
+    let accounts = client.get_multiple_accounts(&[
+        pool_state.amm_config,
+        pool_state.token_0_vault,
+        pool_state.token_1_vault,
+    ])?;
+    let [amm_config_account, vault_0_account, vault_1_account] = accounts.as_slice() else {
+        return Err(anyhow!("unexpected account count"));
+    };
+    let amm_config_account = amm_config_account
+        .as_ref()
+        .ok_or_else(|| anyhow!("amm config account not found"))?;
+    let vault_0_account = vault_0_account
+        .as_ref()
+        .ok_or_else(|| anyhow!("token 0 vault not found"))?;
+    let vault_1_account = vault_1_account
+        .as_ref()
+        .ok_or_else(|| anyhow!("token 1 vault not found"))?;
+    Ok(PoolReserves {
+        pool_state,
+        amm_config: deserialize_anchor_account(amm_config_account)?,
+        vault_0_amount: unpack_token_amount(vault_0_account)?,
+        vault_1_amount: unpack_token_amount(vault_1_account)?,
+    })
+}
+
+// 查找两个代币在 amm_config_index 费率档位下的池子, 还没创建时返回 None
+pub fn find_pool_by_mints(
+    client: &RpcClient,
+    config: &ClientConfig,
+    amm_config_index: u16,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+) -> Result<Option<Pubkey>> {
+    let pool_id = get_pool_address_by_mints(
+        &config.raydium_cp_program(),
+        amm_config_index,
+        mint_a,
+        mint_b,
+    );
+    let account = client.get_account_with_commitment(&pool_id, client.commitment())?;
+    Ok(account.value.map(|_| pool_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amount_with_slippage() {
+        assert_eq!(amount_with_slippage(1_000_000, 0.01, false), 990_000);
+        assert_eq!(amount_with_slippage(1_000_000, 0.01, true), 1_010_000);
+        assert_eq!(amount_with_slippage(999, 0.005, false), 994);
+        assert_eq!(amount_with_slippage(999, 0.005, true), 1004);
+    }
+}
@@ -0,0 +1,483 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use std::str::FromStr;
+
+use crate::pumpfun::bonding_curve::BondingCurve;
+use crate::pumpfun::buy_token::{buy_instr, BuyAccounts, BuyArgs};
+use crate::raydium::amm_instructions::{self, ClientConfig};
+use crate::raydium::amm_v4::{self, AmmV4Pool};
+use crate::raydium::clmm::{self, pool::ClmmPool};
+use crate::raydium::quote::{PoolQuoter, SwapQuote, SwapQuoter};
+use crate::raydium::utils::amount_with_slippage;
+use crate::utils::{
+    unwrap_sol_instruction, wrap_sol_instructions, SPL_TOKEN_PROGRAM_ID, WSOL_MINT,
+};
+
+// 可以参与路由的池子快照
+#[derive(Clone)]
+pub enum Venue {
+    /// pump.fun 曲线只支持用 SOL 买入, 且只作为单跳路由
+    PumpFun {
+        mint: Pubkey,
+        bonding_curve: BondingCurve,
+        /// Global 账户中的交易手续费
+        fee_basis_points: u64,
+    },
+    // Raydium 池子快照远大于 pump.fun 曲线, 装箱避免撑大整个枚举
+    RaydiumCp {
+        pool_id: Pubkey,
+        quoter: Box<PoolQuoter>,
+    },
+    RaydiumAmmV4(Box<AmmV4Pool>),
+    RaydiumClmm(Box<ClmmPool>),
+}
+
+impl Venue {
+    fn is_pump_fun(&self) -> bool {
+        matches!(self, Venue::PumpFun { .. })
+    }
+
+    // 池子中另一边的代币, mint 不在池子中时返回 None
+    fn other_mint(&self, mint: &Pubkey) -> Option<Pubkey> {
+        let (mint_0, mint_1) = self.mints();
+        if *mint == mint_0 {
+            Some(mint_1)
+        } else if *mint == mint_1 {
+            Some(mint_0)
+        } else {
+            None
+        }
+    }
+}
+
+impl SwapQuoter for Venue {
+    fn mints(&self) -> (Pubkey, Pubkey) {
+        match self {
+            Venue::PumpFun { mint, .. } => (Pubkey::from_str(WSOL_MINT).unwrap_or_default(), *mint),
+            Venue::RaydiumCp { quoter, .. } => quoter.mints(),
+            Venue::RaydiumAmmV4(pool) => pool.mints(),
+            Venue::RaydiumClmm(pool) => pool.mints(),
+        }
+    }
+
+    fn swap_base_input(&self, input_token_mint: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
+        match self {
+            Venue::PumpFun {
+                bonding_curve,
+                fee_basis_points,
+                ..
+            } => {
+                if *input_token_mint != Pubkey::from_str(WSOL_MINT)? {
+                    return Err(anyhow!("pump.fun venue only supports buying with SOL"));
+                }
+                let amount_out = bonding_curve.buy_quote(amount_in, *fee_basis_points)?;
+                Ok(SwapQuote {
+                    amount_in,
+                    amount_out,
+                    amount_received: amount_out,
+                    ..Default::default()
+                })
+            }
+            Venue::RaydiumCp { quoter, .. } => quoter.swap_base_input(input_token_mint, amount_in),
+            Venue::RaydiumAmmV4(pool) => pool.swap_base_input(input_token_mint, amount_in),
+            Venue::RaydiumClmm(pool) => pool.swap_base_input(input_token_mint, amount_in),
+        }
+    }
+
+    fn swap_base_output(
+        &self,
+        input_token_mint: &Pubkey,
+        amount_out_less_fee: u64,
+    ) -> Result<SwapQuote> {
+        match self {
+            Venue::PumpFun {
+                bonding_curve,
+                fee_basis_points,
+                ..
+            } => {
+                if *input_token_mint != Pubkey::from_str(WSOL_MINT)? {
+                    return Err(anyhow!("pump.fun venue only supports buying with SOL"));
+                }
+                Ok(SwapQuote {
+                    amount_in: bonding_curve.buy_cost(amount_out_less_fee, *fee_basis_points)?,
+                    amount_out: amount_out_less_fee,
+                    amount_received: amount_out_less_fee,
+                    ..Default::default()
+                })
+            }
+            Venue::RaydiumCp { quoter, .. } => {
+                quoter.swap_base_output(input_token_mint, amount_out_less_fee)
+            }
+            Venue::RaydiumAmmV4(pool) => {
+                pool.swap_base_output(input_token_mint, amount_out_less_fee)
+            }
+            Venue::RaydiumClmm(pool) => {
+                pool.swap_base_output(input_token_mint, amount_out_less_fee)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteHop {
+    /// Router::venues 中的下标
+    pub venue: usize,
+    pub input_token_mint: Pubkey,
+    pub output_token_mint: Pubkey,
+    pub quote: SwapQuote,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    pub amount_in: u64,
+    /// 最后一跳用户实际到账的数量
+    pub amount_out: u64,
+    pub hops: Vec<RouteHop>,
+}
+
+// 在给定的池子快照中选择输出最多的单跳或两跳路由
+#[derive(Clone, Default)]
+pub struct Router {
+    pub venues: Vec<Venue>,
+}
+
+impl Router {
+    pub fn new(venues: Vec<Venue>) -> Self {
+        Self { venues }
+    }
+
+    fn hop(&self, venue: usize, input_token_mint: &Pubkey, amount_in: u64) -> Option<RouteHop> {
+        let output_token_mint = self.venues[venue].other_mint(input_token_mint)?;
+        // 报价失败 (流动性不足 / 方向不支持等) 的池子直接跳过
+        let quote = self.venues[venue]
+            .swap_base_input(input_token_mint, amount_in)
+            .ok()?;
+        (quote.amount_received > 0).then_some(RouteHop {
+            venue,
+            input_token_mint: *input_token_mint,
+            output_token_mint,
+            quote,
+        })
+    }
+
+    // 固定输入 amount_in, 返回最终到账最多的路由
+    pub fn best_route(
+        &self,
+        input_token_mint: &Pubkey,
+        output_token_mint: &Pubkey,
+        amount_in: u64,
+    ) -> Result<Route> {
+        let mut best: Option<Route> = None;
+        let mut consider = |hops: Vec<RouteHop>| {
+            let amount_out = hops
+                .last()
+                .map(|hop| hop.quote.amount_received)
+                .unwrap_or(0);
+            if best
+                .as_ref()
+                .is_none_or(|route| amount_out > route.amount_out)
+            {
+                best = Some(Route {
+                    amount_in,
+                    amount_out,
+                    hops,
+                });
+            }
+        };
+
+        for first in 0..self.venues.len() {
+            let Some(first_hop) = self.hop(first, input_token_mint, amount_in) else {
+                continue;
+            };
+            if first_hop.output_token_mint == *output_token_mint {
+                consider(vec![first_hop]);
+                continue;
+            }
+            if self.venues[first].is_pump_fun() {
+                continue;
+            }
+            for second in 0..self.venues.len() {
+                if second == first || self.venues[second].is_pump_fun() {
+                    continue;
+                }
+                let Some(second_hop) = self.hop(
+                    second,
+                    &first_hop.output_token_mint,
+                    first_hop.quote.amount_received,
+                ) else {
+                    continue;
+                };
+                if second_hop.output_token_mint == *output_token_mint {
+                    consider(vec![first_hop.clone(), second_hop]);
+                }
+            }
+        }
+        best.ok_or_else(|| {
+            anyhow!(
+                "no route from {} to {}",
+                input_token_mint,
+                output_token_mint
+            )
+        })
+    }
+
+    // 按路由生成指令, 每一跳都按 config.slippage 设置最小输出
+    // 第二跳的输入为第一跳的最小输出, 多出来的中间代币留在用户钱包
+    // 输入为 WSOL 时先把 SOL 包装成 WSOL, 输出为 WSOL 时最后解包
+    pub fn prepare_route_instructions(
+        &self,
+        config: &ClientConfig,
+        payer: Pubkey,
+        route: &Route,
+    ) -> Result<Vec<Instruction>> {
+        let wsol_mint = Pubkey::from_str(WSOL_MINT)?;
+        let slippage = config.slippage();
+        let mut instructions = vec![];
+        let mut amount_in = route.amount_in;
+        for (i, hop) in route.hops.iter().enumerate() {
+            let venue = self
+                .venues
+                .get(hop.venue)
+                .ok_or_else(|| anyhow!("venue {} not found", hop.venue))?;
+            if i == 0 && hop.input_token_mint == wsol_mint && !venue.is_pump_fun() {
+                instructions.extend(wrap_sol_instructions(&payer, amount_in)?);
+            }
+            match venue {
+                Venue::PumpFun {
+                    mint,
+                    bonding_curve,
+                    fee_basis_points,
+                } => {
+                    // buy 为固定输出: 买入最小输出数量的代币, 最多花费 amount_in
+                    let amount = amount_with_slippage(
+                        bonding_curve.buy_quote(amount_in, *fee_basis_points)?,
+                        slippage,
+                        false,
+                    );
+                    instructions.push(create_associated_token_account_idempotent(
+                        &payer,
+                        &payer,
+                        mint,
+                        &Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+                    ));
+                    instructions.push(buy_instr(
+                        &BuyAccounts::new(mint, &payer)?,
+                        &BuyArgs {
+                            amount,
+                            max_sol_cost: amount_in,
+                        },
+                    )?);
+                }
+                Venue::RaydiumCp { pool_id, quoter } => {
+                    instructions.extend(amm_instructions::prepare_swap_base_input_instructions(
+                        config,
+                        payer,
+                        *pool_id,
+                        quoter,
+                        hop.input_token_mint,
+                        amount_in,
+                    )?);
+                }
+                Venue::RaydiumAmmV4(pool) => {
+                    instructions.extend(amm_v4::prepare_swap_base_in_instructions(
+                        pool,
+                        payer,
+                        hop.input_token_mint,
+                        amount_in,
+                        slippage,
+                    )?);
+                }
+                Venue::RaydiumClmm(pool) => {
+                    instructions.extend(clmm::instructions::prepare_swap_base_input_instructions(
+                        pool,
+                        payer,
+                        hop.input_token_mint,
+                        amount_in,
+                        slippage,
+                    )?);
+                }
+            }
+            let quote = venue.swap_base_input(&hop.input_token_mint, amount_in)?;
+            amount_in = amount_with_slippage(quote.amount_received, slippage, false);
+        }
+        if route.hops.last().map(|hop| hop.output_token_mint) == Some(wsol_mint) {
+            instructions.push(unwrap_sol_instruction(&payer)?);
+        }
+        Ok(instructions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raydium::amm_instructions::ClientConfigBuilder;
+    use crate::raydium::amm_v4::{
+        AmmInfo, MarketState, AMM_STATUS_SWAP_ONLY, RAYDIUM_AMM_V4_PROGRAM_ID,
+    };
+    use crate::raydium::clmm::pool::tests::test_pool;
+    use crate::raydium::utils::PoolReserves;
+    use raydium_cp_swap::states::{AmmConfig, PoolState};
+    use spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeConfig};
+
+    fn amm_v4_pool(coin_mint: Pubkey, pc_mint: Pubkey, coin_amount: u64, pc_amount: u64) -> Venue {
+        Venue::RaydiumAmmV4(Box::new(AmmV4Pool {
+            program_id: Pubkey::from_str(RAYDIUM_AMM_V4_PROGRAM_ID).unwrap(),
+            amm_id: Pubkey::new_unique(),
+            amm_info: AmmInfo {
+                status: AMM_STATUS_SWAP_ONLY,
+                swap_fee_numerator: 25,
+                swap_fee_denominator: 10_000,
+                coin_vault_mint: coin_mint,
+                pc_vault_mint: pc_mint,
+                ..Default::default()
+            },
+            market: MarketState::default(),
+            coin_vault_amount: coin_amount,
+            pc_vault_amount: pc_amount,
+        }))
+    }
+
+    // 输出代币 (token_1) 有 1% 的转账手续费
+    fn raydium_cp_pool(mint_0: Pubkey, mint_1: Pubkey) -> Venue {
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        Venue::RaydiumCp {
+            pool_id: Pubkey::new_unique(),
+            quoter: Box::new(PoolQuoter {
+                reserves: PoolReserves {
+                    pool_state: PoolState {
+                        token_0_mint: mint_0,
+                        token_1_mint: mint_1,
+                        token_0_program: Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+                        token_1_program: spl_token_2022::id(),
+                        ..Default::default()
+                    },
+                    amm_config: AmmConfig {
+                        trade_fee_rate: 2_500,
+                        ..Default::default()
+                    },
+                    vault_0_amount: 1_000_000_000,
+                    vault_1_amount: 1_000_000_000,
+                },
+                mint_0_transfer_fee: None,
+                mint_1_transfer_fee: Some(TransferFeeConfig {
+                    older_transfer_fee: transfer_fee,
+                    newer_transfer_fee: transfer_fee,
+                    ..Default::default()
+                }),
+                epoch: 0,
+            }),
+        }
+    }
+
+    fn clmm_pool(mint_0: Pubkey, mint_1: Pubkey) -> Venue {
+        let mut pool = test_pool(1_000_000_000_000);
+        pool.pool_state.token_mint_0 = mint_0;
+        pool.pool_state.token_mint_1 = mint_1;
+        Venue::RaydiumClmm(Box::new(pool))
+    }
+
+    #[test]
+    fn test_best_route() {
+        let (x, y, z) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let router = Router::new(vec![
+            // x/y 价格 1:1, 深度不同
+            amm_v4_pool(x, y, 10_000_000, 10_000_000),
+            amm_v4_pool(x, y, 1_000_000_000, 1_000_000_000),
+            clmm_pool(y, z),
+        ]);
+
+        let route = router.best_route(&x, &y, 1_000_000).unwrap();
+        assert_eq!(route.hops.len(), 1);
+        assert_eq!(route.hops[0].venue, 1);
+
+        let route = router.best_route(&x, &z, 1_000_000).unwrap();
+        assert_eq!(
+            route.hops.iter().map(|hop| hop.venue).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(route.hops[1].input_token_mint, y);
+        assert_eq!(route.amount_out, route.hops[1].quote.amount_received);
+
+        assert!(router.best_route(&z, &Pubkey::new_unique(), 1_000).is_err());
+    }
+
+    #[test]
+    fn test_route_instructions() {
+        let mint = Pubkey::new_unique();
+        let (y, z) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let wsol_mint = Pubkey::from_str(WSOL_MINT).unwrap();
+        let router = Router::new(vec![
+            Venue::PumpFun {
+                mint,
+                bonding_curve: BondingCurve {
+                    virtual_token_reserves: 1_073_000_000_000_000,
+                    virtual_sol_reserves: 30_000_000_000,
+                    real_token_reserves: 793_100_000_000_000,
+                    real_sol_reserves: 0,
+                    token_total_supply: 1_000_000_000_000_000,
+                    complete: false,
+                },
+                fee_basis_points: 100,
+            },
+            clmm_pool(wsol_mint, y),
+            clmm_pool(y, z),
+        ]);
+        let config = ClientConfigBuilder::default().build().unwrap();
+        let payer = Pubkey::new_unique();
+
+        // pump.fun 直接用 SOL 买入, 不需要包装 WSOL
+        let route = router.best_route(&wsol_mint, &mint, 1_000_000_000).unwrap();
+        assert_eq!(route.hops[0].venue, 0);
+        let instructions = router
+            .prepare_route_instructions(&config, payer, &route)
+            .unwrap();
+        assert_eq!(instructions.len(), 2);
+
+        // 不能把 pump.fun 代币卖出, 也不能作为两跳的一部分
+        assert!(router.best_route(&mint, &wsol_mint, 1_000).is_err());
+
+        // wsol -> y -> z: 包装 SOL (3 条) + 两跳各 2 条
+        let route = router.best_route(&wsol_mint, &z, 1_000_000).unwrap();
+        assert_eq!(route.hops.len(), 2);
+        let instructions = router
+            .prepare_route_instructions(&config, payer, &route)
+            .unwrap();
+        assert_eq!(instructions.len(), 3 + 2 + 2);
+    }
+
+    #[test]
+    fn test_raydium_cp_route_instructions() {
+        let (x, y) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let router = Router::new(vec![raydium_cp_pool(x, y)]);
+        let config = ClientConfigBuilder::default()
+            .slippage(0.01)
+            .build()
+            .unwrap();
+        let payer = Pubkey::new_unique();
+
+        let route = router.best_route(&x, &y, 1_000_000).unwrap();
+        let quote = &route.hops[0].quote;
+        assert!(quote.output_transfer_fee > 0);
+        assert_eq!(route.amount_out, quote.amount_received);
+        let instructions = router
+            .prepare_route_instructions(&config, payer, &route)
+            .unwrap();
+        assert_eq!(instructions.len(), 2);
+        // 最小输出按扣除转账手续费后的到账数量计算, 与路由排序用的报价一致
+        let swap = &instructions[1];
+        assert_eq!(swap.data[8..16], 1_000_000u64.to_le_bytes());
+        assert_eq!(
+            swap.data[16..24],
+            amount_with_slippage(quote.amount_received, 0.01, false).to_le_bytes()
+        );
+    }
+}
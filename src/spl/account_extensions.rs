@@ -0,0 +1,188 @@
+use anyhow::Result;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use spl_token_2022::extension::cpi_guard::{self, CpiGuard};
+use spl_token_2022::extension::memo_transfer;
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_2022::state::Account;
+
+// token program 只检查转账的上一条指令是否为 memo 程序, 不检查内容
+pub const DEFAULT_TRANSFER_MEMO: &str = "transfer";
+
+fn token_account(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address_with_program_id(
+        owner,
+        mint,
+        &spl_token_2022::id(),
+    )
+}
+
+// 转入该账户的 transfer 前面必须紧跟一条 memo 指令
+pub fn memo_required(account_data: &[u8]) -> Result<bool> {
+    let state = StateWithExtensions::<Account>::unpack(account_data)?;
+    Ok(memo_transfer::memo_required(&state))
+}
+
+// 开启后 owner 签名的转账 / 销毁 / 授权等操作不能通过 cpi 调用
+pub fn cpi_guard_enabled(account_data: &[u8]) -> Result<bool> {
+    let state = StateWithExtensions::<Account>::unpack(account_data)?;
+    Ok(state
+        .get_extension::<CpiGuard>()
+        .map(|extension| bool::from(extension.lock_cpi))
+        .unwrap_or(false))
+}
+
+// 扩展不存在时先扩容账户 (payer 支付租金), 已存在时 reallocate 不做任何事
+fn reallocate_instr(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    extension_type: ExtensionType,
+) -> Result<Instruction> {
+    Ok(spl_token_2022::instruction::reallocate(
+        &spl_token_2022::id(),
+        &token_account(owner, mint),
+        payer,
+        owner,
+        &[],
+        &[extension_type],
+    )?)
+}
+
+pub fn enable_required_memo_transfers_instr(owner: &Pubkey, mint: &Pubkey) -> Result<Instruction> {
+    Ok(memo_transfer::instruction::enable_required_transfer_memos(
+        &spl_token_2022::id(),
+        &token_account(owner, mint),
+        owner,
+        &[],
+    )?)
+}
+
+pub fn disable_required_memo_transfers_instr(owner: &Pubkey, mint: &Pubkey) -> Result<Instruction> {
+    Ok(memo_transfer::instruction::disable_required_transfer_memos(
+        &spl_token_2022::id(),
+        &token_account(owner, mint),
+        owner,
+        &[],
+    )?)
+}
+
+// 需要 payer / owner 签名
+pub fn prepare_enable_required_memo_transfers_instructions(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Vec<Instruction>> {
+    Ok(vec![
+        reallocate_instr(payer, owner, mint, ExtensionType::MemoTransfer)?,
+        enable_required_memo_transfers_instr(owner, mint)?,
+    ])
+}
+
+pub fn enable_cpi_guard_instr(owner: &Pubkey, mint: &Pubkey) -> Result<Instruction> {
+    Ok(cpi_guard::instruction::enable_cpi_guard(
+        &spl_token_2022::id(),
+        &token_account(owner, mint),
+        owner,
+        &[],
+    )?)
+}
+
+pub fn disable_cpi_guard_instr(owner: &Pubkey, mint: &Pubkey) -> Result<Instruction> {
+    Ok(cpi_guard::instruction::disable_cpi_guard(
+        &spl_token_2022::id(),
+        &token_account(owner, mint),
+        owner,
+        &[],
+    )?)
+}
+
+// 需要 payer / owner 签名
+pub fn prepare_enable_cpi_guard_instructions(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Vec<Instruction>> {
+    Ok(vec![
+        reallocate_instr(payer, owner, mint, ExtensionType::CpiGuard)?,
+        enable_cpi_guard_instr(owner, mint)?,
+    ])
+}
+
+// 目标账户要求 memo 时返回需要放在转账前面的 memo 指令
+pub fn required_memo_instr(destination_data: &[u8]) -> Result<Option<Instruction>> {
+    Ok(memo_required(destination_data)?
+        .then(|| spl_memo::build_memo(DEFAULT_TRANSFER_MEMO.as_bytes(), &[])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::program_pack::Pack;
+    use spl_token_2022::extension::memo_transfer::MemoTransfer;
+    use spl_token_2022::extension::{BaseStateWithExtensionsMut, StateWithExtensionsMut};
+    use spl_token_2022::state::AccountState;
+
+    fn account_data(require_memo: bool, lock_cpi: bool) -> Vec<u8> {
+        let space = ExtensionType::try_calculate_account_len::<Account>(&[
+            ExtensionType::MemoTransfer,
+            ExtensionType::CpiGuard,
+        ])
+        .unwrap();
+        let mut data = vec![0; space];
+        let mut state = StateWithExtensionsMut::<Account>::unpack_uninitialized(&mut data).unwrap();
+        let memo_transfer = state.init_extension::<MemoTransfer>(true).unwrap();
+        memo_transfer.require_incoming_transfer_memos = require_memo.into();
+        let cpi_guard = state.init_extension::<CpiGuard>(true).unwrap();
+        cpi_guard.lock_cpi = lock_cpi.into();
+        state.base = Account {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_memo_transfer_and_cpi_guard() {
+        let data = account_data(true, true);
+        assert!(memo_required(&data).unwrap());
+        assert!(cpi_guard_enabled(&data).unwrap());
+        let memo = required_memo_instr(&data).unwrap().unwrap();
+        assert_eq!(memo.program_id, spl_memo::id());
+        assert_eq!(memo.data, DEFAULT_TRANSFER_MEMO.as_bytes());
+
+        let data = account_data(false, false);
+        assert!(!cpi_guard_enabled(&data).unwrap());
+        assert!(required_memo_instr(&data).unwrap().is_none());
+
+        // 没有扩展的普通账户
+        let mut data = vec![0; Account::LEN];
+        Account {
+            state: AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        assert!(!memo_required(&data).unwrap());
+        assert!(!cpi_guard_enabled(&data).unwrap());
+
+        let (payer, owner, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let instructions =
+            prepare_enable_required_memo_transfers_instructions(&payer, &owner, &mint).unwrap();
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0].accounts[1].pubkey, payer);
+        assert_eq!(
+            instructions[1].accounts[0].pubkey,
+            token_account(&owner, &mint)
+        );
+        assert!(instructions[1].accounts[1].is_signer);
+        let instructions = prepare_enable_cpi_guard_instructions(&payer, &owner, &mint).unwrap();
+        assert_eq!(instructions.len(), 2);
+    }
+}
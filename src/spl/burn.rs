@@ -0,0 +1,53 @@
+use anyhow::Result;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+// 销毁 owner 的 token 2022 ata 中的 amount
+// authority 可以是 owner 本人, 也可以是 mint 的 permanent delegate
+pub fn burn_instr(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction> {
+    let ata = spl_associated_token_account::get_associated_token_address_with_program_id(
+        owner,
+        mint,
+        &spl_token_2022::id(),
+    );
+    Ok(spl_token_2022::instruction::burn_checked(
+        &spl_token_2022::id(),
+        &ata,
+        mint,
+        authority,
+        &[],
+        amount,
+        decimals,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burn_instr() {
+        let (delegate, mint, holder) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ix = burn_instr(&delegate, &mint, &holder, 100, 6).unwrap();
+        assert_eq!(
+            ix.accounts[0].pubkey,
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &holder,
+                &mint,
+                &spl_token_2022::id(),
+            )
+        );
+        assert_eq!(ix.accounts[1].pubkey, mint);
+        assert_eq!(ix.accounts[2].pubkey, delegate);
+        assert!(ix.accounts[2].is_signer);
+    }
+}
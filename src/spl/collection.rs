@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Result};
+use mpl_token_metadata::accounts::{MasterEdition, Metadata};
+use mpl_token_metadata::instructions::{
+    UnverifyCollectionV1Builder, UnverifyCreatorV1Builder, VerifyCollectionV1Builder,
+    VerifyCreatorV1Builder,
+};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use super::nft::{prepare_create_nft_instructions, NftArgs};
+use crate::utils::batch_instructions;
+
+// 每个 item 需要 create + mint + verify 三条指令和 item mint 的签名, 一笔交易只能放下一个 item
+// 单独验证时每条指令只多出 item 的 metadata 账户, 一笔交易可以验证多个
+pub const VERIFY_COLLECTION_BATCH_SIZE: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollectionItem {
+    /// 需要作为签名者的新 mint
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub args: NftArgs,
+}
+
+// 创建 collection nft, 之后 item 的 collection 字段指向 collection_mint
+pub fn prepare_create_collection_instructions(
+    collection_mint: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    payer: &Pubkey,
+    args: &NftArgs,
+) -> Result<Vec<Instruction>> {
+    if args.collection.is_some() {
+        return Err(anyhow!(
+            "a collection nft cannot belong to another collection"
+        ));
+    }
+    let args = NftArgs {
+        is_collection: true,
+        ..args.clone()
+    };
+    prepare_create_nft_instructions(collection_mint, authority, owner, payer, &args)
+}
+
+// authority 为 collection 的 update authority
+pub fn verify_collection_instr(
+    mint: &Pubkey,
+    collection_mint: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
+    VerifyCollectionV1Builder::new()
+        .authority(*authority)
+        .metadata(Metadata::find_pda(mint).0)
+        .collection_mint(*collection_mint)
+        .collection_metadata(Some(Metadata::find_pda(collection_mint).0))
+        .collection_master_edition(Some(MasterEdition::find_pda(collection_mint).0))
+        .instruction()
+}
+
+pub fn unverify_collection_instr(
+    mint: &Pubkey,
+    collection_mint: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
+    UnverifyCollectionV1Builder::new()
+        .authority(*authority)
+        .metadata(Metadata::find_pda(mint).0)
+        .collection_mint(*collection_mint)
+        .collection_metadata(Some(Metadata::find_pda(collection_mint).0))
+        .instruction()
+}
+
+// creator 只能验证 / 取消验证自己, 需要 creator 签名
+pub fn verify_creator_instr(mint: &Pubkey, creator: &Pubkey) -> Instruction {
+    VerifyCreatorV1Builder::new()
+        .authority(*creator)
+        .metadata(Metadata::find_pda(mint).0)
+        .instruction()
+}
+
+pub fn unverify_creator_instr(mint: &Pubkey, creator: &Pubkey) -> Instruction {
+    UnverifyCreatorV1Builder::new()
+        .authority(*creator)
+        .metadata(Metadata::find_pda(mint).0)
+        .instruction()
+}
+
+// 创建 item 并在同一笔交易中验证 collection, 需要 item mint / authority / payer 签名
+pub fn prepare_mint_collection_item_instructions(
+    collection_mint: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    item: &CollectionItem,
+) -> Result<Vec<Instruction>> {
+    let args = NftArgs {
+        collection: Some(*collection_mint),
+        is_collection: false,
+        ..item.args.clone()
+    };
+    let mut instructions =
+        prepare_create_nft_instructions(&item.mint, authority, &item.owner, payer, &args)?;
+    instructions.push(verify_collection_instr(
+        &item.mint,
+        collection_mint,
+        authority,
+    ));
+    Ok(instructions)
+}
+
+// 整批 drop: 每个 item 一笔交易, 返回的第 i 组指令需要 items[i].mint 签名
+pub fn prepare_collection_drop_transactions(
+    collection_mint: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    items: &[CollectionItem],
+) -> Result<Vec<Vec<Instruction>>> {
+    items
+        .iter()
+        .map(|item| {
+            prepare_mint_collection_item_instructions(collection_mint, authority, payer, item)
+        })
+        .collect()
+}
+
+// 对已创建但未验证 (或验证失败需要重试) 的 item 分批验证, 只需要 authority 签名
+pub fn prepare_verify_collection_transactions(
+    collection_mint: &Pubkey,
+    authority: &Pubkey,
+    mints: &[Pubkey],
+    batch_size: usize,
+) -> Result<Vec<Vec<Instruction>>> {
+    batch_instructions(mints, batch_size, |mint| {
+        Ok(verify_collection_instr(mint, collection_mint, authority))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collection_instructions() {
+        let (collection_mint, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let args = NftArgs::new("News", "NEWS", "https://example.com/collection.json");
+        let instructions = prepare_create_collection_instructions(
+            &collection_mint,
+            &authority,
+            &authority,
+            &authority,
+            &args,
+        )
+        .unwrap();
+        assert_eq!(instructions.len(), 2);
+        let args = NftArgs {
+            collection: Some(collection_mint),
+            ..args
+        };
+        assert!(prepare_create_collection_instructions(
+            &collection_mint,
+            &authority,
+            &authority,
+            &authority,
+            &args,
+        )
+        .is_err());
+
+        let items: Vec<CollectionItem> = (0..1_000)
+            .map(|i| CollectionItem {
+                mint: Pubkey::new_unique(),
+                owner: Pubkey::new_unique(),
+                args: NftArgs::new(
+                    &format!("News #{}", i),
+                    "NEWS",
+                    &format!("https://example.com/{}.json", i),
+                ),
+            })
+            .collect();
+        let transactions =
+            prepare_collection_drop_transactions(&collection_mint, &authority, &authority, &items)
+                .unwrap();
+        assert_eq!(transactions.len(), 1_000);
+        let verify = &transactions[0][2];
+        assert_eq!(verify.accounts[0].pubkey, authority);
+        assert!(verify.accounts[0].is_signer);
+        assert_eq!(
+            verify.accounts[2].pubkey,
+            Metadata::find_pda(&items[0].mint).0
+        );
+        assert_eq!(verify.accounts[3].pubkey, collection_mint);
+
+        let mints: Vec<Pubkey> = items.iter().map(|item| item.mint).collect();
+        let batches = prepare_verify_collection_transactions(
+            &collection_mint,
+            &authority,
+            &mints,
+            VERIFY_COLLECTION_BATCH_SIZE,
+        )
+        .unwrap();
+        assert_eq!(batches.len(), 100);
+        assert!(batches.iter().all(|batch| batch.len() == 10));
+        assert!(
+            prepare_verify_collection_transactions(&collection_mint, &authority, &mints, 0)
+                .is_err()
+        );
+
+        let creator = Pubkey::new_unique();
+        let ix = verify_creator_instr(&mints[0], &creator);
+        assert_eq!(ix.accounts[0].pubkey, creator);
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(ix.accounts[2].pubkey, Metadata::find_pda(&mints[0]).0);
+        let ix = unverify_collection_instr(&mints[0], &collection_mint, &authority);
+        assert_eq!(
+            ix.accounts[4].pubkey,
+            Metadata::find_pda(&collection_mint).0
+        );
+    }
+}
@@ -0,0 +1,579 @@
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signer::Signer, system_instruction};
+use spl_token_2022::extension::confidential_transfer::account_info::{
+    ApplyPendingBalanceAccountInfo, TransferAccountInfo, WithdrawAccountInfo,
+};
+use spl_token_2022::extension::confidential_transfer::ciphertext_extraction::SourceDecryptHandles;
+use spl_token_2022::extension::confidential_transfer::instruction::{
+    self as confidential_instruction, CloseSplitContextStateAccounts,
+    TransferSplitContextStateAccounts,
+};
+use spl_token_2022::extension::confidential_transfer::{
+    ConfidentialTransferAccount, ConfidentialTransferMint,
+};
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_2022::proof::ProofLocation;
+use spl_token_2022::solana_zk_token_sdk::encryption::auth_encryption::{AeCiphertext, AeKey};
+use spl_token_2022::solana_zk_token_sdk::encryption::elgamal;
+use spl_token_2022::solana_zk_token_sdk::instruction::{
+    BatchedGroupedCiphertext2HandlesValidityProofData, BatchedRangeProofU128Data,
+    CiphertextCommitmentEqualityProofData, Pod, PubkeyValidityData, WithdrawData, ZkProofData,
+};
+use spl_token_2022::solana_zk_token_sdk::zk_token_elgamal::pod;
+use spl_token_2022::solana_zk_token_sdk::zk_token_proof_instruction::{
+    close_context_state, ContextStateInfo, ProofInstruction,
+};
+use spl_token_2022::solana_zk_token_sdk::zk_token_proof_program;
+use spl_token_2022::solana_zk_token_sdk::zk_token_proof_state::ProofContextState;
+use spl_token_2022::state::{Account, Mint};
+use std::mem::size_of;
+use std::num::NonZeroI8;
+
+// apply pending balance 之前最多可以收到的入账次数, 与 spl-token cli 默认值一致
+pub const MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER: u64 = 65_536;
+
+// 账户的 ElGamal 密钥 (链上密文余额) 和 AES 密钥 (owner 自己可快速解密的余额)
+pub struct ConfidentialKeys {
+    pub elgamal: elgamal::ElGamalKeypair,
+    pub aes: AeKey,
+}
+
+// 转账的三个证明分别验证到三个 context state 账户, 创建时需要这些账户签名
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransferProofAccounts {
+    pub equality: Pubkey,
+    pub ciphertext_validity: Pubkey,
+    pub range: Pubkey,
+}
+
+pub fn get_confidential_ata(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address_with_program_id(
+        owner,
+        mint,
+        &spl_token_2022::id(),
+    )
+}
+
+// 用 owner 对 token account 地址的签名确定性派生密钥, 不需要另外保存
+pub fn derive_confidential_keys(owner: &dyn Signer, mint: &Pubkey) -> Result<ConfidentialKeys> {
+    let token_account = get_confidential_ata(&owner.pubkey(), mint);
+    let elgamal = elgamal::ElGamalKeypair::new_from_signer(owner, &token_account.to_bytes())
+        .map_err(|e| anyhow!("failed to derive elgamal keypair: {}", e))?;
+    let aes = AeKey::new_from_signer(owner, &token_account.to_bytes())
+        .map_err(|e| anyhow!("failed to derive aes key: {}", e))?;
+    Ok(ConfidentialKeys { elgamal, aes })
+}
+
+fn confidential_account(account_data: &[u8]) -> Result<ConfidentialTransferAccount> {
+    let state = StateWithExtensions::<Account>::unpack(account_data)?;
+    state
+        .get_extension::<ConfidentialTransferAccount>()
+        .copied()
+        .map_err(|_| anyhow!("token account is not configured for confidential transfers"))
+}
+
+// configure_account 和公钥有效性证明, 证明较小可以放在同一笔交易的下一条指令
+pub fn configure_account_instr(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    keys: &ConfidentialKeys,
+) -> Result<Vec<Instruction>> {
+    let proof_data = PubkeyValidityData::new(&keys.elgamal)?;
+    Ok(confidential_instruction::configure_account(
+        &spl_token_2022::id(),
+        &get_confidential_ata(owner, mint),
+        mint,
+        keys.aes.encrypt(0),
+        MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+        owner,
+        &[],
+        ProofLocation::InstructionOffset(NonZeroI8::new(1).unwrap(), &proof_data),
+    )?)
+}
+
+// 创建 ata, 扩容出 ConfidentialTransferAccount, 再配置密钥, 需要 payer / owner 签名
+pub fn prepare_configure_account_instructions(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    keys: &ConfidentialKeys,
+) -> Result<Vec<Instruction>> {
+    let token_account = get_confidential_ata(owner, mint);
+    let mut instructions = vec![
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            payer,
+            owner,
+            mint,
+            &spl_token_2022::id(),
+        ),
+        spl_token_2022::instruction::reallocate(
+            &spl_token_2022::id(),
+            &token_account,
+            payer,
+            owner,
+            &[],
+            &[ExtensionType::ConfidentialTransferAccount],
+        )?,
+    ];
+    instructions.extend(configure_account_instr(owner, mint, keys)?);
+    Ok(instructions)
+}
+
+// mint 没有开启 auto approve 时, 由 confidential transfer authority 审核账户
+pub fn approve_account_instr(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction> {
+    Ok(confidential_instruction::approve_account(
+        &spl_token_2022::id(),
+        &get_confidential_ata(owner, mint),
+        mint,
+        authority,
+        &[],
+    )?)
+}
+
+// 公开余额存入 pending 密文余额, 需要 apply_pending_balance 之后才能使用
+pub fn deposit_instr(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction> {
+    Ok(confidential_instruction::deposit(
+        &spl_token_2022::id(),
+        &get_confidential_ata(owner, mint),
+        mint,
+        amount,
+        decimals,
+        owner,
+        &[],
+    )?)
+}
+
+// 解密 pending 余额并合并到可用余额, account_data 为 owner 的 token account 数据
+pub fn apply_pending_balance_instr(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    account_data: &[u8],
+    keys: &ConfidentialKeys,
+) -> Result<Instruction> {
+    let account_info = ApplyPendingBalanceAccountInfo::new(&confidential_account(account_data)?);
+    let new_decryptable_balance =
+        account_info.new_decryptable_available_balance(keys.elgamal.secret(), &keys.aes)?;
+    Ok(confidential_instruction::apply_pending_balance(
+        &spl_token_2022::id(),
+        &get_confidential_ata(owner, mint),
+        account_info.pending_balance_credit_counter(),
+        new_decryptable_balance,
+        owner,
+        &[],
+    )?)
+}
+
+pub fn prepare_apply_pending_balance_instructions(
+    client: &RpcClient,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    keys: &ConfidentialKeys,
+) -> Result<Vec<Instruction>> {
+    let account_data = client.get_account_data(&get_confidential_ata(owner, mint))?;
+    Ok(vec![apply_pending_balance_instr(
+        owner,
+        mint,
+        &account_data,
+        keys,
+    )?])
+}
+
+// 证明数据太大放不进 token 指令所在的交易, 先在 context state 账户中验证
+// 返回 (创建账户, 验证证明) 两条指令, 创建时需要 context_state_account 签名, authority 支付租金
+fn context_state_instrs<T: Pod + ZkProofData<U>, U: Pod>(
+    client: &RpcClient,
+    authority: &Pubkey,
+    context_state_account: &Pubkey,
+    proof_instruction: ProofInstruction,
+    proof_data: &T,
+) -> Result<(Instruction, Instruction)> {
+    let space = size_of::<ProofContextState<U>>();
+    let lamports = client.get_minimum_balance_for_rent_exemption(space)?;
+    let create = system_instruction::create_account(
+        authority,
+        context_state_account,
+        lamports,
+        space as u64,
+        &zk_token_proof_program::id(),
+    );
+    let verify = proof_instruction.encode_verify_proof(
+        Some(ContextStateInfo {
+            context_state_account,
+            context_state_authority: authority,
+        }),
+        proof_data,
+    );
+    Ok((create, verify))
+}
+
+// 有 TransferFee 扩展的 mint 需要带手续费的证明, 这里生成的转账 / 取回证明都不包含手续费, 链上会失败
+fn check_no_transfer_fee(mint: &StateWithExtensions<Mint>) -> Result<()> {
+    if mint.get_extension::<TransferFeeConfig>().is_ok() {
+        return Err(anyhow!(
+            "confidential transfers on mints with transfer fees are not supported"
+        ));
+    }
+    Ok(())
+}
+
+fn withdraw_proof_data(
+    account_data: &[u8],
+    amount: u64,
+    keys: &ConfidentialKeys,
+) -> Result<(WithdrawData, AeCiphertext)> {
+    let account_info = WithdrawAccountInfo::new(&confidential_account(account_data)?);
+    let proof_data = account_info.generate_proof_data(amount, &keys.elgamal, &keys.aes)?;
+    let new_decryptable_balance =
+        account_info.new_decryptable_available_balance(amount, &keys.aes)?;
+    Ok((proof_data, new_decryptable_balance))
+}
+
+// 密文可用余额取回公开余额, 依次发送: 创建 context state 账户 (需要 context_state_account 签名),
+// 验证证明, withdraw 并关闭 context state 账户退回租金
+pub fn prepare_withdraw_transactions(
+    client: &RpcClient,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    context_state_account: &Pubkey,
+    amount: u64,
+    keys: &ConfidentialKeys,
+) -> Result<Vec<Vec<Instruction>>> {
+    let token_account = get_confidential_ata(owner, mint);
+    let accounts = client.get_multiple_accounts(&[token_account, *mint])?;
+    let [Some(account), Some(mint_account)] = accounts.as_slice() else {
+        return Err(anyhow!(
+            "token account {} or mint {} not found",
+            token_account,
+            mint
+        ));
+    };
+    let mint_state = StateWithExtensions::<Mint>::unpack(&mint_account.data)?;
+    check_no_transfer_fee(&mint_state)?;
+    let decimals = mint_state.base.decimals;
+    let (proof_data, new_decryptable_balance) = withdraw_proof_data(&account.data, amount, keys)?;
+    let (create, verify) = context_state_instrs(
+        client,
+        owner,
+        context_state_account,
+        ProofInstruction::VerifyWithdraw,
+        &proof_data,
+    )?;
+    let mut withdraw = confidential_instruction::withdraw(
+        &spl_token_2022::id(),
+        &token_account,
+        mint,
+        amount,
+        decimals,
+        new_decryptable_balance,
+        owner,
+        &[],
+        ProofLocation::ContextStateAccount(context_state_account),
+    )?;
+    withdraw.push(close_context_state(
+        ContextStateInfo {
+            context_state_account,
+            context_state_authority: owner,
+        },
+        owner,
+    ));
+    Ok(vec![vec![create], vec![verify], withdraw])
+}
+
+type TransferProofData = (
+    CiphertextCommitmentEqualityProofData,
+    BatchedGroupedCiphertext2HandlesValidityProofData,
+    BatchedRangeProofU128Data,
+);
+
+// 生成拆分的转账证明, 接收方必须已配置并允许密文入账, mint 设置了 auditor 时转账金额同时对 auditor 加密
+fn transfer_proof_data(
+    source_data: &[u8],
+    destination_data: &[u8],
+    mint_data: &[u8],
+    amount: u64,
+    keys: &ConfidentialKeys,
+) -> Result<(TransferProofData, pod::AeCiphertext, SourceDecryptHandles)> {
+    let account_info = TransferAccountInfo::new(&confidential_account(source_data)?);
+    let destination = confidential_account(destination_data)?;
+    destination
+        .valid_as_destination()
+        .map_err(|e| anyhow!("destination cannot receive confidential transfers: {}", e))?;
+    let destination_pubkey: elgamal::ElGamalPubkey = destination.elgamal_pubkey.try_into()?;
+    let mint = StateWithExtensions::<Mint>::unpack(mint_data)?;
+    check_no_transfer_fee(&mint)?;
+    let config = mint
+        .get_extension::<ConfidentialTransferMint>()
+        .map_err(|_| anyhow!("mint does not support confidential transfers"))?;
+    let auditor_pubkey = Option::<pod::ElGamalPubkey>::from(config.auditor_elgamal_pubkey)
+        .map(elgamal::ElGamalPubkey::try_from)
+        .transpose()?;
+    let (equality, ciphertext_validity, range, source_decrypt_handles) = account_info
+        .generate_split_transfer_proof_data(
+            amount,
+            &keys.elgamal,
+            &keys.aes,
+            &destination_pubkey,
+            auditor_pubkey.as_ref(),
+        )?;
+    let new_decryptable_balance =
+        account_info.new_decryptable_available_balance(amount, &keys.aes)?;
+    Ok((
+        (equality, ciphertext_validity, range),
+        new_decryptable_balance.into(),
+        source_decrypt_handles,
+    ))
+}
+
+// 密文转账, 依次发送:
+// 1. 创建三个 context state 账户 (需要 proof_accounts 中三个账户签名)
+// 2. 验证 equality 和 ciphertext validity 证明
+// 3. 验证 range 证明 (单独一笔交易)
+// 4. 转账, 执行后自动关闭 context state 账户并把租金退回 owner
+// 接收方需要 apply_pending_balance 后才能使用收到的金额
+pub fn prepare_confidential_transfer_transactions(
+    client: &RpcClient,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    destination_owner: &Pubkey,
+    proof_accounts: &TransferProofAccounts,
+    amount: u64,
+    keys: &ConfidentialKeys,
+) -> Result<Vec<Vec<Instruction>>> {
+    let source = get_confidential_ata(owner, mint);
+    let destination = get_confidential_ata(destination_owner, mint);
+    let accounts = client.get_multiple_accounts(&[source, destination, *mint])?;
+    let [Some(source_account), Some(destination_account), Some(mint_account)] = accounts.as_slice()
+    else {
+        return Err(anyhow!(
+            "token account {}, {} or mint {} not found",
+            source,
+            destination,
+            mint
+        ));
+    };
+    let ((equality, ciphertext_validity, range), new_decryptable_balance, source_decrypt_handles) =
+        transfer_proof_data(
+            &source_account.data,
+            &destination_account.data,
+            &mint_account.data,
+            amount,
+            keys,
+        )?;
+    let (create_equality, verify_equality) = context_state_instrs(
+        client,
+        owner,
+        &proof_accounts.equality,
+        ProofInstruction::VerifyCiphertextCommitmentEquality,
+        &equality,
+    )?;
+    let (create_ciphertext_validity, verify_ciphertext_validity) = context_state_instrs(
+        client,
+        owner,
+        &proof_accounts.ciphertext_validity,
+        ProofInstruction::VerifyBatchedGroupedCiphertext2HandlesValidity,
+        &ciphertext_validity,
+    )?;
+    let (create_range, verify_range) = context_state_instrs(
+        client,
+        owner,
+        &proof_accounts.range,
+        ProofInstruction::VerifyBatchedRangeProofU128,
+        &range,
+    )?;
+    let transfer = confidential_instruction::transfer_with_split_proofs(
+        &spl_token_2022::id(),
+        &source,
+        mint,
+        &destination,
+        new_decryptable_balance,
+        owner,
+        TransferSplitContextStateAccounts {
+            equality_proof: &proof_accounts.equality,
+            ciphertext_validity_proof: &proof_accounts.ciphertext_validity,
+            range_proof: &proof_accounts.range,
+            authority: owner,
+            no_op_on_uninitialized_split_context_state: false,
+            close_split_context_state_accounts: Some(CloseSplitContextStateAccounts {
+                lamport_destination: owner,
+                zk_token_proof_program: &zk_token_proof_program::id(),
+            }),
+        },
+        &source_decrypt_handles,
+    )?;
+    Ok(vec![
+        vec![create_equality, create_ciphertext_validity, create_range],
+        vec![verify_equality, verify_ciphertext_validity],
+        vec![verify_range],
+        vec![transfer],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{program_pack::Pack, signature::Keypair};
+    use spl_token_2022::extension::{BaseStateWithExtensionsMut, StateWithExtensionsMut};
+    use spl_token_2022::state::AccountState;
+
+    fn configured_account(
+        mint: &Pubkey,
+        owner: &Pubkey,
+        keys: &ConfidentialKeys,
+        available: u64,
+        pending: u64,
+    ) -> Vec<u8> {
+        let space = ExtensionType::try_calculate_account_len::<Account>(&[
+            ExtensionType::ConfidentialTransferAccount,
+        ])
+        .unwrap();
+        let mut data = vec![0; space];
+        let mut state = StateWithExtensionsMut::<Account>::unpack_uninitialized(&mut data).unwrap();
+        let extension = state
+            .init_extension::<ConfidentialTransferAccount>(true)
+            .unwrap();
+        let elgamal_pubkey = keys.elgamal.pubkey();
+        extension.approved = true.into();
+        extension.elgamal_pubkey = (*elgamal_pubkey).into();
+        extension.pending_balance_lo = elgamal_pubkey.encrypt(pending).into();
+        extension.pending_balance_hi = elgamal_pubkey.encrypt(0_u64).into();
+        extension.available_balance = elgamal_pubkey.encrypt(available).into();
+        extension.decryptable_available_balance = keys.aes.encrypt(available).into();
+        extension.allow_confidential_credits = true.into();
+        extension.pending_balance_credit_counter = 1_u64.into();
+        extension.maximum_pending_balance_credit_counter =
+            MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER.into();
+        state.base = Account {
+            mint: *mint,
+            owner: *owner,
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    fn confidential_mint(transfer_fee: bool) -> Vec<u8> {
+        let mut extensions = vec![ExtensionType::ConfidentialTransferMint];
+        if transfer_fee {
+            extensions.push(ExtensionType::TransferFeeConfig);
+        }
+        let space = ExtensionType::try_calculate_account_len::<Mint>(&extensions).unwrap();
+        let mut data = vec![0; space];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let config = state
+            .init_extension::<ConfidentialTransferMint>(true)
+            .unwrap();
+        config.auto_approve_new_accounts = true.into();
+        if transfer_fee {
+            state.init_extension::<TransferFeeConfig>(true).unwrap();
+        }
+        state.base = Mint {
+            decimals: 2,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_configure_account() {
+        let (owner, mint) = (Keypair::new(), Pubkey::new_unique());
+        let keys = derive_confidential_keys(&owner, &mint).unwrap();
+        // 同一个 owner / mint 每次派生出相同的密钥
+        let derived = derive_confidential_keys(&owner, &mint).unwrap();
+        assert_eq!(keys.elgamal.pubkey(), derived.elgamal.pubkey());
+        assert_eq!(keys.aes.decrypt(&derived.aes.encrypt(42)), Some(42));
+        let other = derive_confidential_keys(&owner, &Pubkey::new_unique()).unwrap();
+        assert_ne!(keys.elgamal.pubkey(), other.elgamal.pubkey());
+
+        let instructions =
+            prepare_configure_account_instructions(&owner.pubkey(), &owner.pubkey(), &mint, &keys)
+                .unwrap();
+        assert_eq!(instructions.len(), 4);
+        let token_account = get_confidential_ata(&owner.pubkey(), &mint);
+        assert_eq!(instructions[1].accounts[0].pubkey, token_account);
+        assert_eq!(instructions[2].accounts[0].pubkey, token_account);
+        assert_eq!(instructions[3].program_id, zk_token_proof_program::id());
+
+        let ix = approve_account_instr(&owner.pubkey(), &mint, &Pubkey::new_unique()).unwrap();
+        assert!(ix.accounts[2].is_signer);
+    }
+
+    #[test]
+    fn test_confidential_balance_proofs() {
+        let (owner, receiver, mint) = (Keypair::new(), Keypair::new(), Pubkey::new_unique());
+        let keys = derive_confidential_keys(&owner, &mint).unwrap();
+        let source_data = configured_account(&mint, &owner.pubkey(), &keys, 100, 50);
+
+        // apply 后可用余额为 100 + 50, 写在指令数据末尾
+        let ix = apply_pending_balance_instr(&owner.pubkey(), &mint, &source_data, &keys).unwrap();
+        let new_balance = pod::AeCiphertext(ix.data[ix.data.len() - 36..].try_into().unwrap());
+        assert_eq!(
+            keys.aes.decrypt(&new_balance.try_into().unwrap()),
+            Some(150)
+        );
+
+        let (proof_data, new_balance) = withdraw_proof_data(&source_data, 30, &keys).unwrap();
+        assert!(proof_data.verify_proof().is_ok());
+        assert_eq!(keys.aes.decrypt(&new_balance), Some(70));
+        assert!(withdraw_proof_data(&source_data, 101, &keys).is_err());
+
+        let receiver_keys = derive_confidential_keys(&receiver, &mint).unwrap();
+        let destination_data = configured_account(&mint, &receiver.pubkey(), &receiver_keys, 0, 0);
+        let ((equality, ciphertext_validity, range), new_balance, _) = transfer_proof_data(
+            &source_data,
+            &destination_data,
+            &confidential_mint(false),
+            40,
+            &keys,
+        )
+        .unwrap();
+        assert!(equality.verify_proof().is_ok());
+        assert!(ciphertext_validity.verify_proof().is_ok());
+        assert!(range.verify_proof().is_ok());
+        assert_eq!(keys.aes.decrypt(&new_balance.try_into().unwrap()), Some(60));
+
+        // 接收方未配置 confidential transfer
+        let mut plain_data = vec![0; Account::LEN];
+        Account {
+            mint,
+            owner: receiver.pubkey(),
+            state: AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut plain_data);
+        assert!(transfer_proof_data(
+            &source_data,
+            &plain_data,
+            &confidential_mint(false),
+            40,
+            &keys
+        )
+        .is_err());
+
+        // mint 有转账手续费时需要带手续费的证明, 直接拒绝
+        let fee_mint = confidential_mint(true);
+        assert!(
+            transfer_proof_data(&source_data, &destination_data, &fee_mint, 40, &keys).is_err()
+        );
+        let fee_mint = StateWithExtensions::<Mint>::unpack(&fee_mint).unwrap();
+        assert!(check_no_transfer_fee(&fee_mint).is_err());
+        let plain_mint = confidential_mint(false);
+        let plain_mint = StateWithExtensions::<Mint>::unpack(&plain_mint).unwrap();
+        assert!(check_no_transfer_fee(&plain_mint).is_ok());
+    }
+}
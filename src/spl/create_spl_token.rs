@@ -1,15 +1,15 @@
+use anyhow::Result;
 use mpl_token_metadata::accounts::{MasterEdition, Metadata};
 use mpl_token_metadata::instructions::{CreateV1, CreateV1Builder, CreateV1InstructionArgs};
 use mpl_token_metadata::types::{PrintSupply, TokenStandard};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::transaction::Transaction;
 use solana_sdk::{instruction::Instruction, native_token::LAMPORTS_PER_SOL};
-use solana_sdk::{
-    program_pack::Pack,
-    signature::{Keypair, Signer},
-    system_instruction,
-};
-use spl_token_2022::state::Mint;
+use solana_sdk::signature::{Keypair, Signer};
+
+use super::metadata_json::{upload_token_content, MetadataUploader, TokenContent};
+use super::mint_extensions::{mint_space, prepare_create_mint_instructions, MintExtension};
+
 // concept
 // mint account: https://solana.com/docs/core/tokens#mint-account
 // token program: https://solana.com/docs/core/tokens#token-program
@@ -19,34 +19,31 @@ use spl_token_2022::state::Mint;
 // An Associated Token Account is a Token Account created with an address derived from the owner's and mint account's addresses.
 // https://spl.solana.com/token
 
+// 先把图片和描述上传到 uploader, 用返回的 json uri 作为链上 metadata 的 uri
+// extensions 为空时与普通 token 2022 mint 相同
 pub fn prepare_deploy_token_with_metadata_instructions(
     mint_account: &Keypair,
     wallet_keypair: &Keypair,
-) -> Vec<Instruction> {
+    content: &TokenContent,
+    uploader: &dyn MetadataUploader,
+    extensions: &[MintExtension],
+) -> Result<Vec<Instruction>> {
+    let uri = upload_token_content(uploader, content)?;
     let solana_client =
         solana_client::rpc_client::RpcClient::new("https://api.devnet.solana.com".to_string());
     let mint_account = mint_account.pubkey();
-    let mint_rent = solana_client
-        .get_minimum_balance_for_rent_exemption(Mint::LEN)
-        .unwrap();
-    // create empty account ( space )
-    let create_account_space_ix = system_instruction::create_account(
+    let mint_rent =
+        solana_client.get_minimum_balance_for_rent_exemption(mint_space(extensions)?)?;
+    // create empty account ( space ) + init extensions + initialize mint account (init this space)
+    let mut instructions = prepare_create_mint_instructions(
         &wallet_keypair.pubkey(),
         &mint_account,
-        mint_rent as u64,
-        Mint::LEN as u64,
-        &spl_token_2022::id(),
-    );
-
-    // initialize mint account (init this space)
-    let mint_account_init_ix = spl_token_2022::instruction::initialize_mint(
-        &spl_token_2022::id(),
-        &mint_account,
         &wallet_keypair.pubkey(),
         Some(&wallet_keypair.pubkey()),
         9,
-    )
-    .unwrap();
+        mint_rent,
+        extensions,
+    )?;
 
     // update mint account detail
     let payer = wallet_keypair.pubkey();
@@ -59,16 +56,20 @@ pub fn prepare_deploy_token_with_metadata_instructions(
             .authority(payer)
             .payer(payer)
             .update_authority(payer, true)
+            // mint 由 token 2022 创建, 不传时默认 legacy token program
+            .spl_token_program(Some(spl_token_2022::id()))
             .is_mutable(true)
             .primary_sale_happened(false)
-            .name(String::from("NewsMeMe"))
-            .uri(String::from("https://white-historical-basilisk-887.mypinata.cloud/ipfs/QmVd6xVRqg9sJQP1zkUVizZ7jah6zD7j6fSPn9F7MRjZMo"))
+            .name(content.name.clone())
+            .symbol(content.symbol.clone())
+            .uri(uri)
             .seller_fee_basis_points(500)
             .token_standard(TokenStandard::Fungible)
             .print_supply(PrintSupply::Limited(10000000000000000))
             .instruction();
 
-    vec![create_account_space_ix, mint_account_init_ix, create_ix]
+    instructions.push(create_ix);
+    Ok(instructions)
 }
 
 pub fn prepare_mint_token_instruction(
@@ -119,13 +120,14 @@ mod tests {
     use std::str::FromStr;
 
     use super::*;
-    use mpl_token_metadata::{
-        accounts::{MasterEdition, Metadata},
-        instructions::CreateV1Builder,
-        types::{PrintSupply, TokenStandard},
+    use crate::spl::metadata::{prepare_update_metadata_instructions, MetadataUpdate};
+    use crate::spl::metadata_json::MetadataJson;
+    use solana_sdk::{
+        program_pack::Pack, pubkey::Pubkey, system_instruction, transaction::Transaction,
     };
-    use solana_sdk::{pubkey::Pubkey, transaction::Transaction};
+    use spl_token_2022::state::Mint;
     use spl_token_2022::id;
+    use std::cell::RefCell;
 
     #[test]
     fn test_mint_token_instruction() {
@@ -157,6 +159,21 @@ mod tests {
             .send_and_confirm_transaction_with_spinner(&transaction)
             .unwrap();
     }
+    // 内容已经上传好, 直接返回固定的 uri, 同时记录收到的内容用于检查
+    struct PinnedUploader {
+        uri: &'static str,
+        uploads: RefCell<Vec<(Vec<u8>, String)>>,
+    }
+
+    impl MetadataUploader for PinnedUploader {
+        fn upload(&self, data: &[u8], content_type: &str) -> Result<String> {
+            self.uploads
+                .borrow_mut()
+                .push((data.to_vec(), content_type.to_string()));
+            Ok(self.uri.to_string())
+        }
+    }
+
     #[test]
     fn test_deploy_token_with_metadata() {
         let mint_account = Keypair::new();
@@ -167,9 +184,43 @@ mod tests {
             98, 170, 226, 75, 220, 140, 11, 41,
         ])
         .unwrap();
-        let instructions =
-            prepare_deploy_token_with_metadata_instructions(&mint_account, &wallet_keypair);
+        // devnet 上的 metadata 需要能被钱包 / 浏览器解析, 使用已经固定在 ipfs 上的 json
+        // 本地目录的 LocalUploader 只在 metadata_json 的离线测试中使用
+        let uploader = PinnedUploader {
+            uri: "https://white-historical-basilisk-887.mypinata.cloud/ipfs/QmVd6xVRqg9sJQP1zkUVizZ7jah6zD7j6fSPn9F7MRjZMo",
+            uploads: RefCell::new(vec![]),
+        };
+        let content = TokenContent {
+            name: String::from("NewsMeMe"),
+            symbol: String::from("NEWS"),
+            description: String::from("news meme"),
+            image: vec![0x89, b'P', b'N', b'G'],
+            image_type: String::from("image/png"),
+        };
+        let instructions = prepare_deploy_token_with_metadata_instructions(
+            &mint_account,
+            &wallet_keypair,
+            &content,
+            &uploader,
+            &[],
+        )
+        .unwrap();
         println!("instructions: {}", instructions.len());
+
+        // 先上传图片, 再上传引用图片 uri 的 json
+        let uploads = uploader.uploads.borrow();
+        assert_eq!(uploads.len(), 2);
+        assert_eq!(
+            uploads[0],
+            (content.image.clone(), content.image_type.clone())
+        );
+        assert_eq!(uploads[1].1, "application/json");
+        let json = MetadataJson::from_slice(&uploads[1].0).unwrap();
+        assert_eq!(json.name, content.name);
+        assert_eq!(json.symbol, content.symbol);
+        assert_eq!(json.description.as_deref(), Some("news meme"));
+        assert_eq!(json.image.as_deref(), Some(uploader.uri));
+
         let solana_client =
             solana_client::rpc_client::RpcClient::new("https://api.devnet.solana.com".to_string());
         let transaction: Transaction = Transaction::new_signed_with_payer(
@@ -274,29 +325,29 @@ mod tests {
         ])
         .unwrap();
         let payer = wallet_keypair.pubkey();
-        let metadata_pda = Metadata::find_pda(&mint_account);
-        let master_edition_pda = MasterEdition::find_pda(&mint_account);
-        // instruction args
-        let create_ix = CreateV1Builder::new()
-            .metadata(metadata_pda.0)
-            .master_edition(Some(master_edition_pda.0))
-            .mint(mint_account, false)
-            .authority(payer)
-            .payer(payer)
-            .update_authority(payer, true)
-            .is_mutable(true)
-            .primary_sale_happened(false)
-            .name(String::from("News"))
-            .uri(String::from("https://white-historical-basilisk-887.mypinata.cloud/ipfs/QmVd6xVRqg9sJQP1zkUVizZ7jah6zD7j6fSPn9F7MRjZMo"))
-            .seller_fee_basis_points(500)
-            .token_standard(TokenStandard::Fungible)
-            .print_supply(PrintSupply::Limited(10000000000000000))
-            .instruction();
         let solana_client =
             solana_client::rpc_client::RpcClient::new("https://api.devnet.solana.com".to_string());
+        // 只发送与链上不同的字段, 没有变化时不发交易
+        let update = MetadataUpdate {
+            name: Some(String::from("News")),
+            seller_fee_basis_points: Some(500),
+            ..Default::default()
+        };
+        let instructions = prepare_update_metadata_instructions(
+            &solana_client,
+            &mint_account,
+            &payer,
+            &payer,
+            &update,
+        )
+        .unwrap();
+        if instructions.is_empty() {
+            println!("metadata is already up to date");
+            return;
+        }
 
         let transaction: Transaction = Transaction::new_signed_with_payer(
-            &[create_ix],
+            &instructions,
             Some(&wallet_keypair.pubkey()),
             &[&wallet_keypair],
             solana_client.get_latest_blockhash().unwrap(),
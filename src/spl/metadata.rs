@@ -0,0 +1,396 @@
+use anyhow::{anyhow, Result};
+use mpl_token_metadata::accounts::{MasterEdition, Metadata};
+use mpl_token_metadata::instructions::{UpdateV1Builder, UpdateV1InstructionArgs};
+use mpl_token_metadata::types::{Collection, CollectionToggle, Creator, Data, TokenStandard};
+use mpl_token_metadata::{MAX_CREATOR_LIMIT, MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+// 链上 name / symbol / uri 按最大长度用 \0 补齐, 比较前需要去掉
+pub fn trim_padding(value: &str) -> &str {
+    value.trim_end_matches('\0')
+}
+
+pub fn fetch_metadata(client: &RpcClient, mint: &Pubkey) -> Result<Metadata> {
+    let data = client.get_account_data(&Metadata::find_pda(mint).0)?;
+    Ok(Metadata::safe_deserialize(&data)?)
+}
+
+// 需要修改的字段, None 表示保持不变
+// creators / collection 的 Some(None) 表示清空
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetadataUpdate {
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub uri: Option<String>,
+    pub seller_fee_basis_points: Option<u16>,
+    /// 新增的 creator 都是未验证状态, 原来已验证的地址保持验证
+    pub creators: Option<Option<Vec<Pubkey>>>,
+    /// creators 对应的分成比例, 合计必须为 100
+    pub creator_shares: Vec<u8>,
+    /// 设置的 collection 为未验证状态, 需要再由 collection 的 update authority 验证
+    pub collection: Option<Option<Pubkey>>,
+    /// 只能从 true 改成 false
+    pub is_mutable: Option<bool>,
+    pub new_update_authority: Option<Pubkey>,
+}
+
+fn check_data(data: &Data) -> Result<()> {
+    if data.name.len() > MAX_NAME_LENGTH {
+        return Err(anyhow!("name is longer than {} bytes", MAX_NAME_LENGTH));
+    }
+    if data.symbol.len() > MAX_SYMBOL_LENGTH {
+        return Err(anyhow!("symbol is longer than {} bytes", MAX_SYMBOL_LENGTH));
+    }
+    if data.uri.len() > MAX_URI_LENGTH {
+        return Err(anyhow!("uri is longer than {} bytes", MAX_URI_LENGTH));
+    }
+    if data.seller_fee_basis_points > 10_000 {
+        return Err(anyhow!("seller fee basis points must not exceed 10000"));
+    }
+    if let Some(creators) = &data.creators {
+        if creators.len() > MAX_CREATOR_LIMIT {
+            return Err(anyhow!(
+                "at most {} creators are allowed",
+                MAX_CREATOR_LIMIT
+            ));
+        }
+        if creators
+            .iter()
+            .map(|creator| u32::from(creator.share))
+            .sum::<u32>()
+            != 100
+        {
+            return Err(anyhow!("creator shares must add up to 100"));
+        }
+    }
+    Ok(())
+}
+
+// 和当前链上的 metadata 比较, 只发送变化的字段; 没有任何变化时返回 None
+// name / symbol / uri / seller_fee_basis_points / creators 在链上是一个整体 (Data), 任一变化时整体发送
+pub fn diff_metadata(
+    current: &Metadata,
+    update: &MetadataUpdate,
+) -> Result<Option<UpdateV1InstructionArgs>> {
+    let current_creators = current
+        .creators
+        .clone()
+        .filter(|creators| !creators.is_empty());
+    let creators = match &update.creators {
+        None => current_creators.clone(),
+        Some(None) => None,
+        Some(Some(addresses)) => {
+            if addresses.len() != update.creator_shares.len() {
+                return Err(anyhow!("every creator needs a share"));
+            }
+            let verified = |address: &Pubkey| {
+                current_creators
+                    .iter()
+                    .flatten()
+                    .any(|creator| creator.address == *address && creator.verified)
+            };
+            Some(
+                addresses
+                    .iter()
+                    .zip(&update.creator_shares)
+                    .map(|(address, share)| Creator {
+                        address: *address,
+                        verified: verified(address),
+                        share: *share,
+                    })
+                    .collect(),
+            )
+        }
+    };
+    let data = Data {
+        name: update
+            .name
+            .clone()
+            .unwrap_or_else(|| trim_padding(&current.name).to_string()),
+        symbol: update
+            .symbol
+            .clone()
+            .unwrap_or_else(|| trim_padding(&current.symbol).to_string()),
+        uri: update
+            .uri
+            .clone()
+            .unwrap_or_else(|| trim_padding(&current.uri).to_string()),
+        seller_fee_basis_points: update
+            .seller_fee_basis_points
+            .unwrap_or(current.seller_fee_basis_points),
+        creators,
+    };
+    let data_changed = data.name != trim_padding(&current.name)
+        || data.symbol != trim_padding(&current.symbol)
+        || data.uri != trim_padding(&current.uri)
+        || data.seller_fee_basis_points != current.seller_fee_basis_points
+        || data.creators != current_creators;
+
+    let collection = match update.collection {
+        Some(Some(key)) if current.collection.as_ref().map(|c| c.key) != Some(key) => {
+            CollectionToggle::Set(Collection {
+                verified: false,
+                key,
+            })
+        }
+        Some(None) if current.collection.is_some() => CollectionToggle::Clear,
+        _ => CollectionToggle::None,
+    };
+    let is_mutable = match update.is_mutable {
+        Some(true) if !current.is_mutable => {
+            return Err(anyhow!("immutable metadata cannot be made mutable"))
+        }
+        Some(is_mutable) if is_mutable != current.is_mutable => Some(is_mutable),
+        _ => None,
+    };
+    let new_update_authority = update
+        .new_update_authority
+        .filter(|authority| *authority != current.update_authority);
+
+    if !data_changed
+        && collection == CollectionToggle::None
+        && is_mutable.is_none()
+        && new_update_authority.is_none()
+    {
+        return Ok(None);
+    }
+    if !current.is_mutable {
+        return Err(anyhow!("metadata of {} is immutable", current.mint));
+    }
+    let data = if data_changed {
+        check_data(&data)?;
+        Some(data)
+    } else {
+        None
+    };
+    Ok(Some(UpdateV1InstructionArgs {
+        new_update_authority,
+        data,
+        is_mutable,
+        collection,
+        ..Default::default()
+    }))
+}
+
+// authority 为当前的 update authority, nft / pnft 需要同时传入 master edition
+pub fn update_metadata_instr(
+    mint: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    token_standard: Option<TokenStandard>,
+    args: UpdateV1InstructionArgs,
+) -> Instruction {
+    let edition = match token_standard {
+        Some(TokenStandard::NonFungible)
+        | Some(TokenStandard::ProgrammableNonFungible)
+        | Some(TokenStandard::NonFungibleEdition)
+        | Some(TokenStandard::ProgrammableNonFungibleEdition) => {
+            Some(MasterEdition::find_pda(mint).0)
+        }
+        _ => None,
+    };
+    let mut builder = UpdateV1Builder::new();
+    builder
+        .authority(*authority)
+        .mint(*mint)
+        .metadata(Metadata::find_pda(mint).0)
+        .edition(edition)
+        .payer(*payer)
+        .collection(args.collection)
+        .collection_details(args.collection_details)
+        .uses(args.uses)
+        .rule_set(args.rule_set);
+    if let Some(new_update_authority) = args.new_update_authority {
+        builder.new_update_authority(new_update_authority);
+    }
+    if let Some(data) = args.data {
+        builder.data(data);
+    }
+    if let Some(primary_sale_happened) = args.primary_sale_happened {
+        builder.primary_sale_happened(primary_sale_happened);
+    }
+    if let Some(is_mutable) = args.is_mutable {
+        builder.is_mutable(is_mutable);
+    }
+    if let Some(authorization_data) = args.authorization_data {
+        builder.authorization_data(authorization_data);
+    }
+    builder.instruction()
+}
+
+// 读取当前 metadata 并生成 UpdateV1, 没有变化时返回空列表
+pub fn prepare_update_metadata_instructions(
+    client: &RpcClient,
+    mint: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    update: &MetadataUpdate,
+) -> Result<Vec<Instruction>> {
+    let current = fetch_metadata(client, mint)?;
+    if current.update_authority != *authority {
+        return Err(anyhow!(
+            "{} is not the update authority of {}",
+            authority,
+            mint
+        ));
+    }
+    Ok(diff_metadata(&current, update)?
+        .map(|args| update_metadata_instr(mint, authority, payer, current.token_standard, args))
+        .into_iter()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpl_token_metadata::instructions::UpdateV1;
+    use mpl_token_metadata::types::Key;
+
+    fn metadata(update_authority: Pubkey) -> Metadata {
+        Metadata {
+            key: Key::MetadataV1,
+            update_authority,
+            mint: Pubkey::new_unique(),
+            name: format!("{:\0<32}", "News"),
+            symbol: format!("{:\0<10}", "NEWS"),
+            uri: format!("{:\0<200}", "https://example.com/news.json"),
+            seller_fee_basis_points: 500,
+            creators: Some(vec![Creator {
+                address: update_authority,
+                verified: true,
+                share: 100,
+            }]),
+            primary_sale_happened: false,
+            is_mutable: true,
+            edition_nonce: None,
+            token_standard: Some(TokenStandard::Fungible),
+            collection: None,
+            uses: None,
+            collection_details: None,
+            programmable_config: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_metadata() {
+        let authority = Pubkey::new_unique();
+        let current = metadata(authority);
+
+        // 与当前值相同, 不需要更新
+        let update = MetadataUpdate {
+            name: Some("News".to_string()),
+            seller_fee_basis_points: Some(500),
+            new_update_authority: Some(authority),
+            is_mutable: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(diff_metadata(&current, &update).unwrap(), None);
+
+        // 只改 symbol, 其余字段沿用当前值 (去掉 \0)
+        let update = MetadataUpdate {
+            symbol: Some("NWS".to_string()),
+            ..Default::default()
+        };
+        let args = diff_metadata(&current, &update).unwrap().unwrap();
+        let data = args.data.unwrap();
+        assert_eq!(data.name, "News");
+        assert_eq!(data.symbol, "NWS");
+        assert_eq!(data.uri, "https://example.com/news.json");
+        assert_eq!(data.creators, current.creators);
+        assert_eq!(args.collection, CollectionToggle::None);
+        assert_eq!(args.new_update_authority, None);
+
+        // 新增 creator 为未验证, 原来的保持验证
+        let other = Pubkey::new_unique();
+        let update = MetadataUpdate {
+            creators: Some(Some(vec![authority, other])),
+            creator_shares: vec![50, 50],
+            collection: Some(Some(other)),
+            is_mutable: Some(false),
+            new_update_authority: Some(other),
+            ..Default::default()
+        };
+        let args = diff_metadata(&current, &update).unwrap().unwrap();
+        let creators = args.data.unwrap().creators.unwrap();
+        assert!(creators[0].verified);
+        assert!(!creators[1].verified);
+        assert_eq!(
+            args.collection,
+            CollectionToggle::Set(Collection {
+                verified: false,
+                key: other
+            })
+        );
+        assert_eq!(args.is_mutable, Some(false));
+        assert_eq!(args.new_update_authority, Some(other));
+
+        let update = MetadataUpdate {
+            creators: Some(Some(vec![authority, other])),
+            creator_shares: vec![50, 40],
+            ..Default::default()
+        };
+        assert!(diff_metadata(&current, &update).is_err());
+        let update = MetadataUpdate {
+            name: Some("x".repeat(MAX_NAME_LENGTH + 1)),
+            ..Default::default()
+        };
+        assert!(diff_metadata(&current, &update).is_err());
+
+        let immutable = Metadata {
+            is_mutable: false,
+            ..current
+        };
+        let update = MetadataUpdate {
+            uri: Some("https://example.com/new.json".to_string()),
+            ..Default::default()
+        };
+        assert!(diff_metadata(&immutable, &update).is_err());
+    }
+
+    #[test]
+    fn test_update_metadata_instr() {
+        let authority = Pubkey::new_unique();
+        let current = metadata(authority);
+        let update = MetadataUpdate {
+            uri: Some("https://example.com/new.json".to_string()),
+            ..Default::default()
+        };
+        let args = diff_metadata(&current, &update).unwrap().unwrap();
+        let expected = |edition: Option<Pubkey>| {
+            UpdateV1 {
+                authority,
+                delegate_record: None,
+                token: None,
+                mint: current.mint,
+                metadata: Metadata::find_pda(&current.mint).0,
+                edition,
+                payer: authority,
+                system_program: solana_sdk::system_program::id(),
+                sysvar_instructions: solana_sdk::sysvar::instructions::id(),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            }
+            .instruction(args.clone())
+        };
+
+        // fungible 没有 edition
+        let ix = update_metadata_instr(
+            &current.mint,
+            &authority,
+            &authority,
+            current.token_standard,
+            args.clone(),
+        );
+        assert_eq!(ix, expected(None));
+
+        let ix = update_metadata_instr(
+            &current.mint,
+            &authority,
+            &authority,
+            Some(TokenStandard::NonFungible),
+            args.clone(),
+        );
+        assert_eq!(ix, expected(Some(MasterEdition::find_pda(&current.mint).0)));
+    }
+}
@@ -0,0 +1,269 @@
+use anyhow::{anyhow, Result};
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// metaplex 链下 json 标准: https://developers.metaplex.com/token-metadata/token-standard
+// 字段都可能缺失, 解析时按默认值处理
+// let json = MetadataJsonBuilder::default().name("News").image(uri).attribute(Attribute::new("level", 3)).build()?;
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Builder)]
+#[builder(setter(into))]
+pub struct MetadataJson {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    #[builder(default)]
+    pub symbol: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(into, strip_option), default)]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(into, strip_option), default)]
+    pub image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(into, strip_option), default)]
+    pub animation_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(into, strip_option), default)]
+    pub external_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[builder(setter(each(name = "attribute")), default)]
+    pub attributes: Vec<Attribute>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(setter(into, strip_option), default)]
+    pub properties: Option<Properties>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attribute {
+    pub trait_type: String,
+    /// 字符串或数字
+    pub value: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Properties {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<File>,
+    /// image / video / audio / vr / html
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct File {
+    pub uri: String,
+    /// mime 类型, 如 image/png
+    #[serde(rename = "type")]
+    pub file_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cdn: Option<bool>,
+}
+
+impl Attribute {
+    pub fn new(trait_type: &str, value: impl Into<serde_json::Value>) -> Self {
+        Self {
+            trait_type: trait_type.to_string(),
+            value: value.into(),
+        }
+    }
+}
+
+impl MetadataJson {
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(data)?)
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+}
+
+// 拉取链下 json 的 http 客户端, 由调用方提供实现 (reqwest / ureq / 测试用的 mock)
+pub trait HttpFetcher {
+    fn get(&self, url: &str) -> Result<Vec<u8>>;
+}
+
+pub fn fetch_metadata_json(fetcher: &dyn HttpFetcher, uri: &str) -> Result<MetadataJson> {
+    MetadataJson::from_slice(&fetcher.get(uri)?)
+}
+
+// 上传图片 / json 等链下内容, 返回可以写入链上 metadata 的 uri
+// 由调用方提供实现 (pinata / arweave / 测试用的本地目录)
+pub trait MetadataUploader {
+    fn upload(&self, data: &[u8], content_type: &str) -> Result<String>;
+}
+
+// 按内容寻址的本地目录: 文件名为内容的 sha256, 相同内容只保存一份, uri 为 base_uri/<sha256>
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalUploader {
+    dir: PathBuf,
+    base_uri: String,
+}
+
+impl LocalUploader {
+    // uri 为 file://<dir>/<sha256>
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        let dir = dir.as_ref().to_path_buf();
+        let base_uri = format!("file://{}", dir.display());
+        Self { dir, base_uri }
+    }
+
+    // 目录由其他服务 (如本地 http 服务) 对外提供时, 指定对外的 uri 前缀
+    pub fn with_base_uri(dir: impl AsRef<Path>, base_uri: &str) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            base_uri: base_uri.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl MetadataUploader for LocalUploader {
+    fn upload(&self, data: &[u8], _content_type: &str) -> Result<String> {
+        let name = solana_sdk::hash::hash(data).to_bytes();
+        let name = hex::encode(name);
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(&name);
+        if !path.exists() {
+            fs::write(&path, data)?;
+        }
+        Ok(format!("{}/{}", self.base_uri, name))
+    }
+}
+
+// 读取本地上传的内容, 方便测试中按 uri 取回
+impl HttpFetcher for LocalUploader {
+    fn get(&self, url: &str) -> Result<Vec<u8>> {
+        let name = url
+            .strip_prefix(&self.base_uri)
+            .and_then(|name| name.strip_prefix('/'))
+            .ok_or_else(|| anyhow!("{} is not uploaded by this uploader", url))?;
+        Ok(fs::read(self.dir.join(name))?)
+    }
+}
+
+// 部署代币时的链下内容, image 为原始图片数据
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TokenContent {
+    pub name: String,
+    pub symbol: String,
+    pub description: String,
+    pub image: Vec<u8>,
+    /// mime 类型, 如 image/png
+    pub image_type: String,
+}
+
+// 先上传图片, 再把图片 uri 写入 json 上传, 返回 json 的 uri
+pub fn upload_token_content(
+    uploader: &dyn MetadataUploader,
+    content: &TokenContent,
+) -> Result<String> {
+    let image = uploader.upload(&content.image, &content.image_type)?;
+    let json = MetadataJsonBuilder::default()
+        .name(content.name.as_str())
+        .symbol(content.symbol.as_str())
+        .description(content.description.as_str())
+        .image(image.as_str())
+        .properties(Properties {
+            files: vec![File {
+                uri: image.clone(),
+                file_type: content.image_type.clone(),
+                cdn: None,
+            }],
+            category: Some("image".to_string()),
+        })
+        .build()?;
+    uploader.upload(&json.to_vec()?, "application/json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metadata_json() {
+        let json = br#"{
+            "name": "News",
+            "symbol": "NEWS",
+            "description": "news meme",
+            "image": "https://example.com/news.png",
+            "attributes": [
+                {"trait_type": "level", "value": 3},
+                {"trait_type": "color", "value": "red"}
+            ],
+            "properties": {
+                "files": [{"uri": "https://example.com/news.png", "type": "image/png"}],
+                "category": "image"
+            },
+            "seller_fee_basis_points": 500
+        }"#;
+        let metadata = MetadataJson::from_slice(json).unwrap();
+        assert_eq!(metadata.name, "News");
+        assert_eq!(
+            metadata.image.as_deref(),
+            Some("https://example.com/news.png")
+        );
+        assert_eq!(metadata.attributes[0].value, serde_json::json!(3));
+        assert_eq!(metadata.attributes[1].value, serde_json::json!("red"));
+        let properties = metadata.properties.unwrap();
+        assert_eq!(properties.files[0].file_type, "image/png");
+
+        // 缺失的字段用默认值
+        let metadata = MetadataJson::from_slice(br#"{"image": "ipfs://x"}"#).unwrap();
+        assert_eq!(metadata.name, "");
+        assert!(metadata.attributes.is_empty());
+        assert!(MetadataJson::from_slice(b"not json").is_err());
+    }
+
+    #[test]
+    fn test_build_and_upload_metadata_json() {
+        let json = MetadataJsonBuilder::default()
+            .name("News")
+            .image("https://example.com/news.png")
+            .attribute(Attribute::new("level", 3))
+            .attribute(Attribute::new("color", "red"))
+            .build()
+            .unwrap();
+        assert_eq!(json.symbol, "");
+        assert_eq!(json.attributes[1].value, serde_json::json!("red"));
+        assert_eq!(
+            MetadataJson::from_slice(&json.to_vec().unwrap()).unwrap(),
+            json
+        );
+        // name 必填
+        assert!(MetadataJsonBuilder::default()
+            .symbol("NEWS")
+            .build()
+            .is_err());
+
+        let dir = std::env::temp_dir().join(format!(
+            "metadata-{}",
+            solana_sdk::pubkey::Pubkey::new_unique()
+        ));
+        let uploader = LocalUploader::new(&dir);
+        let content = TokenContent {
+            name: "News".to_string(),
+            symbol: "NEWS".to_string(),
+            description: "news meme".to_string(),
+            image: vec![0x89, b'P', b'N', b'G'],
+            image_type: "image/png".to_string(),
+        };
+        let uri = upload_token_content(&uploader, &content).unwrap();
+        // 相同内容得到相同 uri
+        assert_eq!(upload_token_content(&uploader, &content).unwrap(), uri);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        let json = fetch_metadata_json(&uploader, &uri).unwrap();
+        assert_eq!(json.description.as_deref(), Some("news meme"));
+        let image = json.image.unwrap();
+        assert_eq!(uploader.get(&image).unwrap(), content.image);
+        assert_eq!(json.properties.unwrap().files[0].uri, image);
+        assert!(uploader.get("https://example.com/news.png").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
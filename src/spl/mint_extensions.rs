@@ -0,0 +1,531 @@
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    clock::Clock, instruction::Instruction, pubkey::Pubkey, system_instruction, sysvar,
+};
+use spl_tlv_account_resolution::account::ExtraAccountMeta;
+use spl_token_2022::extension::confidential_transfer;
+use spl_token_2022::extension::default_account_state;
+use spl_token_2022::extension::interest_bearing_mint::{self, InterestBearingConfig};
+use spl_token_2022::extension::mint_close_authority::MintCloseAuthority;
+use spl_token_2022::extension::transfer_hook;
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_2022::instruction::AuthorityType;
+use spl_token_2022::solana_zk_token_sdk::zk_token_elgamal::pod::ElGamalPubkey;
+use spl_token_2022::state::{AccountState, Mint};
+use spl_transfer_hook_interface::get_extra_account_metas_address;
+
+// token 2022 mint 的可选扩展, 部署时在 initialize_mint 之前逐个初始化
+#[derive(Clone, Debug, PartialEq)]
+pub enum MintExtension {
+    /// 按年化利率 (基点) 连续复利, 只影响 ui 显示数量, 链上原始数量不变
+    InterestBearing {
+        rate_authority: Option<Pubkey>,
+        rate: i16,
+    },
+    /// 不可转账 (soulbound), 只能 mint / burn
+    NonTransferable,
+    /// 新建 token account 的默认状态, Frozen 时需要 freeze authority 逐个解冻
+    DefaultAccountState(AccountState),
+    /// 可以从任意 token account 转出或销毁的永久委托人
+    PermanentDelegate(Pubkey),
+    /// supply 为 0 时可以关闭 mint 取回租金
+    MintCloseAuthority(Pubkey),
+    /// 每次转账都会 cpi 调用 program_id 的 execute, authority 可以更换 hook 程序
+    TransferHook {
+        authority: Option<Pubkey>,
+        program_id: Pubkey,
+    },
+    /// 密文余额和转账, authority 审核账户 (auto_approve 时无需审核), auditor 可以解密所有转账金额
+    ConfidentialTransferMint {
+        authority: Option<Pubkey>,
+        auto_approve_new_accounts: bool,
+        auditor_elgamal_pubkey: Option<ElGamalPubkey>,
+    },
+}
+
+impl MintExtension {
+    pub fn extension_type(&self) -> ExtensionType {
+        match self {
+            MintExtension::InterestBearing { .. } => ExtensionType::InterestBearingConfig,
+            MintExtension::NonTransferable => ExtensionType::NonTransferable,
+            MintExtension::DefaultAccountState(_) => ExtensionType::DefaultAccountState,
+            MintExtension::PermanentDelegate(_) => ExtensionType::PermanentDelegate,
+            MintExtension::MintCloseAuthority(_) => ExtensionType::MintCloseAuthority,
+            MintExtension::TransferHook { .. } => ExtensionType::TransferHook,
+            MintExtension::ConfidentialTransferMint { .. } => {
+                ExtensionType::ConfidentialTransferMint
+            }
+        }
+    }
+
+    pub fn init_instruction(&self, mint: &Pubkey) -> Result<Instruction> {
+        let instruction = match self {
+            MintExtension::InterestBearing {
+                rate_authority,
+                rate,
+            } => interest_bearing_mint::instruction::initialize(
+                &spl_token_2022::id(),
+                mint,
+                *rate_authority,
+                *rate,
+            )?,
+            MintExtension::NonTransferable => {
+                spl_token_2022::instruction::initialize_non_transferable_mint(
+                    &spl_token_2022::id(),
+                    mint,
+                )?
+            }
+            MintExtension::DefaultAccountState(state) => {
+                default_account_state::instruction::initialize_default_account_state(
+                    &spl_token_2022::id(),
+                    mint,
+                    state,
+                )?
+            }
+            MintExtension::PermanentDelegate(delegate) => {
+                spl_token_2022::instruction::initialize_permanent_delegate(
+                    &spl_token_2022::id(),
+                    mint,
+                    delegate,
+                )?
+            }
+            MintExtension::MintCloseAuthority(close_authority) => {
+                spl_token_2022::instruction::initialize_mint_close_authority(
+                    &spl_token_2022::id(),
+                    mint,
+                    Some(close_authority),
+                )?
+            }
+            MintExtension::TransferHook {
+                authority,
+                program_id,
+            } => transfer_hook::instruction::initialize(
+                &spl_token_2022::id(),
+                mint,
+                *authority,
+                Some(*program_id),
+            )?,
+            MintExtension::ConfidentialTransferMint {
+                authority,
+                auto_approve_new_accounts,
+                auditor_elgamal_pubkey,
+            } => confidential_transfer::instruction::initialize_mint(
+                &spl_token_2022::id(),
+                mint,
+                *authority,
+                *auto_approve_new_accounts,
+                *auditor_elgamal_pubkey,
+            )?,
+        };
+        Ok(instruction)
+    }
+}
+
+// 带扩展的 mint 账户大小
+pub fn mint_space(extensions: &[MintExtension]) -> Result<usize> {
+    let extension_types: Vec<ExtensionType> = extensions
+        .iter()
+        .map(MintExtension::extension_type)
+        .collect();
+    Ok(ExtensionType::try_calculate_account_len::<Mint>(
+        &extension_types,
+    )?)
+}
+
+// create_account + 扩展初始化 + initialize_mint, lamports 为 mint_space 对应的租金
+pub fn prepare_create_mint_instructions(
+    payer: &Pubkey,
+    mint: &Pubkey,
+    mint_authority: &Pubkey,
+    freeze_authority: Option<&Pubkey>,
+    decimals: u8,
+    lamports: u64,
+    extensions: &[MintExtension],
+) -> Result<Vec<Instruction>> {
+    if freeze_authority.is_none()
+        && extensions.contains(&MintExtension::DefaultAccountState(AccountState::Frozen))
+    {
+        return Err(anyhow!("default frozen mint requires a freeze authority"));
+    }
+    let mut instructions = vec![system_instruction::create_account(
+        payer,
+        mint,
+        lamports,
+        mint_space(extensions)? as u64,
+        &spl_token_2022::id(),
+    )];
+    for extension in extensions {
+        instructions.push(extension.init_instruction(mint)?);
+    }
+    instructions.push(spl_token_2022::instruction::initialize_mint(
+        &spl_token_2022::id(),
+        mint,
+        mint_authority,
+        freeze_authority,
+        decimals,
+    )?);
+    Ok(instructions)
+}
+
+// 由 rate_authority 签名修改年化利率, 之前累计的利息按旧利率保留
+pub fn update_interest_rate_instr(
+    mint: &Pubkey,
+    rate_authority: &Pubkey,
+    rate: i16,
+) -> Result<Instruction> {
+    Ok(interest_bearing_mint::instruction::update_rate(
+        &spl_token_2022::id(),
+        mint,
+        rate_authority,
+        &[],
+        rate,
+    )?)
+}
+
+// 由 freeze authority 签名修改新建 token account 的默认状态, 已有账户不受影响
+pub fn update_default_account_state_instr(
+    mint: &Pubkey,
+    freeze_authority: &Pubkey,
+    state: AccountState,
+) -> Result<Instruction> {
+    Ok(
+        default_account_state::instruction::update_default_account_state(
+            &spl_token_2022::id(),
+            mint,
+            freeze_authority,
+            &[],
+            &state,
+        )?,
+    )
+}
+
+// 更换或移除 (None) permanent delegate, 需要当前 delegate 签名
+pub fn set_permanent_delegate_instr(
+    mint: &Pubkey,
+    current_delegate: &Pubkey,
+    new_delegate: Option<&Pubkey>,
+) -> Result<Instruction> {
+    Ok(spl_token_2022::instruction::set_authority(
+        &spl_token_2022::id(),
+        mint,
+        new_delegate,
+        AuthorityType::PermanentDelegate,
+        current_delegate,
+        &[],
+    )?)
+}
+
+// 更换或移除 (None) mint close authority, 需要当前 close authority 签名
+pub fn set_mint_close_authority_instr(
+    mint: &Pubkey,
+    current_authority: &Pubkey,
+    new_authority: Option<&Pubkey>,
+) -> Result<Instruction> {
+    Ok(spl_token_2022::instruction::set_authority(
+        &spl_token_2022::id(),
+        mint,
+        new_authority,
+        AuthorityType::CloseMint,
+        current_authority,
+        &[],
+    )?)
+}
+
+// 关闭 mint, 租金退到 destination
+pub fn close_mint_instr(
+    mint: &Pubkey,
+    close_authority: &Pubkey,
+    destination: &Pubkey,
+) -> Result<Instruction> {
+    Ok(spl_token_2022::instruction::close_account(
+        &spl_token_2022::id(),
+        mint,
+        destination,
+        close_authority,
+        &[],
+    )?)
+}
+
+// 检查 close authority 和 supply 后关闭 mint
+pub fn prepare_close_mint_instructions(
+    client: &RpcClient,
+    mint: &Pubkey,
+    close_authority: &Pubkey,
+    destination: &Pubkey,
+) -> Result<Vec<Instruction>> {
+    let data = client.get_account_data(mint)?;
+    let state = StateWithExtensions::<Mint>::unpack(&data)?;
+    let authority = state
+        .get_extension::<MintCloseAuthority>()
+        .map_err(|_| anyhow!("mint {} has no close authority", mint))?
+        .close_authority;
+    if Option::<Pubkey>::from(authority) != Some(*close_authority) {
+        return Err(anyhow!(
+            "{} is not the close authority of {}",
+            close_authority,
+            mint
+        ));
+    }
+    if state.base.supply != 0 {
+        return Err(anyhow!(
+            "mint {} still has a supply of {}",
+            mint,
+            state.base.supply
+        ));
+    }
+    Ok(vec![close_mint_instr(mint, close_authority, destination)?])
+}
+
+// 更换 (None 为移除) hook 程序, 需要 transfer hook authority 签名
+pub fn update_transfer_hook_instr(
+    mint: &Pubkey,
+    authority: &Pubkey,
+    program_id: Option<Pubkey>,
+) -> Result<Instruction> {
+    Ok(transfer_hook::instruction::update(
+        &spl_token_2022::id(),
+        mint,
+        authority,
+        &[],
+        program_id,
+    )?)
+}
+
+// 在实现了 transfer hook interface 的程序中创建 mint 的 ExtraAccountMetaList PDA
+// 转账时按这里声明的账户补全 execute 需要的账户, authority 为 mint authority
+pub fn initialize_extra_account_meta_list_instr(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    authority: &Pubkey,
+    extra_account_metas: &[ExtraAccountMeta],
+) -> Instruction {
+    spl_transfer_hook_interface::instruction::initialize_extra_account_meta_list(
+        program_id,
+        &get_extra_account_metas_address(mint, program_id),
+        mint,
+        authority,
+        extra_account_metas,
+    )
+}
+
+// 原始数量转换为 ui 数量, 有 InterestBearingConfig 时计入截至 unix_timestamp 的利息
+pub fn amount_to_ui_amount(mint_data: &[u8], amount: u64, unix_timestamp: i64) -> Result<String> {
+    let state = StateWithExtensions::<Mint>::unpack(mint_data)?;
+    let decimals = state.base.decimals;
+    match state.get_extension::<InterestBearingConfig>() {
+        Ok(config) => config
+            .amount_to_ui_amount(amount, decimals, unix_timestamp)
+            .ok_or_else(|| anyhow!("interest calculation overflow")),
+        Err(_) => Ok(spl_token_2022::amount_to_ui_amount_string_trimmed(
+            amount, decimals,
+        )),
+    }
+}
+
+// ui 数量转换回原始数量, 用于按 ui 数量转账
+pub fn ui_amount_to_amount(mint_data: &[u8], ui_amount: &str, unix_timestamp: i64) -> Result<u64> {
+    let state = StateWithExtensions::<Mint>::unpack(mint_data)?;
+    let decimals = state.base.decimals;
+    let amount = match state.get_extension::<InterestBearingConfig>() {
+        Ok(config) => config.try_ui_amount_into_amount(ui_amount, decimals, unix_timestamp)?,
+        Err(_) => spl_token_2022::try_ui_amount_into_amount(ui_amount.to_string(), decimals)?,
+    };
+    Ok(amount)
+}
+
+// 读取 mint 和链上 Clock, 按当前链上时间计算 ui 数量
+pub fn fetch_ui_amount(client: &RpcClient, mint: &Pubkey, amount: u64) -> Result<String> {
+    let accounts = client.get_multiple_accounts(&[*mint, sysvar::clock::id()])?;
+    let [Some(mint_account), Some(clock_account)] = accounts.as_slice() else {
+        return Err(anyhow!("mint {} not found", mint));
+    };
+    let clock: Clock = solana_sdk::account::from_account(clock_account)
+        .ok_or_else(|| anyhow!("invalid clock sysvar"))?;
+    amount_to_ui_amount(&mint_account.data, amount, clock.unix_timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{program_option::COption, program_pack::Pack};
+    use spl_token_2022::extension::{BaseStateWithExtensionsMut, StateWithExtensionsMut};
+
+    const SECONDS_PER_YEAR: i64 = 31_556_736;
+
+    fn interest_bearing_mint(rate: i16) -> Vec<u8> {
+        let extensions = [MintExtension::InterestBearing {
+            rate_authority: None,
+            rate,
+        }];
+        let mut data = vec![0; mint_space(&extensions).unwrap()];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let config = state.init_extension::<InterestBearingConfig>(true).unwrap();
+        config.current_rate = rate.into();
+        state.base = Mint {
+            mint_authority: COption::None,
+            supply: 0,
+            decimals: 2,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_interest_bearing_mint() {
+        let (payer, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let extensions = [MintExtension::InterestBearing {
+            rate_authority: Some(payer),
+            rate: 500,
+        }];
+        let instructions =
+            prepare_create_mint_instructions(&payer, &mint, &payer, None, 2, 1, &extensions)
+                .unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert!(mint_space(&extensions).unwrap() > mint_space(&[]).unwrap());
+        assert_eq!(instructions[1].accounts[0].pubkey, mint);
+        assert_eq!(instructions[2].program_id, spl_token_2022::id());
+
+        let ix = update_interest_rate_instr(&mint, &payer, -100).unwrap();
+        assert!(ix.accounts[1].is_signer);
+
+        // 5% 连续复利一年: 100 * e^0.05
+        let data = interest_bearing_mint(500);
+        let ui_amount = amount_to_ui_amount(&data, 10_000, SECONDS_PER_YEAR).unwrap();
+        let ui_amount: f64 = ui_amount.parse().unwrap();
+        assert!((ui_amount - 100.0 * 0.05_f64.exp()).abs() < 1e-9);
+        assert_eq!(
+            ui_amount_to_amount(&data, &ui_amount.to_string(), SECONDS_PER_YEAR).unwrap(),
+            10_000
+        );
+        assert_eq!(amount_to_ui_amount(&data, 10_000, 0).unwrap(), "100");
+
+        // 没有扩展时按 decimals 换算
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            decimals: 2,
+            is_initialized: true,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        assert_eq!(
+            amount_to_ui_amount(&data, 12_345, SECONDS_PER_YEAR).unwrap(),
+            "123.45"
+        );
+        assert_eq!(ui_amount_to_amount(&data, "1.5", 0).unwrap(), 150);
+    }
+
+    #[test]
+    fn test_non_transferable_and_default_frozen_mint() {
+        let (payer, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let extensions = [
+            MintExtension::NonTransferable,
+            MintExtension::DefaultAccountState(AccountState::Frozen),
+        ];
+        assert!(
+            prepare_create_mint_instructions(&payer, &mint, &payer, None, 0, 1, &extensions)
+                .is_err()
+        );
+        let instructions = prepare_create_mint_instructions(
+            &payer,
+            &mint,
+            &payer,
+            Some(&payer),
+            0,
+            1,
+            &extensions,
+        )
+        .unwrap();
+        assert_eq!(instructions.len(), 4);
+        assert_eq!(
+            instructions[1],
+            spl_token_2022::instruction::initialize_non_transferable_mint(
+                &spl_token_2022::id(),
+                &mint
+            )
+            .unwrap()
+        );
+
+        let ix =
+            update_default_account_state_instr(&mint, &payer, AccountState::Initialized).unwrap();
+        assert_eq!(ix.accounts[1].pubkey, payer);
+        assert!(ix.accounts[1].is_signer);
+    }
+
+    #[test]
+    fn test_permanent_delegate_and_close_authority() {
+        let (payer, mint, delegate) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let extensions = [
+            MintExtension::PermanentDelegate(delegate),
+            MintExtension::MintCloseAuthority(payer),
+        ];
+        let instructions =
+            prepare_create_mint_instructions(&payer, &mint, &payer, None, 6, 1, &extensions)
+                .unwrap();
+        assert_eq!(instructions.len(), 4);
+        assert_eq!(
+            instructions[1],
+            spl_token_2022::instruction::initialize_permanent_delegate(
+                &spl_token_2022::id(),
+                &mint,
+                &delegate
+            )
+            .unwrap()
+        );
+
+        let ix = set_permanent_delegate_instr(&mint, &delegate, None).unwrap();
+        assert_eq!(ix.accounts[1].pubkey, delegate);
+        assert!(ix.accounts[1].is_signer);
+        let ix = close_mint_instr(&mint, &payer, &payer).unwrap();
+        assert_eq!(ix.accounts[0].pubkey, mint);
+        assert!(ix.accounts[2].is_signer);
+    }
+
+    #[test]
+    fn test_transfer_hook_mint() {
+        let (payer, mint, hook_program) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let extensions = [MintExtension::TransferHook {
+            authority: Some(payer),
+            program_id: hook_program,
+        }];
+        let instructions =
+            prepare_create_mint_instructions(&payer, &mint, &payer, None, 6, 1, &extensions)
+                .unwrap();
+        assert_eq!(
+            instructions[1],
+            transfer_hook::instruction::initialize(
+                &spl_token_2022::id(),
+                &mint,
+                Some(payer),
+                Some(hook_program)
+            )
+            .unwrap()
+        );
+        let ix = update_transfer_hook_instr(&mint, &payer, None).unwrap();
+        assert!(ix.accounts[1].is_signer);
+
+        let extra = Pubkey::new_unique();
+        let ix = initialize_extra_account_meta_list_instr(
+            &hook_program,
+            &mint,
+            &payer,
+            &[ExtraAccountMeta::new_with_pubkey(&extra, false, false).unwrap()],
+        );
+        assert_eq!(ix.program_id, hook_program);
+        assert_eq!(
+            ix.accounts[0].pubkey,
+            get_extra_account_metas_address(&mint, &hook_program)
+        );
+    }
+}
@@ -1,6 +1,15 @@
+pub mod account_extensions;
+pub mod burn;
+pub mod collection;
+pub mod confidential_transfer;
 pub mod create_spl_token;
 pub mod freeze;
+pub mod metadata;
+pub mod metadata_json;
+pub mod mint_extensions;
 pub mod mint_to;
+pub mod nft;
+pub mod token_info;
 pub mod transfer_to;
 pub mod unfreeze;
 pub mod transfer_sol;
@@ -14,7 +23,7 @@ mod tests {
 
     use crate::spl::mint_to::process_mint_to;
 
-    use super::transfer_to::process_transfer_to;
+    use super::transfer_to::prepare_transfer_to_instructions;
 
     #[test]
     fn test_mint_to() {
@@ -62,12 +71,19 @@ mod tests {
         let receiver_account =
             Pubkey::from_str("FNPYLsgYpJDUuDCiJmwuPSo2eKend71n8kp4cZBendfm").unwrap();
         let amount = 22 * 10_u64.pow(9);
-        let instruction = process_transfer_to(&signer, mint_account, receiver_account, amount);
 
         let solana_client =
             solana_client::rpc_client::RpcClient::new("https://api.devnet.solana.com".to_string());
+        let instructions = prepare_transfer_to_instructions(
+            &solana_client,
+            &signer,
+            mint_account,
+            receiver_account,
+            amount,
+        )
+        .unwrap();
         let transaction: Transaction = Transaction::new_signed_with_payer(
-            &[instruction],
+            &instructions,
             Some(&signer.pubkey()),
             &[&signer],
             solana_client.get_latest_blockhash().unwrap(),
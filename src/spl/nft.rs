@@ -0,0 +1,477 @@
+use anyhow::{anyhow, Result};
+use mpl_token_metadata::accounts::{
+    EditionMarker, EditionMarkerV2, MasterEdition, Metadata, TokenRecord,
+};
+use mpl_token_metadata::instructions::{
+    CreateV1Builder, MintV1Builder, PrintV1Builder, TransferV1Builder,
+};
+use mpl_token_metadata::types::{
+    Collection, CollectionDetails, Creator, Key, PrintSupply, ProgrammableConfig, TokenStandard,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use std::str::FromStr;
+
+use super::metadata::fetch_metadata;
+use crate::utils::SPL_TOKEN_PROGRAM_ID;
+
+// pnft 的转账规则由 token auth rules 程序校验
+pub const MPL_TOKEN_AUTH_RULES_PROGRAM_ID: &str = "auth9SigNpDKz4sJJ1DfCTuZrZNSAgh9sFD3rboVmgg";
+// 普通 nft 每个 edition marker 账户记录 248 个编号
+pub const EDITION_MARKER_BIT_SIZE: u64 = 248;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NftArgs {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub seller_fee_basis_points: u16,
+    pub creators: Option<Vec<Creator>>,
+    pub is_mutable: bool,
+    /// true 时为 pnft (ProgrammableNonFungible), 否则为普通 nft
+    pub programmable: bool,
+    /// 只对 pnft 有效, None 表示不限制转账
+    pub rule_set: Option<Pubkey>,
+    /// master edition 可以打印的份数, Zero 表示不能打印
+    pub print_supply: PrintSupply,
+    /// 所属 collection 的 mint, 创建时为未验证状态, 需要 collection 的 update authority 验证
+    pub collection: Option<Pubkey>,
+    /// true 时创建的是 collection nft 本身 (带 collection_details)
+    pub is_collection: bool,
+}
+
+impl NftArgs {
+    pub fn new(name: &str, symbol: &str, uri: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            symbol: symbol.to_string(),
+            uri: uri.to_string(),
+            seller_fee_basis_points: 0,
+            creators: None,
+            is_mutable: true,
+            programmable: false,
+            rule_set: None,
+            print_supply: PrintSupply::Zero,
+            collection: None,
+            is_collection: false,
+        }
+    }
+
+    pub fn token_standard(&self) -> TokenStandard {
+        if self.programmable {
+            TokenStandard::ProgrammableNonFungible
+        } else {
+            TokenStandard::NonFungible
+        }
+    }
+}
+
+fn is_programmable(token_standard: Option<TokenStandard>) -> bool {
+    matches!(
+        token_standard,
+        Some(TokenStandard::ProgrammableNonFungible)
+            | Some(TokenStandard::ProgrammableNonFungibleEdition)
+    )
+}
+
+fn rule_set_of(metadata: &Metadata) -> Option<Pubkey> {
+    match &metadata.programmable_config {
+        Some(ProgrammableConfig::V1 { rule_set }) => *rule_set,
+        None => None,
+    }
+}
+
+fn auth_rules_program(rule_set: Option<Pubkey>) -> Result<Option<Pubkey>> {
+    rule_set
+        .map(|_| Pubkey::from_str(MPL_TOKEN_AUTH_RULES_PROGRAM_ID))
+        .transpose()
+        .map_err(Into::into)
+}
+
+pub fn get_nft_ata(owner: &Pubkey, mint: &Pubkey) -> Result<Pubkey> {
+    Ok(
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            owner,
+            mint,
+            &Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?,
+        ),
+    )
+}
+
+// pnft 的每个 token account 都有一个 token record 记录状态 (锁定 / 委托)
+pub fn get_token_record_address(mint: &Pubkey, token_account: &Pubkey) -> Pubkey {
+    TokenRecord::find_pda(mint, token_account).0
+}
+
+// 打印第 edition_number 份时需要的 edition marker, pnft 使用 v2 (每个 master 一个)
+pub fn get_edition_marker_address(
+    master_mint: &Pubkey,
+    edition_number: u64,
+    programmable: bool,
+) -> Pubkey {
+    if programmable {
+        EditionMarkerV2::find_pda(master_mint).0
+    } else {
+        EditionMarker::find_pda(
+            master_mint,
+            &(edition_number / EDITION_MARKER_BIT_SIZE).to_string(),
+        )
+        .0
+    }
+}
+
+pub fn fetch_master_edition(client: &RpcClient, mint: &Pubkey) -> Result<MasterEdition> {
+    let data = client.get_account_data(&MasterEdition::find_pda(mint).0)?;
+    let master_edition = MasterEdition::from_bytes(&data)?;
+    if master_edition.key != Key::MasterEditionV2 {
+        return Err(anyhow!("{} is not a master edition", mint));
+    }
+    Ok(master_edition)
+}
+
+// CreateV1: 创建 mint (0 位小数, mint 需要签名) + metadata + master edition
+// authority 同时作为 mint authority 和 update authority
+pub fn create_nft_instr(
+    mint: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    args: &NftArgs,
+) -> Result<Instruction> {
+    let mut builder = CreateV1Builder::new();
+    builder
+        .metadata(Metadata::find_pda(mint).0)
+        .master_edition(Some(MasterEdition::find_pda(mint).0))
+        .mint(*mint, true)
+        .authority(*authority)
+        .payer(*payer)
+        .update_authority(*authority, true)
+        .spl_token_program(Some(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?))
+        .name(args.name.clone())
+        .symbol(args.symbol.clone())
+        .uri(args.uri.clone())
+        .seller_fee_basis_points(args.seller_fee_basis_points)
+        .is_mutable(args.is_mutable)
+        .primary_sale_happened(false)
+        .token_standard(args.token_standard())
+        .decimals(0)
+        .print_supply(args.print_supply.clone());
+    if let Some(creators) = &args.creators {
+        builder.creators(creators.clone());
+    }
+    if let (true, Some(rule_set)) = (args.programmable, args.rule_set) {
+        builder.rule_set(rule_set);
+    }
+    if let Some(collection) = args.collection {
+        builder.collection(Collection {
+            verified: false,
+            key: collection,
+        });
+    }
+    if args.is_collection {
+        builder.collection_details(CollectionDetails::V1 { size: 0 });
+    }
+    Ok(builder.instruction())
+}
+
+// MintV1: 铸造唯一的 1 个到 owner 的 ata (不存在时由程序创建), pnft 同时创建 token record
+pub fn mint_nft_instr(
+    mint: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    payer: &Pubkey,
+    programmable: bool,
+    rule_set: Option<Pubkey>,
+) -> Result<Instruction> {
+    let token = get_nft_ata(owner, mint)?;
+    let rule_set = rule_set.filter(|_| programmable);
+    Ok(MintV1Builder::new()
+        .token(token)
+        .token_owner(Some(*owner))
+        .metadata(Metadata::find_pda(mint).0)
+        .master_edition(Some(MasterEdition::find_pda(mint).0))
+        .token_record(programmable.then(|| get_token_record_address(mint, &token)))
+        .mint(*mint)
+        .authority(*authority)
+        .payer(*payer)
+        .spl_token_program(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?)
+        .authorization_rules_program(auth_rules_program(rule_set)?)
+        .authorization_rules(rule_set)
+        .amount(1)
+        .instruction())
+}
+
+// 创建并铸造一个 nft / pnft 给 owner, 需要 mint / authority / payer 签名
+pub fn prepare_create_nft_instructions(
+    mint: &Pubkey,
+    authority: &Pubkey,
+    owner: &Pubkey,
+    payer: &Pubkey,
+    args: &NftArgs,
+) -> Result<Vec<Instruction>> {
+    if args.rule_set.is_some() && !args.programmable {
+        return Err(anyhow!("rule set is only supported for programmable nfts"));
+    }
+    Ok(vec![
+        create_nft_instr(mint, authority, payer, args)?,
+        mint_nft_instr(
+            mint,
+            authority,
+            owner,
+            payer,
+            args.programmable,
+            args.rule_set,
+        )?,
+    ])
+}
+
+// PrintV1: 由 master 的持有人从 master edition 打印第 edition_number 份到 edition_owner
+// edition_mint 需要签名, 由程序创建
+#[allow(clippy::too_many_arguments)]
+pub fn print_edition_instr(
+    master_mint: &Pubkey,
+    master_owner: &Pubkey,
+    update_authority: &Pubkey,
+    edition_mint: &Pubkey,
+    edition_owner: &Pubkey,
+    payer: &Pubkey,
+    edition_number: u64,
+    programmable: bool,
+) -> Result<Instruction> {
+    let edition_token_account = get_nft_ata(edition_owner, edition_mint)?;
+    Ok(PrintV1Builder::new()
+        .edition_metadata(Metadata::find_pda(edition_mint).0)
+        .edition(MasterEdition::find_pda(edition_mint).0)
+        .edition_mint(*edition_mint, true)
+        .edition_token_account_owner(*edition_owner)
+        .edition_token_account(edition_token_account)
+        .edition_mint_authority(*master_owner)
+        .edition_token_record(
+            programmable.then(|| get_token_record_address(edition_mint, &edition_token_account)),
+        )
+        .master_edition(MasterEdition::find_pda(master_mint).0)
+        .edition_marker_pda(get_edition_marker_address(
+            master_mint,
+            edition_number,
+            programmable,
+        ))
+        .payer(*payer)
+        .master_token_account_owner(*master_owner)
+        .master_token_account(get_nft_ata(master_owner, master_mint)?)
+        .master_metadata(Metadata::find_pda(master_mint).0)
+        .update_authority(*update_authority)
+        .spl_token_program(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?)
+        .edition_number(edition_number)
+        .instruction())
+}
+
+// 读取 master edition 的已打印数量, 打印下一份; 超过 max_supply 时报错
+pub fn prepare_print_edition_instructions(
+    client: &RpcClient,
+    master_mint: &Pubkey,
+    master_owner: &Pubkey,
+    edition_mint: &Pubkey,
+    edition_owner: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Vec<Instruction>> {
+    let metadata = fetch_metadata(client, master_mint)?;
+    let master_edition = fetch_master_edition(client, master_mint)?;
+    let edition_number = master_edition.supply + 1;
+    if let Some(max_supply) = master_edition.max_supply {
+        if edition_number > max_supply {
+            return Err(anyhow!(
+                "all {} editions of {} have been printed",
+                max_supply,
+                master_mint
+            ));
+        }
+    }
+    Ok(vec![print_edition_instr(
+        master_mint,
+        master_owner,
+        &metadata.update_authority,
+        edition_mint,
+        edition_owner,
+        payer,
+        edition_number,
+        is_programmable(metadata.token_standard),
+    )?])
+}
+
+// TransferV1: pnft 需要两边的 token record 以及 rule set, 目标 ata 不存在时由程序创建
+pub fn transfer_nft_instr(
+    mint: &Pubkey,
+    owner: &Pubkey,
+    destination_owner: &Pubkey,
+    payer: &Pubkey,
+    token_standard: Option<TokenStandard>,
+    rule_set: Option<Pubkey>,
+) -> Result<Instruction> {
+    let programmable = is_programmable(token_standard);
+    let token = get_nft_ata(owner, mint)?;
+    let destination_token = get_nft_ata(destination_owner, mint)?;
+    let rule_set = rule_set.filter(|_| programmable);
+    Ok(TransferV1Builder::new()
+        .token(token)
+        .token_owner(*owner)
+        .destination_token(destination_token)
+        .destination_owner(*destination_owner)
+        .mint(*mint)
+        .metadata(Metadata::find_pda(mint).0)
+        .edition(Some(MasterEdition::find_pda(mint).0))
+        .token_record(programmable.then(|| get_token_record_address(mint, &token)))
+        .destination_token_record(
+            programmable.then(|| get_token_record_address(mint, &destination_token)),
+        )
+        .authority(*owner)
+        .payer(*payer)
+        .spl_token_program(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?)
+        .authorization_rules_program(auth_rules_program(rule_set)?)
+        .authorization_rules(rule_set)
+        .amount(1)
+        .instruction())
+}
+
+// 按链上 metadata 的 token standard / rule set 生成转账指令
+pub fn prepare_transfer_nft_instructions(
+    client: &RpcClient,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    destination_owner: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Vec<Instruction>> {
+    let metadata = fetch_metadata(client, mint)?;
+    Ok(vec![transfer_nft_instr(
+        mint,
+        owner,
+        destination_owner,
+        payer,
+        metadata.token_standard,
+        rule_set_of(&metadata),
+    )?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_nft_instructions() {
+        let (mint, authority, owner) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let args = NftArgs::new("News #1", "NEWS", "https://example.com/1.json");
+        let instructions =
+            prepare_create_nft_instructions(&mint, &authority, &owner, &authority, &args).unwrap();
+        assert_eq!(instructions.len(), 2);
+        let create = &instructions[0];
+        assert_eq!(create.program_id, mpl_token_metadata::ID);
+        assert_eq!(create.accounts[1].pubkey, MasterEdition::find_pda(&mint).0);
+        assert_eq!(create.accounts[2].pubkey, mint);
+        assert!(create.accounts[2].is_signer);
+        let mint_ix = &instructions[1];
+        assert_eq!(
+            mint_ix.accounts[0].pubkey,
+            get_nft_ata(&owner, &mint).unwrap()
+        );
+        // 普通 nft 没有 token record, 可选账户用程序 id 占位
+        assert_eq!(mint_ix.accounts[4].pubkey, mpl_token_metadata::ID);
+
+        let rule_set = Pubkey::new_unique();
+        let mut args = NftArgs {
+            rule_set: Some(rule_set),
+            ..args
+        };
+        assert!(
+            prepare_create_nft_instructions(&mint, &authority, &owner, &authority, &args).is_err()
+        );
+        args.programmable = true;
+        let instructions =
+            prepare_create_nft_instructions(&mint, &authority, &owner, &authority, &args).unwrap();
+        let mint_ix = &instructions[1];
+        let token = get_nft_ata(&owner, &mint).unwrap();
+        assert_eq!(
+            mint_ix.accounts[4].pubkey,
+            get_token_record_address(&mint, &token)
+        );
+        assert_eq!(
+            mint_ix.accounts[13].pubkey,
+            Pubkey::from_str(MPL_TOKEN_AUTH_RULES_PROGRAM_ID).unwrap()
+        );
+        assert_eq!(mint_ix.accounts[14].pubkey, rule_set);
+    }
+
+    #[test]
+    fn test_print_and_transfer_instructions() {
+        let (master_mint, owner, edition_mint, receiver) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ix = print_edition_instr(
+            &master_mint,
+            &owner,
+            &owner,
+            &edition_mint,
+            &receiver,
+            &owner,
+            250,
+            false,
+        )
+        .unwrap();
+        // 第 250 份在第 2 个 (编号 1) marker 中
+        assert_eq!(
+            ix.accounts[8].pubkey,
+            EditionMarker::find_pda(&master_mint, "1").0
+        );
+        assert_eq!(ix.accounts[6].pubkey, mpl_token_metadata::ID);
+        assert!(ix.accounts[2].is_signer);
+
+        let ix = print_edition_instr(
+            &master_mint,
+            &owner,
+            &owner,
+            &edition_mint,
+            &receiver,
+            &owner,
+            250,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            ix.accounts[8].pubkey,
+            EditionMarkerV2::find_pda(&master_mint).0
+        );
+
+        let ix = transfer_nft_instr(
+            &master_mint,
+            &owner,
+            &receiver,
+            &owner,
+            Some(TokenStandard::ProgrammableNonFungible),
+            None,
+        )
+        .unwrap();
+        let destination_token = get_nft_ata(&receiver, &master_mint).unwrap();
+        assert_eq!(ix.accounts[2].pubkey, destination_token);
+        assert_eq!(
+            ix.accounts[8].pubkey,
+            get_token_record_address(&master_mint, &destination_token)
+        );
+        assert_eq!(ix.accounts[15].pubkey, mpl_token_metadata::ID);
+
+        let ix = transfer_nft_instr(
+            &master_mint,
+            &owner,
+            &receiver,
+            &owner,
+            Some(TokenStandard::NonFungible),
+            Some(Pubkey::new_unique()),
+        )
+        .unwrap();
+        assert_eq!(ix.accounts[7].pubkey, mpl_token_metadata::ID);
+        assert_eq!(ix.accounts[16].pubkey, mpl_token_metadata::ID);
+    }
+}
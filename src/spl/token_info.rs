@@ -0,0 +1,299 @@
+use anyhow::{anyhow, Result};
+use mpl_token_metadata::accounts::Metadata;
+use mpl_token_metadata::types::{Collection, Creator};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{account::Account, program_option::COption, pubkey::Pubkey};
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_2022::state::Mint;
+use spl_token_metadata_interface::state::TokenMetadata;
+use std::str::FromStr;
+
+use super::metadata::trim_padding;
+use super::metadata_json::{fetch_metadata_json, HttpFetcher, MetadataJson};
+use crate::utils::SPL_TOKEN_PROGRAM_ID;
+
+// 链上 metadata 的来源: metaplex metadata 账户, 或 token 2022 mint 内嵌的 TokenMetadata 扩展
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenMetadataSource {
+    Metaplex(Metadata),
+    Token2022(TokenMetadata),
+}
+
+impl TokenMetadataSource {
+    pub fn name(&self) -> &str {
+        match self {
+            TokenMetadataSource::Metaplex(metadata) => trim_padding(&metadata.name),
+            TokenMetadataSource::Token2022(metadata) => &metadata.name,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            TokenMetadataSource::Metaplex(metadata) => trim_padding(&metadata.symbol),
+            TokenMetadataSource::Token2022(metadata) => &metadata.symbol,
+        }
+    }
+
+    pub fn uri(&self) -> &str {
+        match self {
+            TokenMetadataSource::Metaplex(metadata) => trim_padding(&metadata.uri),
+            TokenMetadataSource::Token2022(metadata) => &metadata.uri,
+        }
+    }
+
+    pub fn update_authority(&self) -> Option<Pubkey> {
+        match self {
+            TokenMetadataSource::Metaplex(metadata) => Some(metadata.update_authority),
+            TokenMetadataSource::Token2022(metadata) => {
+                Option::<Pubkey>::from(metadata.update_authority)
+            }
+        }
+    }
+
+    // token 2022 内嵌 metadata 没有 creators / collection
+    pub fn creators(&self) -> &[Creator] {
+        match self {
+            TokenMetadataSource::Metaplex(metadata) => metadata.creators.as_deref().unwrap_or(&[]),
+            TokenMetadataSource::Token2022(_) => &[],
+        }
+    }
+
+    pub fn collection(&self) -> Option<&Collection> {
+        match self {
+            TokenMetadataSource::Metaplex(metadata) => metadata.collection.as_ref(),
+            TokenMetadataSource::Token2022(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenInfo {
+    pub mint: Pubkey,
+    /// legacy token program 或 token 2022
+    pub token_program: Pubkey,
+    pub decimals: u8,
+    pub supply: u64,
+    pub mint_authority: Option<Pubkey>,
+    pub freeze_authority: Option<Pubkey>,
+    /// legacy mint 为空
+    pub extensions: Vec<ExtensionType>,
+    /// 两种都有时优先 metaplex
+    pub metadata: Option<TokenMetadataSource>,
+    /// 没有传入 fetcher / 没有 uri 时为 None
+    pub off_chain: Option<MetadataJson>,
+    /// 拉取或解析链下 json 失败的原因, 不影响链上信息的返回
+    pub off_chain_error: Option<String>,
+}
+
+fn coption_to_option(value: COption<Pubkey>) -> Option<Pubkey> {
+    match value {
+        COption::Some(value) => Some(value),
+        COption::None => None,
+    }
+}
+
+impl TokenInfo {
+    // 从 mint 账户和 (可选的) metaplex metadata 账户解析, 不包含链下 json
+    pub fn from_accounts(
+        mint: &Pubkey,
+        mint_account: &Account,
+        metadata_account: Option<&Account>,
+    ) -> Result<Self> {
+        let token_program = mint_account.owner;
+        if token_program != Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?
+            && token_program != spl_token_2022::id()
+        {
+            return Err(anyhow!("{} is not a token mint", mint));
+        }
+        let state = StateWithExtensions::<Mint>::unpack(&mint_account.data)?;
+        let extensions = state.get_extension_types()?;
+        let metaplex = metadata_account
+            .filter(|account| account.owner == mpl_token_metadata::ID)
+            .and_then(|account| Metadata::safe_deserialize(&account.data).ok())
+            .map(TokenMetadataSource::Metaplex);
+        let metadata = metaplex.or_else(|| {
+            state
+                .get_variable_len_extension::<TokenMetadata>()
+                .ok()
+                .map(TokenMetadataSource::Token2022)
+        });
+        Ok(Self {
+            mint: *mint,
+            token_program,
+            decimals: state.base.decimals,
+            supply: state.base.supply,
+            mint_authority: coption_to_option(state.base.mint_authority),
+            freeze_authority: coption_to_option(state.base.freeze_authority),
+            extensions,
+            metadata,
+            off_chain: None,
+            off_chain_error: None,
+        })
+    }
+
+    // 按 metadata 中的 uri 拉取链下 json
+    pub fn load_off_chain(&mut self, fetcher: &dyn HttpFetcher) {
+        let Some(uri) = self
+            .metadata
+            .as_ref()
+            .map(|metadata| metadata.uri().to_string())
+        else {
+            return;
+        };
+        if uri.is_empty() {
+            return;
+        }
+        match fetch_metadata_json(fetcher, &uri) {
+            Ok(json) => self.off_chain = Some(json),
+            Err(e) => self.off_chain_error = Some(format!("{}: {}", uri, e)),
+        }
+    }
+}
+
+// 一次 rpc 读取 mint 和 metaplex metadata, 传入 fetcher 时再拉取链下 json
+pub fn get_token_info(
+    client: &RpcClient,
+    mint: &Pubkey,
+    fetcher: Option<&dyn HttpFetcher>,
+) -> Result<TokenInfo> {
+    let accounts = client.get_multiple_accounts(&[*mint, Metadata::find_pda(mint).0])?;
+    let [mint_account, metadata_account] = accounts.as_slice() else {
+        return Err(anyhow!("unexpected account count"));
+    };
+    let mint_account = mint_account
+        .as_ref()
+        .ok_or_else(|| anyhow!("mint {} not found", mint))?;
+    let mut info = TokenInfo::from_accounts(mint, mint_account, metadata_account.as_ref())?;
+    if let Some(fetcher) = fetcher {
+        info.load_off_chain(fetcher);
+    }
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::program_pack::Pack;
+    use spl_token_2022::extension::metadata_pointer::MetadataPointer;
+    use spl_token_2022::extension::{BaseStateWithExtensionsMut, StateWithExtensionsMut};
+    use std::collections::HashMap;
+
+    struct MockFetcher(HashMap<String, Vec<u8>>);
+
+    impl HttpFetcher for MockFetcher {
+        fn get(&self, url: &str) -> Result<Vec<u8>> {
+            self.0
+                .get(url)
+                .cloned()
+                .ok_or_else(|| anyhow!("404 {}", url))
+        }
+    }
+
+    fn mint_account(owner: Pubkey, data: Vec<u8>) -> Account {
+        Account {
+            lamports: 1,
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    // token 2022 mint: MetadataPointer 指向自己 + 内嵌 TokenMetadata
+    fn token_2022_mint(mint: Pubkey, authority: Pubkey, uri: &str) -> Vec<u8> {
+        let metadata = TokenMetadata {
+            update_authority: Some(authority).try_into().unwrap(),
+            mint,
+            name: "News".to_string(),
+            symbol: "NEWS".to_string(),
+            uri: uri.to_string(),
+            additional_metadata: vec![("level".to_string(), "3".to_string())],
+        };
+        let space =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::MetadataPointer])
+                .unwrap()
+                + metadata.tlv_size_of().unwrap();
+        let mut data = vec![0; space];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let pointer = state.init_extension::<MetadataPointer>(true).unwrap();
+        pointer.metadata_address = Some(mint).try_into().unwrap();
+        state.init_variable_len_extension(&metadata, false).unwrap();
+        state.base = Mint {
+            mint_authority: COption::Some(authority),
+            supply: 1_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_legacy_mint_info() {
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            mint_authority: COption::None,
+            supply: 42,
+            decimals: 9,
+            is_initialized: true,
+            freeze_authority: COption::Some(authority),
+        }
+        .pack_into_slice(&mut data);
+        let account = mint_account(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(), data);
+        let info = TokenInfo::from_accounts(&mint, &account, None).unwrap();
+        assert_eq!(info.decimals, 9);
+        assert_eq!(info.supply, 42);
+        assert_eq!(info.mint_authority, None);
+        assert_eq!(info.freeze_authority, Some(authority));
+        assert!(info.extensions.is_empty());
+        assert_eq!(info.metadata, None);
+
+        let account = mint_account(Pubkey::new_unique(), account.data);
+        assert!(TokenInfo::from_accounts(&mint, &account, None).is_err());
+    }
+
+    #[test]
+    fn test_token_2022_metadata_info() {
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let uri = "https://example.com/news.json";
+        let account = mint_account(spl_token_2022::id(), token_2022_mint(mint, authority, uri));
+        let mut info = TokenInfo::from_accounts(&mint, &account, None).unwrap();
+        assert_eq!(info.token_program, spl_token_2022::id());
+        assert_eq!(info.decimals, 6);
+        assert_eq!(info.mint_authority, Some(authority));
+        assert_eq!(
+            info.extensions,
+            vec![ExtensionType::MetadataPointer, ExtensionType::TokenMetadata]
+        );
+        let metadata = info.metadata.as_ref().unwrap();
+        assert_eq!(metadata.name(), "News");
+        assert_eq!(metadata.uri(), uri);
+        assert_eq!(metadata.update_authority(), Some(authority));
+        assert!(metadata.creators().is_empty());
+
+        // 链下 json 拉取失败只记录错误
+        let fetcher = MockFetcher(HashMap::new());
+        info.load_off_chain(&fetcher);
+        assert_eq!(info.off_chain, None);
+        assert!(info.off_chain_error.is_some());
+
+        let fetcher = MockFetcher(HashMap::from([(
+            uri.to_string(),
+            br#"{"name": "News", "image": "https://example.com/news.png", "description": "news"}"#
+                .to_vec(),
+        )]));
+        info.off_chain_error = None;
+        info.load_off_chain(&fetcher);
+        let off_chain = info.off_chain.unwrap();
+        assert_eq!(
+            off_chain.image.as_deref(),
+            Some("https://example.com/news.png")
+        );
+        assert_eq!(off_chain.description.as_deref(), Some("news"));
+    }
+}
@@ -1,41 +1,421 @@
-use mpl_token_metadata::{ instructions::TransferV1Builder};
-use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signer::Signer, system_program, sysvar};
-use mpl_token_metadata::{accounts::Metadata, instructions::MintV1Builder};
+use anyhow::{anyhow, Result};
+use mpl_token_metadata::{accounts::Metadata, instructions::TransferV1Builder};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    instruction::Instruction, pubkey::Pubkey, signer::Signer, system_program, sysvar,
+};
+use spl_token_2022::extension::{transfer_hook, StateWithExtensions};
+use spl_token_2022::offchain::{AccountDataResult, AccountFetchError};
+use spl_token_2022::state::Mint;
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+
+use super::account_extensions::{cpi_guard_enabled, memo_required, required_memo_instr};
+
+// 旧入口, 与 prepare_transfer_to_instructions 相同, hook / memo / cpi guard 的 mint 也能转账
 pub fn process_transfer_to(
+    client: &RpcClient,
     signer: &dyn Signer,
     mint_pubkey: Pubkey,
     receiver_pubkey: Pubkey,
     amount: u64,
+) -> Result<Vec<Instruction>> {
+    prepare_transfer_to_instructions(client, signer, mint_pubkey, receiver_pubkey, amount)
+}
+
+// 通过 metaplex TransferV1 从 signer 的 ata 转到 receiver 的 ata, 目标 ata 不存在时由 signer 创建
+fn metaplex_transfer_instr(
+    signer: &Pubkey,
+    mint_pubkey: &Pubkey,
+    receiver_pubkey: &Pubkey,
+    amount: u64,
 ) -> Instruction {
-    let (metadata, _) = Metadata::find_pda(&mint_pubkey);
+    let (metadata, _) = Metadata::find_pda(mint_pubkey);
     let receiver_ata = spl_associated_token_account::get_associated_token_address_with_program_id(
-        &receiver_pubkey,
-        &mint_pubkey,
+        receiver_pubkey,
+        mint_pubkey,
         &spl_token_2022::id(),
     );
     let signer_ata = spl_associated_token_account::get_associated_token_address_with_program_id(
-        &signer.pubkey(),
-        &mint_pubkey,
+        signer,
+        mint_pubkey,
         &spl_token_2022::id(),
     );
-
-
-
-
-    let transfer_to_ix = TransferV1Builder::new()
+    TransferV1Builder::new()
         .token(signer_ata)
-        .token_owner(signer.pubkey())
+        .token_owner(*signer)
         .destination_token(receiver_ata)
-        .destination_owner(receiver_pubkey)
+        .destination_owner(*receiver_pubkey)
         .metadata(metadata)
-        .mint(mint_pubkey)
+        .mint(*mint_pubkey)
         .amount(amount)
-        .authority(signer.pubkey())
-        .payer(signer.pubkey())
+        .authority(*signer)
+        .payer(*signer)
         .spl_token_program(spl_token_2022::ID)
         .spl_ata_program(spl_associated_token_account::ID)
         .system_program(system_program::ID)
         .sysvar_instructions(sysvar::instructions::ID)
-        .instruction();
-    transfer_to_ix
+        .instruction()
+}
+
+// 读取账户数据, 账户不存在时返回 None
+pub type AccountDataFetcher<'a> = &'a dyn Fn(&Pubkey) -> Result<Option<Vec<u8>>>;
+
+pub fn rpc_account_fetcher(client: &RpcClient) -> impl Fn(&Pubkey) -> Result<Option<Vec<u8>>> + '_ {
+    move |address| {
+        Ok(client
+            .get_account_with_commitment(address, client.commitment())?
+            .value
+            .map(|account| account.data))
+    }
+}
+
+// spl 的 offchain 解析是 async 的, 这里传入的读取函数都是同步完成的, poll 一次即可
+fn poll_ready<F: Future>(future: F) -> Result<F::Output> {
+    let mut future = std::pin::pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => Ok(output),
+        Poll::Pending => Err(anyhow!("account fetch did not complete")),
+    }
+}
+
+// mint 有 TransferHook 扩展时, 按 hook 程序的 ExtraAccountMetaList PDA 补全 execute 需要的账户
+// 依次追加: 解析出的额外账户, hook 程序, ExtraAccountMetaList
+pub fn add_transfer_hook_accounts(
+    instruction: &mut Instruction,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    fetch_account_data: AccountDataFetcher,
+) -> Result<()> {
+    let fetch = |address: Pubkey| -> std::future::Ready<AccountDataResult> {
+        std::future::ready(
+            fetch_account_data(&address).map_err(|e| AccountFetchError::from(e.to_string())),
+        )
+    };
+    poll_ready(spl_token_2022::offchain::add_extra_account_metas(
+        instruction,
+        source,
+        mint,
+        destination,
+        authority,
+        amount,
+        fetch,
+    ))?
+    .map_err(|e| anyhow!("failed to resolve transfer hook accounts: {}", e))
+}
+
+// 直接调用 token 2022 transfer_checked, 不经过 metaplex
+// authority 可以是 source_owner 本人, 也可以是 mint 的 permanent delegate
+pub fn transfer_checked_instr(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    source_owner: &Pubkey,
+    destination_owner: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction> {
+    let source_ata = spl_associated_token_account::get_associated_token_address_with_program_id(
+        source_owner,
+        mint,
+        &spl_token_2022::id(),
+    );
+    let destination_ata =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            destination_owner,
+            mint,
+            &spl_token_2022::id(),
+        );
+    Ok(spl_token_2022::instruction::transfer_checked(
+        &spl_token_2022::id(),
+        &source_ata,
+        mint,
+        &destination_ata,
+        authority,
+        &[],
+        amount,
+        decimals,
+    )?)
+}
+
+// 从 mint 读取 decimals 构造 transfer_checked, 有 TransferHook 时补全额外账户
+pub fn transfer_checked_with_hook_instr(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    source_owner: &Pubkey,
+    destination_owner: &Pubkey,
+    amount: u64,
+    fetch_account_data: AccountDataFetcher,
+) -> Result<Instruction> {
+    let mint_data = fetch_account_data(mint)?.ok_or_else(|| anyhow!("mint {} not found", mint))?;
+    let decimals = StateWithExtensions::<Mint>::unpack(&mint_data)?
+        .base
+        .decimals;
+    let mut instruction = transfer_checked_instr(
+        authority,
+        mint,
+        source_owner,
+        destination_owner,
+        amount,
+        decimals,
+    )?;
+    let (source, destination) = (
+        instruction.accounts[0].pubkey,
+        instruction.accounts[2].pubkey,
+    );
+    add_transfer_hook_accounts(
+        &mut instruction,
+        &source,
+        mint,
+        &destination,
+        authority,
+        amount,
+        fetch_account_data,
+    )?;
+    Ok(instruction)
+}
+
+// 目标 ata 不存在时由 payer 创建, 目标账户要求 memo 时先加一条 memo, 再转账
+pub fn prepare_transfer_checked_instructions(
+    client: &RpcClient,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    mint: &Pubkey,
+    source_owner: &Pubkey,
+    destination_owner: &Pubkey,
+    amount: u64,
+) -> Result<Vec<Instruction>> {
+    let fetch = rpc_account_fetcher(client);
+    let destination = spl_associated_token_account::get_associated_token_address_with_program_id(
+        destination_owner,
+        mint,
+        &spl_token_2022::id(),
+    );
+    let mut instructions = vec![
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            payer,
+            destination_owner,
+            mint,
+            &spl_token_2022::id(),
+        ),
+    ];
+    if let Some(destination_data) = fetch(&destination)? {
+        instructions.extend(required_memo_instr(&destination_data)?);
+    }
+    instructions.push(transfer_checked_with_hook_instr(
+        authority,
+        mint,
+        source_owner,
+        destination_owner,
+        amount,
+        &fetch,
+    )?);
+    Ok(instructions)
+}
+
+// metaplex TransferV1 通过 cpi 转账, 以下情况改为直接 transfer_checked:
+// mint 带 TransferHook (不会转发 hook 需要的账户), 目标要求 memo (cpi 内的上一条指令不是 memo),
+// source 开启了 CpiGuard
+pub fn prepare_transfer_to_instructions(
+    client: &RpcClient,
+    signer: &dyn Signer,
+    mint_pubkey: Pubkey,
+    receiver_pubkey: Pubkey,
+    amount: u64,
+) -> Result<Vec<Instruction>> {
+    let signer_pubkey = signer.pubkey();
+    let source = spl_associated_token_account::get_associated_token_address_with_program_id(
+        &signer_pubkey,
+        &mint_pubkey,
+        &spl_token_2022::id(),
+    );
+    let destination = spl_associated_token_account::get_associated_token_address_with_program_id(
+        &receiver_pubkey,
+        &mint_pubkey,
+        &spl_token_2022::id(),
+    );
+    let accounts = client.get_multiple_accounts(&[mint_pubkey, source, destination])?;
+    let [Some(mint_account), source_account, destination_account] = accounts.as_slice() else {
+        return Err(anyhow!("mint {} not found", mint_pubkey));
+    };
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_account.data)?;
+    let has_hook = transfer_hook::get_program_id(&mint).is_some();
+    let requires_memo = destination_account
+        .as_ref()
+        .map(|account| memo_required(&account.data))
+        .transpose()?
+        .unwrap_or(false);
+    let cpi_guarded = source_account
+        .as_ref()
+        .map(|account| cpi_guard_enabled(&account.data))
+        .transpose()?
+        .unwrap_or(false);
+    if !has_hook && !requires_memo && !cpi_guarded {
+        return Ok(vec![metaplex_transfer_instr(
+            &signer_pubkey,
+            &mint_pubkey,
+            &receiver_pubkey,
+            amount,
+        )]);
+    }
+    prepare_transfer_checked_instructions(
+        client,
+        &signer_pubkey,
+        &signer_pubkey,
+        &mint_pubkey,
+        &signer_pubkey,
+        &receiver_pubkey,
+        amount,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::instruction::AccountMeta;
+    use solana_sdk::program_option::COption;
+    use spl_tlv_account_resolution::{
+        account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+    };
+    use spl_token_2022::extension::transfer_hook::TransferHook;
+    use spl_token_2022::extension::{
+        BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+    };
+    use spl_transfer_hook_interface::get_extra_account_metas_address;
+    use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+    use std::collections::HashMap;
+
+    fn mint_data(hook_program_id: Option<Pubkey>) -> Vec<u8> {
+        let mut data =
+            vec![
+                0;
+                ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferHook])
+                    .unwrap()
+            ];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let hook = state.init_extension::<TransferHook>(true).unwrap();
+        hook.program_id = hook_program_id.try_into().unwrap();
+        state.base = Mint {
+            mint_authority: COption::None,
+            supply: 1_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_permanent_delegate_transfer() {
+        let (delegate, mint, holder, receiver) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ix = transfer_checked_instr(&delegate, &mint, &holder, &receiver, 100, 6).unwrap();
+        assert_eq!(ix.accounts.len(), 4);
+        assert_eq!(
+            ix.accounts[0].pubkey,
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &holder,
+                &mint,
+                &spl_token_2022::id(),
+            )
+        );
+        assert_eq!(ix.accounts[1].pubkey, mint);
+        assert_eq!(ix.accounts[3].pubkey, delegate);
+        assert!(ix.accounts[3].is_signer);
+    }
+
+    #[test]
+    fn test_transfer_hook_accounts() {
+        let (owner, mint, receiver, hook_program, extra) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        // 一个固定账户 + 一个由 source / destination 推导的 PDA
+        let extra_metas = [
+            ExtraAccountMeta::new_with_pubkey(&extra, false, false).unwrap(),
+            ExtraAccountMeta::new_with_seeds(
+                &[Seed::AccountKey { index: 0 }, Seed::AccountKey { index: 2 }],
+                false,
+                true,
+            )
+            .unwrap(),
+        ];
+        let mut validation_data =
+            vec![0; ExtraAccountMetaList::size_of(extra_metas.len()).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut validation_data, &extra_metas)
+            .unwrap();
+        let validation = get_extra_account_metas_address(&mint, &hook_program);
+        let accounts = HashMap::from([
+            (mint, mint_data(Some(hook_program))),
+            (validation, validation_data),
+        ]);
+        let fetch =
+            |address: &Pubkey| -> Result<Option<Vec<u8>>> { Ok(accounts.get(address).cloned()) };
+
+        // 没有 hook 时与 transfer_checked_instr 相同, mint 不存在时报错
+        let plain_mint = Pubkey::new_unique();
+        let plain_accounts = HashMap::from([(plain_mint, mint_data(None))]);
+        let plain_fetch = |address: &Pubkey| -> Result<Option<Vec<u8>>> {
+            Ok(plain_accounts.get(address).cloned())
+        };
+        assert_eq!(
+            transfer_checked_with_hook_instr(
+                &owner,
+                &plain_mint,
+                &owner,
+                &receiver,
+                100,
+                &plain_fetch
+            )
+            .unwrap(),
+            transfer_checked_instr(&owner, &plain_mint, &owner, &receiver, 100, 6).unwrap()
+        );
+        assert!(transfer_checked_with_hook_instr(
+            &owner,
+            &mint,
+            &owner,
+            &receiver,
+            100,
+            &plain_fetch
+        )
+        .is_err());
+
+        let ix = transfer_checked_with_hook_instr(&owner, &mint, &owner, &receiver, 100, &fetch)
+            .unwrap();
+        let (source, destination) = (ix.accounts[0].pubkey, ix.accounts[2].pubkey);
+        let (pda, _) =
+            Pubkey::find_program_address(&[source.as_ref(), destination.as_ref()], &hook_program);
+        assert_eq!(
+            ix.accounts[4..],
+            [
+                AccountMeta::new_readonly(extra, false),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(hook_program, false),
+                AccountMeta::new_readonly(validation, false),
+            ]
+        );
+
+        // ExtraAccountMetaList 不存在时报错
+        let accounts = HashMap::from([(mint, mint_data(Some(hook_program)))]);
+        let fetch =
+            |address: &Pubkey| -> Result<Option<Vec<u8>>> { Ok(accounts.get(address).cloned()) };
+        assert!(
+            transfer_checked_with_hook_instr(&owner, &mint, &owner, &receiver, 100, &fetch)
+                .is_err()
+        );
+    }
 }
@@ -1,22 +1,77 @@
+use anyhow::Result;
 use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signer::Signer};
 
+use crate::utils::batch_instructions;
+
+// 每个 holder 只多出一个 ata 账户, 一笔交易可以解冻多个
+pub const THAW_BATCH_SIZE: usize = 20;
+
 pub fn process_unfreeze_account(
     signer: &dyn Signer,
     mint_pubkey: Pubkey,
     receiver_pubkey: Pubkey,
 ) -> Instruction {
+    thaw_account_instr(&signer.pubkey(), &mint_pubkey, &receiver_pubkey).unwrap()
+}
+
+// 解冻 owner 的 token 2022 ata, 需要 freeze authority 签名
+pub fn thaw_account_instr(
+    freeze_authority: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction> {
     let receiver_ata = spl_associated_token_account::get_associated_token_address_with_program_id(
-        &receiver_pubkey,
-        &mint_pubkey,
+        owner,
+        mint,
         &spl_token_2022::id(),
     );
-    let unfreeze_ix = spl_token_2022::instruction::thaw_account(
+    Ok(spl_token_2022::instruction::thaw_account(
         &spl_token_2022::ID,
         &receiver_ata,
-        &mint_pubkey,
-        &signer.pubkey(),
-        &[&signer.pubkey()],
-    )
-    .unwrap();
-    unfreeze_ix
+        mint,
+        freeze_authority,
+        &[],
+    )?)
+}
+
+// DefaultAccountState=Frozen 的 mint 审核通过后批量解冻, 只需要 freeze authority 签名
+pub fn prepare_thaw_accounts_transactions(
+    freeze_authority: &Pubkey,
+    mint: &Pubkey,
+    owners: &[Pubkey],
+    batch_size: usize,
+) -> Result<Vec<Vec<Instruction>>> {
+    batch_instructions(owners, batch_size, |owner| {
+        thaw_account_instr(freeze_authority, mint, owner)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thaw_accounts_transactions() {
+        let (freeze_authority, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let owners: Vec<Pubkey> = (0..45).map(|_| Pubkey::new_unique()).collect();
+        let transactions =
+            prepare_thaw_accounts_transactions(&freeze_authority, &mint, &owners, THAW_BATCH_SIZE)
+                .unwrap();
+        assert_eq!(
+            transactions.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![20, 20, 5]
+        );
+        let ix = &transactions[2][4];
+        assert_eq!(
+            ix.accounts[0].pubkey,
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &owners[44],
+                &mint,
+                &spl_token_2022::id(),
+            )
+        );
+        assert_eq!(ix.accounts[2].pubkey, freeze_authority);
+        assert!(ix.accounts[2].is_signer);
+        assert!(prepare_thaw_accounts_transactions(&freeze_authority, &mint, &owners, 0).is_err());
+    }
 }
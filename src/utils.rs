@@ -1,7 +1,57 @@
-use solana_program::pubkey::Pubkey;
+use anyhow::{anyhow, Result};
+use solana_program::{instruction::Instruction, pubkey::Pubkey, system_instruction};
 use spl_associated_token_account::get_associated_token_address_with_program_id;
 use std::str::FromStr;
 
+pub const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
+pub const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+// 把 amount lamports 包装成 wsol: 创建 wsol ata (已存在时跳过) -> 转入 SOL -> sync_native
+pub fn wrap_sol_instructions(owner: &Pubkey, amount: u64) -> Result<Vec<Instruction>> {
+    let wsol_mint = Pubkey::from_str(WSOL_MINT)?;
+    let token_program = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?;
+    let wsol_ata = get_associated_token_address_with_program_id(owner, &wsol_mint, &token_program);
+    Ok(vec![
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            owner,
+            owner,
+            &wsol_mint,
+            &token_program,
+        ),
+        system_instruction::transfer(owner, &wsol_ata, amount),
+        spl_token_2022::instruction::sync_native(&token_program, &wsol_ata)?,
+    ])
+}
+
+// 关闭 wsol ata, 剩余的 wsol 和租金全部退回 owner
+pub fn unwrap_sol_instruction(owner: &Pubkey) -> Result<Instruction> {
+    let wsol_mint = Pubkey::from_str(WSOL_MINT)?;
+    let token_program = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?;
+    let wsol_ata = get_associated_token_address_with_program_id(owner, &wsol_mint, &token_program);
+    Ok(spl_token_2022::instruction::close_account(
+        &token_program,
+        &wsol_ata,
+        owner,
+        owner,
+        &[],
+    )?)
+}
+
+// 每个 item 一条指令, 按 batch_size 分组, 每组为一笔交易
+pub fn batch_instructions<T>(
+    items: &[T],
+    batch_size: usize,
+    mut instr: impl FnMut(&T) -> Result<Instruction>,
+) -> Result<Vec<Vec<Instruction>>> {
+    if batch_size == 0 {
+        return Err(anyhow!("batch size must be greater than 0"));
+    }
+    items
+        .chunks(batch_size)
+        .map(|chunk| chunk.iter().map(&mut instr).collect())
+        .collect()
+}
+
 #[test]
 fn test_gengrate_mint_acount_address() {
     // 需要计算出 代币铸币账户的 ata 地址 、 债券曲线账户的地址 以及 关联债券曲线账户的地址